@@ -0,0 +1,84 @@
+// Measures what running from pre-decoded blocks saves, by running loops
+// with the interpreter (Core::execute) and the block cache
+// (BlockCache::execute): the register-only loop of benches/jit.rs, and
+// one using memory operands with extension words. Both fetch opcodes
+// through the bus, so a bus as cheap as FlatMem is used to leave that out.
+//
+// Run with: cargo bench -p r68k-emu --bench blockcache
+extern crate r68k_emu;
+
+use std::time::Instant;
+use r68k_emu::cpu::Core;
+use r68k_emu::cpu::blockcache::BlockCache;
+use r68k_emu::ram::flatmem::FlatMem;
+use r68k_emu::ram::tracking::WriteTracker;
+
+// ADD.L D1, D0; SUB.W D2, D3; EOR.L D0, D5; AND.B D4, D6; MOVE.L D5, D7;
+// ADDQ.L #1, D4; CMP.L D0, D3; BNE.S back to the add; BRA.S back to the add
+const ALU_LOOP: [u8; 20] = [0xd0, 0x81, 0x96, 0x42, 0xb1, 0x85, 0xcc, 0x04, 0x2e, 0x05,
+                            0x52, 0x84, 0xb6, 0x80, 0x66, 0xf0, 0x60, 0xee, 0x4e, 0x71];
+// MOVE.L 16(A0), D1; ADDI.W #$1234, D1; MOVE.L D1, 32(A0);
+// ADD.L 4(A0,D3.W), D0; BRA.S back to the first move
+const MEMORY_LOOP: [u8; 18] = [0x22, 0x28, 0x00, 0x10, 0x06, 0x41, 0x12, 0x34, 0x21, 0x41,
+                               0x00, 0x20, 0xd0, 0xb0, 0x30, 0x04, 0x60, 0xee];
+const CYCLES: i32 = 80_000_000;
+// as often as a machine would interrupt the core, say
+const QUANTUM: i32 = 10_000;
+
+fn core(program: &[u8]) -> Core<FlatMem> {
+    let mut mem = FlatMem::new(0);
+    mem.load(0x1000, program);
+    let mut core = Core::new_with_bus(0x1000, mem);
+    core.dar[1] = 0x12345;
+    core.dar[2] = 0x54321;
+    core.dar[8] = 0x4000;
+    core
+}
+
+const ROUNDS: usize = 5;
+
+fn millis_since(started: Instant) -> f64 {
+    let elapsed = started.elapsed();
+    elapsed.as_secs() as f64 * 1e3 + elapsed.subsec_nanos() as f64 / 1e6
+}
+
+fn run_interpreted(program: &[u8]) -> (f64, Core<FlatMem>) {
+    let mut core = core(program);
+    let started = Instant::now();
+    for _ in 0..CYCLES / QUANTUM {
+        core.execute(QUANTUM);
+    }
+    (millis_since(started), core)
+}
+
+fn run_cached(program: &[u8]) -> (f64, Core<FlatMem>) {
+    let mut core = core(program).map_bus(WriteTracker::new);
+    let mut cache = BlockCache::new();
+    let started = Instant::now();
+    for _ in 0..CYCLES / QUANTUM {
+        cache.execute(&mut core, QUANTUM);
+    }
+    (millis_since(started), core.map_bus(WriteTracker::into_inner))
+}
+
+fn bench(name: &str, program: &[u8]) {
+    // best of several alternating rounds, to keep noise down
+    let (mut interpreted_best, mut cached_best) = (f64::MAX, f64::MAX);
+    for _ in 0..ROUNDS {
+        let (interpreted_millis, interpreted) = run_interpreted(program);
+        let (cached_millis, cached) = run_cached(program);
+        assert_eq!(interpreted.dar, cached.dar);
+        assert_eq!(interpreted.elapsed_cycles, cached.elapsed_cycles);
+        interpreted_best = interpreted_best.min(interpreted_millis);
+        cached_best = cached_best.min(cached_millis);
+    }
+    println!("{}:", name);
+    println!("  interpreted: {:8.1} ms for {} cycles", interpreted_best, CYCLES);
+    println!("       cached: {:8.1} ms for {} cycles", cached_best, CYCLES);
+    println!("     speed-up: {:8.1} x", interpreted_best / cached_best);
+}
+
+fn main() {
+    bench("registers", &ALU_LOOP);
+    bench("memory operands", &MEMORY_LOOP);
+}
@@ -0,0 +1,72 @@
+// Measures what translating to host code saves, by running an
+// arithmetic and logic loop with the interpreter (Core::execute) and
+// the dynamic recompiler (Jit::execute). Both fetch opcodes through the
+// bus, so a bus as cheap as FlatMem is used to leave that out.
+//
+// Run with: cargo bench -p r68k-emu --features jit --bench jit
+extern crate r68k_emu;
+
+use std::time::Instant;
+use r68k_emu::cpu::Core;
+use r68k_emu::cpu::jit::Jit;
+use r68k_emu::ram::flatmem::FlatMem;
+use r68k_emu::ram::tracking::WriteTracker;
+
+// ADD.L D1, D0; SUB.W D2, D3; EOR.L D0, D5; AND.B D4, D6; MOVE.L D5, D7;
+// ADDQ.L #1, D4; CMP.L D0, D3; BNE.S back to the add; BRA.S back to the add
+const ALU_LOOP: [u8; 20] = [0xd0, 0x81, 0x96, 0x42, 0xb1, 0x85, 0xcc, 0x04, 0x2e, 0x05,
+                            0x52, 0x84, 0xb6, 0x80, 0x66, 0xf0, 0x60, 0xee, 0x4e, 0x71];
+const CYCLES: i32 = 80_000_000;
+// as often as a machine would interrupt the core, say
+const QUANTUM: i32 = 10_000;
+
+fn core() -> Core<FlatMem> {
+    let mut mem = FlatMem::new(0);
+    mem.load(0x1000, &ALU_LOOP);
+    let mut core = Core::new_with_bus(0x1000, mem);
+    core.dar[1] = 0x12345;
+    core.dar[2] = 0x54321;
+    core
+}
+
+const ROUNDS: usize = 5;
+
+fn millis_since(started: Instant) -> f64 {
+    let elapsed = started.elapsed();
+    elapsed.as_secs() as f64 * 1e3 + elapsed.subsec_nanos() as f64 / 1e6
+}
+
+fn run_interpreted() -> (f64, Core<FlatMem>) {
+    let mut core = core();
+    let started = Instant::now();
+    for _ in 0..CYCLES / QUANTUM {
+        core.execute(QUANTUM);
+    }
+    (millis_since(started), core)
+}
+
+fn run_translated() -> (f64, Core<FlatMem>) {
+    let mut core = core().map_bus(WriteTracker::new);
+    let mut jit = Jit::new();
+    let started = Instant::now();
+    for _ in 0..CYCLES / QUANTUM {
+        jit.execute(&mut core, QUANTUM);
+    }
+    (millis_since(started), core.map_bus(WriteTracker::into_inner))
+}
+
+fn main() {
+    // best of several alternating rounds, to keep noise down
+    let (mut interpreted_best, mut translated_best) = (f64::MAX, f64::MAX);
+    for _ in 0..ROUNDS {
+        let (interpreted_millis, interpreted) = run_interpreted();
+        let (translated_millis, translated) = run_translated();
+        assert_eq!(interpreted.dar, translated.dar);
+        assert_eq!(interpreted.elapsed_cycles, translated.elapsed_cycles);
+        interpreted_best = interpreted_best.min(interpreted_millis);
+        translated_best = translated_best.min(translated_millis);
+    }
+    println!("interpreted: {:8.1} ms for {} cycles", interpreted_best, CYCLES);
+    println!(" translated: {:8.1} ms for {} cycles", translated_best, CYCLES);
+    println!("    speed-up: {:8.1} x", interpreted_best / translated_best);
+}
@@ -0,0 +1,82 @@
+// Compares the buses, by running a loop of memory to memory moves and
+// read-modify-writes on each of them.
+//
+// Run with: cargo bench -p r68k-emu --bench memory
+extern crate r68k_emu;
+
+use std::time::Instant;
+use r68k_emu::cpu::Core;
+use r68k_emu::ram::{AddressBus, SUPERVISOR_DATA};
+use r68k_emu::ram::flatmem::FlatMem;
+use r68k_emu::ram::loggingmem::{LoggingMem, OpsLogger};
+use r68k_emu::ram::pagedmem::PagedMem;
+
+// MOVE.L (A0), (A1)+; ADDQ.L #1, (A0); CMPA.L A0, A1; BNE.S back to the
+// move; MOVEA.L A2, A1; BRA.S back to the move
+const MEMORY_LOOP: [u8; 12] = [0x22, 0xd0, 0x52, 0x90, 0xb3, 0xc8, 0x66, 0xf8, 0x22, 0x4a, 0x60, 0xf4];
+// LoggingMem keeps every access, so it gets fewer cycles
+const CYCLES: i32 = 40_000_000;
+const LOGGED_CYCLES: i32 = 4_000_000;
+
+fn core<A: AddressBus>(mut mem: A) -> Core<A> {
+    for (offset, byte) in MEMORY_LOOP.iter().enumerate() {
+        mem.write_byte(SUPERVISOR_DATA, 0x1000 + offset as u32, *byte as u32);
+    }
+    let mut core = Core::new_with_bus(0x1000, mem);
+    core.dar[8] = 0x8000;
+    core.dar[9] = 0x4000;
+    core.dar[10] = 0x4000;
+    core
+}
+
+const ROUNDS: usize = 5;
+
+fn millis_since(started: Instant) -> f64 {
+    let elapsed = started.elapsed();
+    elapsed.as_secs() as f64 * 1e3 + elapsed.subsec_nanos() as f64 / 1e6
+}
+
+// the best of several rounds, in ms per million cycles
+fn best<A: AddressBus, F: Fn() -> A>(mem: F, cycles: i32) -> (f64, Core<A>) {
+    let mut best = (f64::MAX, None);
+    for _ in 0..ROUNDS {
+        let mut core = core(mem());
+        let started = Instant::now();
+        core.execute(cycles);
+        let millis = millis_since(started) * 1e6 / cycles as f64;
+        if millis < best.0 {
+            best = (millis, Some(core));
+        }
+    }
+    (best.0, best.1.unwrap())
+}
+
+// loading an image of this size, byte by byte and as a block, in ms
+const IMAGE_SIZE: usize = 0x100000;
+
+fn load<F: Fn(&mut FlatMem, &[u8])>(load: F) -> f64 {
+    let image: Vec<u8> = (0..IMAGE_SIZE).map(|byte| byte as u8).collect();
+    let mut mem = FlatMem::new(0);
+    (0..ROUNDS).map(|_| {
+        let started = Instant::now();
+        load(&mut mem, &image);
+        millis_since(started)
+    }).fold(f64::MAX, f64::min)
+}
+
+fn main() {
+    let (flat, flat_core) = best(|| FlatMem::new(0), CYCLES);
+    let (paged, paged_core) = best(|| PagedMem::new(0), CYCLES);
+    let (logged, _) = best(|| LoggingMem::new(0, OpsLogger::new()), LOGGED_CYCLES);
+    assert_eq!(paged_core.dar, flat_core.dar);
+    assert_eq!(paged_core.mem.read_long(SUPERVISOR_DATA, 0x4000), flat_core.mem.read_long(SUPERVISOR_DATA, 0x4000));
+    println!("   FlatMem: {:8.2} ms per million cycles", flat);
+    println!("  PagedMem: {:8.2} ms per million cycles", paged);
+    println!("LoggingMem: {:8.2} ms per million cycles", logged);
+    println!("   speedup: {:8.1}x over PagedMem, {:.1}x over LoggingMem", paged / flat, logged / flat);
+    let bytewise = load(|mem, image| for (offset, &byte) in image.iter().enumerate() {
+        mem.write_byte(SUPERVISOR_DATA, offset as u32, byte as u32);
+    });
+    let block = load(|mem, image| mem.write_bytes(SUPERVISOR_DATA, 0, image));
+    println!("loading 1 MB: {:.3} ms byte by byte, {:.3} ms as a block", bytewise, block);
+}
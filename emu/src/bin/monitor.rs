@@ -0,0 +1,451 @@
+// A command-line monitor for a core, in the style of TUTOR and MacsBug.
+//
+//     monitor image.bin [load address]
+//
+// loads a raw image into memory at the load address (0 by default). An
+// image loaded at 0 is expected to start with the reset vectors, and
+// the core is reset from them; otherwise execution starts at the load
+// address. Type help at the prompt for the commands. Numbers are hex,
+// with or without a leading $.
+extern crate r68k_emu;
+extern crate r68k_tools;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
+use r68k_emu::cpu::{Core, Breakpoint, StopConditions, StopReason, Symbols};
+use r68k_emu::ram::flatmem::FlatMem;
+use r68k_tools::assembler::{Assembler, encode_instruction};
+use r68k_tools::disassembler::disassemble;
+use r68k_tools::memory::{Memory, MemoryVec};
+
+// go gives up after this many cycles, about ten seconds of an 8 MHz 68000
+const GO_LIMIT: u64 = 80_000_000;
+
+const HELP: &str = "\
+r                      show registers, and the instruction at PC
+r <reg> <value>        set D0-D7, A0-A7, PC, SR, USP or SSP
+di [addr] [count]      disassemble, at PC by default
+t [count]              trace (single step) instructions
+so                     step over a JSR or BSR, or trace anything else
+g [addr]               go, until a breakpoint, STOP or halt
+br [addr [condition]]  list breakpoints, or add one, like br 1000 D0 == 3
+nobr [id]              remove a breakpoint, or all of them
+dm <addr> [length]     dump memory
+mm <addr> <byte>...    modify memory
+as <addr> <line>       assemble a line in place, like as 1000 ADD.B #3,D0
+irq <level>            raise an interrupt, level 1 to 7
+bt                     backtrace of the calls and exceptions taken
+q                      quit
+";
+
+struct Monitor {
+    core: Core<FlatMem>,
+    assembler: Assembler,
+    // where di continues from
+    next_disassembly: u32,
+}
+
+impl Monitor {
+    fn new(mut core: Core<FlatMem>) -> Monitor {
+        core.call_stack.enable();
+        let pc = core.pc;
+        Monitor { core, assembler: Assembler::new(), next_disassembly: pc }
+    }
+
+    // The output of a command line, or None to quit
+    fn command(&mut self, line: &str) -> Option<String> {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command.to_lowercase(),
+            None => return Some(String::new()),
+        };
+        let args: Vec<&str> = words.collect();
+        let output = match command.as_str() {
+            "r" if args.is_empty() => Ok(self.registers()),
+            "r" => self.set_register(&args),
+            "di" => self.disassemble_command(&args),
+            "t" => self.trace(&args),
+            "so" => Ok(self.step_over()),
+            "g" => self.go(&args),
+            "br" => self.breakpoint(line, &args),
+            "nobr" => self.remove_breakpoint(&args),
+            "dm" => self.dump(&args),
+            "mm" => self.modify(&args),
+            "as" => self.assemble(line, &args),
+            "irq" => self.interrupt(&args),
+            "bt" => Ok(self.core.backtrace(&Symbols::new())),
+            "help" | "h" | "?" => Ok(HELP.to_string()),
+            "q" | "quit" => return None,
+            _ => Err(format!("unknown command {}, try help", command)),
+        };
+        Some(output.unwrap_or_else(|error| format!("error: {}\n", error)))
+    }
+
+    fn registers(&mut self) -> String {
+        let core = &self.core;
+        let row = |first: usize, name: char| (0..8)
+            .map(|n| format!("{}{} {:08x}", name, n, core.dar[first + n]))
+            .collect::<Vec<_>>().join(" ");
+        let mut output = format!("{}\n{}\n", row(0, 'D'), row(8, 'A'));
+        output.push_str(&format!("PC {:08x} SR {:04x} {} USP {:08x} SSP {:08x}\n",
+            core.pc, core.status_register(), core.flags(), core.usp(), core.ssp()));
+        let pc = core.pc;
+        output.push_str(&self.disassemble(pc, 1));
+        output
+    }
+
+    fn set_register(&mut self, args: &[&str]) -> Result<String, String> {
+        if args.len() != 2 {
+            return Err("r <reg> <value>".to_string());
+        }
+        let value = number(args[1])?;
+        let name = args[0].to_uppercase();
+        let index = |prefix: char| name.strip_prefix(prefix).and_then(|n| n.parse::<usize>().ok()).filter(|&n| n < 8);
+        match name.as_str() {
+            "PC" => self.core.jump(value),
+            "SR" => self.core.sr_to_flags(value as u16),
+            "USP" if self.core.s_flag != 0 => self.core.inactive_usp = value,
+            "SSP" if self.core.s_flag == 0 => self.core.inactive_ssp = value,
+            "USP" | "SSP" | "SP" => self.core.dar[15] = value,
+            _ => match (index('D'), index('A')) {
+                (Some(n), _) => self.core.dar[n] = value,
+                (_, Some(n)) => self.core.dar[8 + n] = value,
+                _ => return Err(format!("unknown register {}", args[0])),
+            }
+        }
+        Ok(self.registers())
+    }
+
+    // One instruction per line, as address, words and instruction.
+    // What the disassembler doesn't know yet shows as DC.W.
+    fn disassemble(&mut self, mut pc: u32, count: u32) -> String {
+        let mut output = String::new();
+        for _ in 0..count {
+            let bytes = (0..10).map(|offset| self.core.mem.read_u8(pc.wrapping_add(offset)) as u8).collect();
+            let mem = MemoryVec::new8(pc, bytes);
+            let (text, words) = match disassemble(pc, &mem) {
+                Ok(instruction) => (format!("{}", instruction), instruction.length()),
+                Err(_) => (format!("DC.W\t${:04x}", mem.read_word(pc)), 1),
+            };
+            let hex: Vec<String> = (0..words).map(|word| format!("{:04x}", mem.read_word(pc + 2 * word))).collect();
+            let marker = if self.core.breakpoints.iter().any(|(_, breakpoint)| breakpoint.pc == pc) { '*' } else { ' ' };
+            output.push_str(&format!("{}{:08x}  {:<20} {}\n", marker, pc, hex.join(" "), text));
+            pc = pc.wrapping_add(2 * words);
+        }
+        self.next_disassembly = pc;
+        output
+    }
+
+    fn disassemble_command(&mut self, args: &[&str]) -> Result<String, String> {
+        let pc = match args.first() {
+            Some(arg) => number(arg)?,
+            None => self.next_disassembly,
+        };
+        let count = match args.get(1) {
+            Some(arg) => number(arg)?,
+            None => 8,
+        };
+        Ok(self.disassemble(pc, count))
+    }
+
+    fn trace(&mut self, args: &[&str]) -> Result<String, String> {
+        let count = match args.first() {
+            Some(arg) => number(arg)? as u64,
+            None => 1,
+        };
+        let reason = self.core.run_instructions(count);
+        Ok(self.stopped(reason))
+    }
+
+    // Runs a JSR or BSR until it returns to the next instruction
+    fn step_over(&mut self) -> String {
+        let pc = self.core.pc;
+        let opcode = self.core.mem.read_u8(pc) << 8 | self.core.mem.read_u8(pc.wrapping_add(1));
+        let length = if opcode & 0xff00 == 0x6100 {
+            // BSR, with an 8 or 16 bit displacement
+            if opcode & 0xff == 0 { 4 } else { 2 }
+        } else if opcode & 0xffc0 == 0x4e80 {
+            // JSR, with the extension words of its effective address
+            match (opcode >> 3) & 7 {
+                2 => 2,
+                5 | 6 => 4,
+                _ => match opcode & 7 {
+                    1 => 6,
+                    _ => 4,
+                },
+            }
+        } else {
+            let reason = self.core.run_instructions(1);
+            return self.stopped(reason);
+        };
+        let conditions = StopConditions { pc: Some(pc.wrapping_add(length)), deadline: Some(self.core.elapsed_cycles + GO_LIMIT), ..Default::default() };
+        let reason = self.core.run_until(&conditions);
+        self.stopped(reason)
+    }
+
+    fn go(&mut self, args: &[&str]) -> Result<String, String> {
+        if let Some(arg) = args.first() {
+            let pc = number(arg)?;
+            self.core.jump(pc);
+        }
+        let reason = self.core.run_until_cycle(self.core.elapsed_cycles + GO_LIMIT);
+        Ok(self.stopped(reason))
+    }
+
+    fn stopped(&mut self, reason: StopReason) -> String {
+        let why = match reason {
+            StopReason::Breakpoint(id) => format!("breakpoint {}\n", id),
+            StopReason::Stopped => "stopped, waiting for an interrupt\n".to_string(),
+            StopReason::Halted => "halted\n".to_string(),
+            StopReason::Deadline(_) => format!("still running after {} cycles\n", GO_LIMIT),
+            _ => String::new(),
+        };
+        why + &self.registers()
+    }
+
+    fn breakpoint(&mut self, line: &str, args: &[&str]) -> Result<String, String> {
+        if args.is_empty() {
+            let listing = self.core.breakpoints.iter().map(|(id, breakpoint)| {
+                let conditional = if breakpoint.condition.is_some() { " conditional" } else { "" };
+                format!("{:>3} {:08x} hits {}{}\n", id, breakpoint.pc, breakpoint.hits, conditional)
+            });
+            return Ok(listing.collect());
+        }
+        let pc = number(args[0])?;
+        let breakpoint = if args.len() > 1 {
+            let condition = rest_of(line, 2);
+            Breakpoint::conditional(pc, condition).ok_or_else(|| format!("cannot parse condition {}", condition))?
+        } else {
+            Breakpoint::new(pc)
+        };
+        let id = self.core.breakpoints.add(breakpoint);
+        Ok(format!("breakpoint {} at {:08x}\n", id, pc))
+    }
+
+    fn remove_breakpoint(&mut self, args: &[&str]) -> Result<String, String> {
+        match args.first() {
+            Some(arg) => {
+                let id = number(arg)? as usize;
+                self.core.breakpoints.remove(id).ok_or_else(|| format!("no breakpoint {}", id))?;
+            },
+            None => self.core.breakpoints.clear(),
+        }
+        Ok(String::new())
+    }
+
+    fn dump(&mut self, args: &[&str]) -> Result<String, String> {
+        let start = number(args.first().ok_or("dm <addr> [length]")?)?;
+        let length = match args.get(1) {
+            Some(arg) => number(arg)?,
+            None => 0x40,
+        };
+        let mut output = String::new();
+        for line in (0..length).step_by(16) {
+            let address = start.wrapping_add(line);
+            let bytes: Vec<u8> = (0..16.min(length - line)).map(|offset| self.core.mem.read_u8(address.wrapping_add(offset)) as u8).collect();
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            let text: String = bytes.iter().map(|&byte| if (0x20..0x7f).contains(&byte) { byte as char } else { '.' }).collect();
+            output.push_str(&format!("{:08x}  {:<48} {}\n", address, hex.join(" "), text));
+        }
+        Ok(output)
+    }
+
+    fn modify(&mut self, args: &[&str]) -> Result<String, String> {
+        if args.len() < 2 {
+            return Err("mm <addr> <byte>...".to_string());
+        }
+        let address = number(args[0])?;
+        let bytes = args[1..].iter().map(|arg| number(arg)).collect::<Result<Vec<u32>, String>>()?;
+        self.write(address, bytes.iter().map(|&byte| byte as u8));
+        let length = format!("{:x}", bytes.len());
+        self.dump(&[args[0], &length])
+    }
+
+    fn write<I: Iterator<Item = u8>>(&mut self, address: u32, bytes: I) {
+        for (offset, byte) in bytes.enumerate() {
+            self.core.mem.write_u8(address.wrapping_add(offset as u32), byte as u32);
+        }
+        // what was prefetched may have been overwritten
+        self.core.prefetch_addr = 1;
+    }
+
+    fn assemble(&mut self, line: &str, args: &[&str]) -> Result<String, String> {
+        if args.len() < 2 {
+            return Err("as <addr> <line>".to_string());
+        }
+        let address = number(args[0])?;
+        // the assembler wants the leading space of an unlabelled line
+        let source = format!(" {}", rest_of(line, 2));
+        let assembler = &self.assembler;
+        // and panics on what it cannot parse or encode
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| ()));
+        let assembled = panic::catch_unwind(AssertUnwindSafe(|| {
+            let instruction = assembler.parse_assembler(&source);
+            let mut mem = MemoryVec::new();
+            encode_instruction(&source, &instruction, address, &mut mem);
+            mem.data().to_vec()
+        }));
+        panic::set_hook(hook);
+        let bytes = assembled.map_err(|_| format!("cannot assemble{}", source))?;
+        self.write(address, bytes.into_iter());
+        Ok(self.disassemble(address, 1))
+    }
+
+    fn interrupt(&mut self, args: &[&str]) -> Result<String, String> {
+        let level = number(args.first().ok_or("irq <level>")?)?;
+        if !(1..=7).contains(&level) {
+            return Err("the level must be 1 to 7".to_string());
+        }
+        self.core.int_ctrl.request_interrupt(level as u8);
+        Ok(format!("interrupt level {} requested\n", level))
+    }
+}
+
+fn number(arg: &str) -> Result<u32, String> {
+    let digits = arg.strip_prefix('$').unwrap_or(arg);
+    u32::from_str_radix(digits, 16).map_err(|_| format!("{} is not a hex number", arg))
+}
+
+// The line after its first words, like the condition of a breakpoint
+fn rest_of(line: &str, words: usize) -> &str {
+    let mut rest = line.trim_start();
+    for _ in 0..words {
+        rest = rest.trim_start_matches(|c: char| !c.is_whitespace()).trim_start();
+    }
+    rest.trim_end()
+}
+
+fn load(path: &str, address: u32) -> io::Result<Core<FlatMem>> {
+    let mut image = Vec::new();
+    File::open(path)?.read_to_end(&mut image)?;
+    let mut mem = FlatMem::new(0);
+    mem.load(address, &image);
+    // images at 0 start with their reset vectors
+    Ok(if address == 0 { Core::power_on(mem) } else { Core::new_with_bus(address, mem) })
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 || args.len() > 3 {
+        eprintln!("usage: {} <image> [load address]", args[0]);
+        std::process::exit(2);
+    }
+    let address = match args.get(2).map(|arg| number(arg)) {
+        Some(Ok(address)) => address,
+        Some(Err(error)) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        },
+        None => 0,
+    };
+    let core = match load(&args[1], address) {
+        Ok(core) => core,
+        Err(error) => {
+            eprintln!("cannot load {}: {}", args[1], error);
+            std::process::exit(1);
+        },
+    };
+    let mut monitor = Monitor::new(core);
+    print!("{}", monitor.registers());
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        match monitor.command(&line) {
+            Some(output) => print!("{}", output),
+            None => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Monitor;
+    use r68k_emu::cpu::Core;
+    use r68k_emu::ram::flatmem::FlatMem;
+
+    // ADD.B #3, D0; BSR.S to the ADD.B D0, D1 after the BRA.S back
+    const PROGRAM: [u8; 12] = [0x06, 0x00, 0x00, 0x03, 0x61, 0x02, 0x60, 0xf8, 0xd2, 0x00, 0x4e, 0x75];
+
+    fn monitor() -> Monitor {
+        let mut mem = FlatMem::new(0);
+        for (offset, byte) in PROGRAM.iter().enumerate() {
+            mem.write_u8(0x1000 + offset as u32, *byte as u32);
+        }
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x8000;
+        Monitor::new(core)
+    }
+
+    fn run(monitor: &mut Monitor, line: &str) -> String {
+        monitor.command(line).unwrap()
+    }
+
+    #[test]
+    fn shows_registers_and_the_instruction_at_pc() {
+        let mut monitor = monitor();
+        let output = run(&mut monitor, "r d2 $1234");
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].contains("D2 00001234"));
+        assert!(lines[1].contains("A7 00008000"));
+        assert!(lines[2].starts_with("PC 00001000 SR 2700 -S7-----"));
+        assert_eq!(" 00001000  0600 0003            ADDI.B\t#$03,D0", lines[3]);
+    }
+
+    #[test]
+    fn steps_over_subroutine_calls() {
+        let mut monitor = monitor();
+        run(&mut monitor, "t");
+        assert_eq!((0x1004, 3), (monitor.core.pc, monitor.core.dar[0]));
+        run(&mut monitor, "t");
+        assert_eq!("#0  00001008 00001008\n#1  00001004 00001004\n", run(&mut monitor, "bt"));
+        run(&mut monitor, "t 4");
+        assert_eq!((0x1004, 3), (monitor.core.pc, monitor.core.dar[1]));
+        run(&mut monitor, "so");
+        assert_eq!((0x1006, 9), (monitor.core.pc, monitor.core.dar[1]));
+        run(&mut monitor, "t 2");
+        assert_eq!(0x1004, monitor.core.pc);
+    }
+
+    #[test]
+    fn goes_until_a_breakpoint() {
+        let mut monitor = monitor();
+        assert_eq!("breakpoint 0 at 00001008\n", run(&mut monitor, "br 1008 D0 == 9"));
+        let output = run(&mut monitor, "g");
+        assert!(output.starts_with("breakpoint 0\n"));
+        assert_eq!((0x1008, 9), (monitor.core.pc, monitor.core.dar[0]));
+        assert!(run(&mut monitor, "br").contains("  0 00001008 hits 1 conditional"));
+        run(&mut monitor, "nobr");
+        assert!(monitor.core.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn memory_can_be_modified_and_assembled_into() {
+        let mut monitor = monitor();
+        let output = run(&mut monitor, "mm 2000 41 42 0");
+        assert_eq!("00002000  41 42 00                                         AB.\n", output);
+        let output = run(&mut monitor, "as 1008 ADD.B D0,(A1)");
+        assert!(output.contains("d111") && output.contains("ADD.B\tD0,(A1)"));
+        assert_eq!(0xd1, monitor.core.mem.read_u8(0x1008));
+        assert!(run(&mut monitor, "as 1008 NOT AN INSTRUCTION").starts_with("error: cannot assemble"));
+        assert_eq!(0xd1, monitor.core.mem.read_u8(0x1008));
+    }
+
+    #[test]
+    fn interrupts_can_be_raised() {
+        let mut monitor = monitor();
+        run(&mut monitor, "r sr 2000");
+        run(&mut monitor, "irq 3");
+        run(&mut monitor, "t");
+        // taken through the autovector, which is 0 in empty memory
+        assert_eq!(0x0300, monitor.core.status_register() & 0x0700);
+        assert!(run(&mut monitor, "irq 8").starts_with("error"));
+    }
+}
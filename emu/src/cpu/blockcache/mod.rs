@@ -0,0 +1,385 @@
+// Interpreter mode running from a cache of pre-decoded blocks.
+//
+// Straight-line runs of instructions are recorded the first time they are
+// interpreted. For each instruction the block keeps the opcode and its
+// handler, and for the most common forms the instruction with its
+// operands decoded, extension words included (see predecoded), which is
+// run instead of the handler. Fetches still go through the prefetch and
+// the bus as usual, extension words included, so bus accesses and cycle
+// counts are identical to the plain interpreter. So are breakpoints and
+// watchpoints, checked at every instruction boundary.
+//
+// Blocks are kept in a ram::tracking::CodeBlocks, so the bus has to
+// implement CodeWrites.
+use cpu::{Core, Cycles, Handler, Callbacks, EmulateAllExceptions};
+use ram::{AddressBus, SUPERVISOR_PROGRAM, USER_PROGRAM};
+use ram::tracking::{CodeWrites, CodeBlocks, MAX_INSTRUCTION_BYTES, watch_instruction};
+use ram::watching::WatchHit;
+
+mod predecoded;
+use self::predecoded::{Run, Operands};
+
+const MAX_BLOCK_INSTRUCTIONS: usize = 64;
+
+struct Decoded<A: AddressBus> {
+    pc: u32,
+    opcode: u16,
+    handler: Handler<A>,
+    // for the common forms, what runs it instead, and the cycles it
+    // takes, which are always the same
+    op: Option<(Run<A>, Operands, Cycles)>,
+}
+
+pub struct BlockCache<A: AddressBus + CodeWrites> {
+    blocks: CodeBlocks<Vec<Decoded<A>>>,
+}
+
+impl<A: AddressBus + CodeWrites> BlockCache<A> {
+    pub fn new() -> BlockCache<A> {
+        BlockCache { blocks: CodeBlocks::new() }
+    }
+    // number of blocks currently cached
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+    // number of blocks ever decoded, and dropped due to code writes
+    pub fn decoded(&self) -> usize {
+        self.blocks.built()
+    }
+    pub fn invalidated(&self) -> usize {
+        self.blocks.invalidated()
+    }
+    // Drops all blocks, like after loading new code without going
+    // through the bus
+    pub fn flush(&mut self, core: &mut Core<A>) {
+        self.blocks.flush(&mut core.mem);
+    }
+
+    // Same contract as Core::execute
+    pub fn execute(&mut self, core: &mut Core<A>, cycles: i32) -> Cycles {
+        self.execute_with_state(core, cycles, &mut EmulateAllExceptions)
+    }
+    pub fn execute_with_state<T: Callbacks<A>>(&mut self, core: &mut Core<A>, cycles: i32, state: &mut T) -> Cycles {
+        let cycles = Cycles(cycles);
+        let mut remaining_cycles = cycles;
+        core.watch_hit = None;
+        core.mem.take_watch_hit();
+        core.mem.take_bus_error();
+        while remaining_cycles.any() && !core.is_idle() {
+            self.blocks.invalidate_written(&mut core.mem);
+            let (remaining, stopped) = match self.blocks.get(&(core.pc, core.s_flag != 0)) {
+                Some(block) => run_block(block, core, remaining_cycles, state),
+                None => self.record(core, remaining_cycles, state)
+            };
+            remaining_cycles = remaining;
+            if stopped {
+                break;
+            }
+        }
+        core.cycles_consumed(cycles, remaining_cycles)
+    }
+
+    // Interprets from PC until control flow leaves the straight line,
+    // and caches what was run; the cycles left, and whether execution
+    // stopped at a breakpoint or watchpoint
+    fn record<T: Callbacks<A>>(&mut self, core: &mut Core<A>, mut remaining_cycles: Cycles, state: &mut T) -> (Cycles, bool) {
+        let key = (core.pc, core.s_flag != 0);
+        let mut instructions = Vec::new();
+        let mut pages = Vec::new();
+        let mut stopped = false;
+        loop {
+            if core.breakpoint_hit().is_some() {
+                stopped = true;
+                break;
+            }
+            let pc = core.pc;
+            let supervisor = core.s_flag != 0;
+            watch_instruction(&mut core.mem, pc, &mut pages);
+            let result = match core.read_instruction() {
+                Ok(opcode) => {
+                    let handler = core.ophandlers[opcode as usize];
+                    core.start_instruction(opcode);
+                    let result = handler(core);
+                    let address_space = if supervisor {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
+                    let op = match result {
+                        Ok(cycles) => predecoded::decode(&core.mem, address_space, pc, opcode).map(|(run, operands)| (run, operands, cycles)),
+                        Err(_) => None
+                    };
+                    instructions.push(Decoded { pc, opcode, handler, op });
+                    result
+                }
+                Err(ex) => Err(ex)
+            };
+            let completed = result.is_ok();
+            remaining_cycles = remaining_cycles - core.complete_step(result, state);
+            if let Some(hit) = core.mem.take_watch_hit() {
+                core.watch_hit = Some(WatchHit { pc, ..hit });
+                stopped = true;
+                break;
+            }
+            let falls_through = completed && core.pc > pc && core.pc - pc <= MAX_INSTRUCTION_BYTES;
+            if !falls_through || (core.s_flag != 0) != supervisor || instructions.len() == MAX_BLOCK_INSTRUCTIONS
+                || !remaining_cycles.any() || core.is_idle() || core.mem.code_written() {
+                break;
+            }
+        }
+        // code that modified itself while being recorded is left to the
+        // interpreter until it settles down
+        if instructions.is_empty() || core.mem.code_written() {
+            self.blocks.discard(&mut core.mem, pages);
+        } else {
+            self.blocks.insert(key, instructions, pages);
+        }
+        (remaining_cycles, stopped)
+    }
+}
+
+impl<A: AddressBus + CodeWrites> Default for BlockCache<A> {
+    fn default() -> BlockCache<A> {
+        BlockCache::new()
+    }
+}
+
+// Runs the block for as long as control flows the way it did when it was
+// recorded, going round again while it branches back to its start
+fn run_block<A: AddressBus + CodeWrites, T: Callbacks<A>>(block: &[Decoded<A>], core: &mut Core<A>, mut remaining_cycles: Cycles, state: &mut T) -> (Cycles, bool) {
+    // interrupts are taken by the interpreter
+    if core.pending_interrupt().is_some() {
+        return (remaining_cycles - core.step(state), false);
+    }
+    let supervisor = core.s_flag != 0;
+    // neither changes while instructions run
+    let breakpoints = !core.breakpoints.is_empty();
+    let tracking_calls = core.call_stack.is_enabled();
+    'block: loop {
+        for decoded in block {
+            // leave the block whenever the interpreter would have stopped,
+            // or control didn't flow the way it did when the block was
+            // recorded
+            if !remaining_cycles.any() || core.pc != decoded.pc || (core.s_flag != 0) != supervisor || core.mem.code_written() {
+                break 'block;
+            }
+            if breakpoints && core.breakpoint_hit().is_some() {
+                return (remaining_cycles, true);
+            }
+            // what read_instruction would have done, but the opcode fetched
+            // is not decoded again
+            let (completed, cycles) = match decoded.op {
+                Some((run, ref operands, cycles)) if !tracking_calls => {
+                    let result = core.prefetch_if_needed().and_then(|_| {
+                        core.start_instruction(decoded.opcode);
+                        run(core, operands)
+                    });
+                    match result {
+                        // as complete_step would count it
+                        Ok(()) => {
+                            core.elapsed_cycles += cycles.0 as u64;
+                            (true, cycles)
+                        },
+                        Err(ex) => (false, core.complete_step(Err(ex), state))
+                    }
+                },
+                Some((run, ref operands, cycles)) => {
+                    let result = core.prefetch_if_needed().and_then(|_| {
+                        core.start_instruction(decoded.opcode);
+                        run(core, operands).map(|_| cycles)
+                    });
+                    (result.is_ok(), core.complete_step(result, state))
+                },
+                None => {
+                    let result = core.read_imm_u16().and_then(|_| {
+                        core.start_instruction(decoded.opcode);
+                        (decoded.handler)(core)
+                    });
+                    (result.is_ok(), core.complete_step(result, state))
+                }
+            };
+            remaining_cycles = remaining_cycles - cycles;
+            if let Some(hit) = core.mem.take_watch_hit() {
+                core.watch_hit = Some(WatchHit { pc: decoded.pc, ..hit });
+                return (remaining_cycles, true);
+            }
+            // only handlers and exceptions change the interrupt mask and
+            // processing state
+            if (decoded.op.is_none() || !completed) && (core.is_idle() || core.pending_interrupt().is_some()) {
+                break 'block;
+            }
+        }
+    }
+    (remaining_cycles, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BlockCache;
+    use cpu::{Core, Breakpoint, ProcessingState};
+    use ram::AddressBus;
+    use ram::pagedmem::PagedMem;
+    use ram::loggingmem::{LoggingMem, OpsLogger};
+    use ram::tracking::WriteTracker;
+    use ram::watching::{Watcher, Watchpoint, WatchKind};
+    use ram::{SUPERVISOR_PROGRAM, SUPERVISOR_DATA};
+    use testing::{core_on, core_running};
+
+    // MOVE.L #$00010001, D1; ADD.L D1, D0; MOVE.W D0, (A0)+;
+    // DBRA D2, back to the first move; STOP #$2700
+    const SUMMING_LOOP: [u8; 18] = [0x22, 0x3c, 0x00, 0x01, 0x00, 0x01, 0xd0, 0x81, 0x30, 0xc0,
+        0x51, 0xca, 0xff, 0xf4, 0x4e, 0x72, 0x27, 0x00];
+    // MOVE.W D1, (A0) overwriting the following ADDQ.L #1, D0, then
+    // BRA.S back to the move
+    const PATCHING_LOOP: [u8; 6] = [0x30, 0x81, 0x52, 0x80, 0x60, 0xfa];
+
+    fn assert_same_state(expected: &Core<PagedMem>, actual: &Core<WriteTracker<PagedMem>>) {
+        assert_eq!(expected.pc, actual.pc);
+        assert_eq!(expected.dar, actual.dar);
+        assert_eq!(expected.status_register(), actual.status_register());
+        assert_eq!(expected.processing_state, actual.processing_state);
+    }
+
+    #[test]
+    fn runs_like_the_interpreter() {
+        let mut interpreted = core_running(&SUMMING_LOOP);
+        let mut cached = core_running(&SUMMING_LOOP).map_bus(WriteTracker::new);
+        interpreted.dar[2] = 50;
+        interpreted.dar[8] = 0x4000;
+        cached.dar[2] = 50;
+        cached.dar[8] = 0x4000;
+        let mut cache = BlockCache::new();
+        for quantum in 1..40 {
+            assert_eq!(interpreted.execute(quantum * 3), cache.execute(&mut cached, quantum * 3));
+            assert_same_state(&interpreted, &cached);
+        }
+        assert_eq!(ProcessingState::Stopped, cached.processing_state);
+        assert!(!cache.is_empty());
+        for address in 0x4000..0x4070 {
+            assert_eq!(interpreted.mem.read_byte(SUPERVISOR_DATA, address), cached.mem.read_byte(SUPERVISOR_DATA, address));
+        }
+    }
+
+    #[test]
+    fn makes_the_same_bus_accesses_as_the_interpreter() {
+        let logging_core = || {
+            let mut core = core_on(LoggingMem::new(0xaaaaaaaa, OpsLogger::new()), &SUMMING_LOOP);
+            core.dar[2] = 20;
+            core.dar[8] = 0x4000;
+            core
+        };
+        let mut interpreted = logging_core();
+        let mut cached = logging_core().map_bus(WriteTracker::new);
+        let mut cache = BlockCache::new();
+        for _ in 0..10 {
+            interpreted.execute(100);
+            cache.execute(&mut cached, 100);
+        }
+        let cached: Core<LoggingMem<OpsLogger>> = cached.map_bus(WriteTracker::into_inner);
+        assert_eq!(interpreted.mem.logger.ops(), cached.mem.logger.ops());
+    }
+
+    #[test]
+    fn serves_opcodes_from_the_cache() {
+        let mut cached = core_running(&SUMMING_LOOP).map_bus(WriteTracker::new);
+        cached.dar[2] = 50;
+        cached.dar[8] = 0x4000;
+        let mut cache = BlockCache::new();
+        cache.execute(&mut cached, 100);
+        // not reported to the cache, which runs the handler it decoded
+        // for the old opcode, although the new one is fetched
+        cached.mem.inner.write_word(SUPERVISOR_PROGRAM, 0x1006, 0x4e71);
+        cache.execute(&mut cached, 100);
+        assert_eq!(0, cache.invalidated());
+        assert!(cached.dar[0] > 0x10001);
+    }
+
+    #[test]
+    fn redecodes_code_written_by_the_host() {
+        let mut interpreted = core_running(&SUMMING_LOOP);
+        let mut cached = core_running(&SUMMING_LOOP).map_bus(WriteTracker::new);
+        interpreted.dar[2] = 50;
+        cached.dar[2] = 50;
+        let mut cache = BlockCache::new();
+        interpreted.execute(100);
+        cache.execute(&mut cached, 100);
+        // ADD.L D1, D1
+        interpreted.mem.write_word(SUPERVISOR_PROGRAM, 0x1006, 0xd281);
+        cached.mem.write_word(SUPERVISOR_PROGRAM, 0x1006, 0xd281);
+        assert_eq!(interpreted.execute(300), cache.execute(&mut cached, 300));
+        assert_same_state(&interpreted, &cached);
+        assert!(cache.invalidated() > 0);
+    }
+
+    #[test]
+    fn runs_self_modifying_code_like_the_interpreter() {
+        let mut interpreted = core_running(&PATCHING_LOOP);
+        let mut cached = core_running(&PATCHING_LOOP).map_bus(WriteTracker::new);
+        // ADDQ.L #2, D0
+        interpreted.dar[1] = 0x5480;
+        interpreted.dar[8] = 0x1002;
+        cached.dar[1] = 0x5480;
+        cached.dar[8] = 0x1002;
+        let mut cache = BlockCache::new();
+        for _ in 0..10 {
+            assert_eq!(interpreted.execute(100), cache.execute(&mut cached, 100));
+            assert_same_state(&interpreted, &cached);
+        }
+    }
+
+    #[test]
+    fn takes_interrupts_like_the_interpreter() {
+        let mut interpreted = core_running(&SUMMING_LOOP);
+        let mut cached = core_running(&SUMMING_LOOP).map_bus(WriteTracker::new);
+        interpreted.dar[2] = 500;
+        cached.dar[2] = 500;
+        interpreted.sr_to_flags(0x2000);
+        cached.sr_to_flags(0x2000);
+        let mut cache = BlockCache::new();
+        assert_eq!(interpreted.execute(200), cache.execute(&mut cached, 200));
+        interpreted.int_ctrl.request_interrupt(3);
+        cached.int_ctrl.request_interrupt(3);
+        assert_eq!(interpreted.execute(200), cache.execute(&mut cached, 200));
+        assert_same_state(&interpreted, &cached);
+    }
+
+    #[test]
+    fn stops_at_breakpoints_like_the_interpreter() {
+        let mut interpreted = core_running(&SUMMING_LOOP);
+        let mut cached = core_running(&SUMMING_LOOP).map_bus(WriteTracker::new);
+        interpreted.dar[2] = 50;
+        cached.dar[2] = 50;
+        let mut cache = BlockCache::new();
+        assert_eq!(interpreted.execute(100), cache.execute(&mut cached, 100));
+        // in the middle of a cached block
+        let id = interpreted.breakpoints.add(Breakpoint::new(0x1008));
+        cached.breakpoints.add(Breakpoint::new(0x1008));
+        for _ in 0..3 {
+            assert_eq!(interpreted.execute(1000), cache.execute(&mut cached, 1000));
+            assert_same_state(&interpreted, &cached);
+            assert_eq!(0x1008, cached.pc);
+            assert_eq!(Some(id), cached.breakpoints.stopped_at());
+        }
+    }
+
+    #[test]
+    fn stops_at_watchpoints_like_the_interpreter() {
+        let watched_core = || {
+            let mut core = core_on(Watcher::new(PagedMem::new(0)), &SUMMING_LOOP);
+            core.dar[2] = 50;
+            core.dar[8] = 0x4000;
+            core.mem.add(Watchpoint::new(WatchKind::Write, 0x4010, 0x4011));
+            core
+        };
+        let mut interpreted = watched_core();
+        let mut cached = watched_core().map_bus(WriteTracker::new);
+        let mut cache = BlockCache::new();
+        // the loop is cached by the time it writes to the watched word
+        assert_eq!(interpreted.execute(1000), cache.execute(&mut cached, 1000));
+        assert!(cache.decoded() > 0);
+        assert!(interpreted.watch_hit.is_some());
+        assert_eq!(interpreted.watch_hit, cached.watch_hit);
+        assert_eq!(interpreted.pc, cached.pc);
+        assert_eq!(interpreted.dar, cached.dar);
+        assert_eq!(Some(0x1008), cached.watch_hit.map(|hit| hit.pc));
+    }
+}
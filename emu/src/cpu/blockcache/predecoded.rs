@@ -0,0 +1,633 @@
+// Instructions of the most common forms with their operands decoded
+// when the block is recorded: MOVE, MOVEA and MOVEQ, ADD, SUB, CMP, AND
+// and OR into data registers, from memory or an immediate, ADDQ and SUBQ
+// on registers, LEA and TST.
+//
+// Like handlers, each form is run by a function of its own, instantiated
+// for its size and addressing modes, but these take the registers and
+// extension words from the operands decoded rather than from the opcode
+// and the instruction stream. They set the same fields to the same values
+// as the handlers, and make the same bus accesses in the same order:
+// extension words are not decoded again, but still fetched through the
+// prefetch, as the handlers would.
+use cpu::{Core, Result};
+use cpu::ops::common;
+use cpu::ops::handlers::HANDLER_NAMES;
+use ram::{AddressBus, AddressSpace};
+use ram::watching::AccessSize;
+
+pub type Run<A> = fn(&mut Core<A>, &Operands) -> Result<()>;
+
+// Registers are indices into dar, so address registers are 8 and up
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Operand {
+    // the register, or the base register of indexed modes
+    pub reg: usize,
+    // the index register, and whether all of it is used
+    pub index: usize,
+    pub long: bool,
+    // what the extension words make up: a displacement, an address, or an
+    // immediate as the handler reads it (bytes masked, words
+    // sign-extended); PC-relative addresses are resolved
+    pub value: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Operands {
+    pub src: Operand,
+    pub dst: Operand,
+}
+
+trait Size {
+    const SIZE: AccessSize;
+}
+
+struct Byte;
+struct Word;
+struct Long;
+
+impl Size for Byte {
+    const SIZE: AccessSize = AccessSize::Byte;
+}
+impl Size for Word {
+    const SIZE: AccessSize = AccessSize::Word;
+}
+impl Size for Long {
+    const SIZE: AccessSize = AccessSize::Long;
+}
+
+trait Mode {
+    // the address of the operand, its extension words fetched
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32>;
+    fn read<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32> {
+        let address = Self::address(core, operand, size)?;
+        match size {
+            AccessSize::Byte => core.read_data_byte(address),
+            AccessSize::Word => core.read_data_word(address),
+            AccessSize::Long => core.read_data_long(address),
+        }
+    }
+    fn write<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize, value: u32) -> Result<()> {
+        let address = Self::address(core, operand, size)?;
+        match size {
+            AccessSize::Byte => core.write_data_byte(address, value),
+            AccessSize::Word => core.write_data_word(address, value),
+            AccessSize::Long => core.write_data_long(address, value),
+        }
+    }
+}
+
+struct DataRegister;
+struct AddressRegister;
+struct Indirect;
+struct PostIncrement;
+struct PreDecrement;
+struct Displacement;
+struct Index;
+struct AbsoluteWord;
+struct AbsoluteLong;
+struct PcDisplacement;
+struct PcIndex;
+struct Immediate;
+// the data of ADDQ, SUBQ and MOVEQ, which is part of the opcode
+struct Quick;
+
+fn fetch_extension_words<A: AddressBus>(core: &mut Core<A>, count: usize) -> Result<()> {
+    for _ in 0..count {
+        core.prefetch_if_needed()?;
+    }
+    Ok(())
+}
+
+fn index_value<A: AddressBus>(core: &Core<A>, operand: &Operand) -> u32 {
+    let xn = core.dar[operand.index];
+    if operand.long { xn } else { xn as i16 as u32 }
+}
+
+fn read_program<A: AddressBus>(core: &mut Core<A>, address: u32, size: AccessSize) -> Result<u32> {
+    match size {
+        AccessSize::Byte => core.read_program_byte(address),
+        AccessSize::Word => core.read_program_word(address),
+        AccessSize::Long => core.read_program_long(address),
+    }
+}
+
+impl Mode for DataRegister {
+    fn address<A: AddressBus>(_: &mut Core<A>, _: &Operand, _: AccessSize) -> Result<u32> {
+        unreachable!("registers have no address")
+    }
+    fn read<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        Ok(core.dar[operand.reg])
+    }
+    fn write<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize, value: u32) -> Result<()> {
+        core.dar[operand.reg] = merge(size, core.dar[operand.reg], value);
+        Ok(())
+    }
+}
+impl Mode for AddressRegister {
+    fn address<A: AddressBus>(_: &mut Core<A>, _: &Operand, _: AccessSize) -> Result<u32> {
+        unreachable!("registers have no address")
+    }
+    fn read<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        Ok(core.dar[operand.reg])
+    }
+}
+impl Mode for Indirect {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        Ok(core.dar[operand.reg])
+    }
+}
+impl Mode for PostIncrement {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32> {
+        let address = core.dar[operand.reg];
+        core.dar[operand.reg] = address.wrapping_add(step(operand.reg, size));
+        Ok(address)
+    }
+}
+impl Mode for PreDecrement {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32> {
+        core.dar[operand.reg] = core.dar[operand.reg].wrapping_sub(step(operand.reg, size));
+        Ok(core.dar[operand.reg])
+    }
+}
+impl Mode for Displacement {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, 1)?;
+        Ok(core.dar[operand.reg].wrapping_add(operand.value))
+    }
+}
+impl Mode for Index {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, 1)?;
+        Ok(core.dar[operand.reg].wrapping_add(index_value(core, operand)).wrapping_add(operand.value))
+    }
+}
+impl Mode for AbsoluteWord {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, 1)?;
+        Ok(operand.value)
+    }
+}
+impl Mode for AbsoluteLong {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, 2)?;
+        Ok(operand.value)
+    }
+}
+impl Mode for PcDisplacement {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, 1)?;
+        Ok(operand.value)
+    }
+    fn read<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32> {
+        let address = Self::address(core, operand, size)?;
+        read_program(core, address, size)
+    }
+}
+impl Mode for PcIndex {
+    fn address<A: AddressBus>(core: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, 1)?;
+        Ok(operand.value.wrapping_add(index_value(core, operand)))
+    }
+    fn read<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32> {
+        let address = Self::address(core, operand, size)?;
+        read_program(core, address, size)
+    }
+}
+impl Mode for Immediate {
+    fn address<A: AddressBus>(_: &mut Core<A>, _: &Operand, _: AccessSize) -> Result<u32> {
+        unreachable!("immediates have no address")
+    }
+    fn read<A: AddressBus>(core: &mut Core<A>, operand: &Operand, size: AccessSize) -> Result<u32> {
+        fetch_extension_words(core, if size == AccessSize::Long { 2 } else { 1 })?;
+        Ok(operand.value)
+    }
+}
+impl Mode for Quick {
+    fn address<A: AddressBus>(_: &mut Core<A>, _: &Operand, _: AccessSize) -> Result<u32> {
+        unreachable!("immediates have no address")
+    }
+    fn read<A: AddressBus>(_: &mut Core<A>, operand: &Operand, _: AccessSize) -> Result<u32> {
+        Ok(operand.value)
+    }
+}
+
+// A7 is kept even
+fn step(reg: usize, size: AccessSize) -> u32 {
+    if reg == 15 && size == AccessSize::Byte { 2 } else { size.bytes() }
+}
+
+fn mask(size: AccessSize, value: u32) -> u32 {
+    match size {
+        AccessSize::Byte => value & 0xff,
+        AccessSize::Word => value & 0xffff,
+        AccessSize::Long => value,
+    }
+}
+
+// the register with its low byte or word replaced
+fn merge(size: AccessSize, register: u32, value: u32) -> u32 {
+    match size {
+        AccessSize::Byte => register & !0xff | value,
+        AccessSize::Word => register & !0xffff | value,
+        AccessSize::Long => value,
+    }
+}
+
+// how far to shift for the sign bit to end up in bit 7, where n_flag
+// keeps it
+fn sign_shift(size: AccessSize) -> u32 {
+    match size {
+        AccessSize::Byte => 0,
+        AccessSize::Word => 8,
+        AccessSize::Long => 24,
+    }
+}
+
+trait Operation {
+    // CMP doesn't store the result
+    const STORES: bool = true;
+    fn apply<A: AddressBus>(core: &mut Core<A>, size: AccessSize, dst: u32, src: u32) -> u32;
+}
+
+struct Add;
+struct Sub;
+struct And;
+struct Or;
+struct Cmp;
+
+impl Operation for Add {
+    fn apply<A: AddressBus>(core: &mut Core<A>, size: AccessSize, dst: u32, src: u32) -> u32 {
+        match size {
+            AccessSize::Byte => common::add_8(core, dst, src),
+            AccessSize::Word => common::add_16(core, dst, src),
+            AccessSize::Long => common::add_32(core, dst, src),
+        }
+    }
+}
+impl Operation for Sub {
+    fn apply<A: AddressBus>(core: &mut Core<A>, size: AccessSize, dst: u32, src: u32) -> u32 {
+        match size {
+            AccessSize::Byte => common::sub_8(core, dst, src),
+            AccessSize::Word => common::sub_16(core, dst, src),
+            AccessSize::Long => common::sub_32(core, dst, src),
+        }
+    }
+}
+impl Operation for And {
+    fn apply<A: AddressBus>(core: &mut Core<A>, size: AccessSize, dst: u32, src: u32) -> u32 {
+        match size {
+            AccessSize::Byte => common::and_8(core, dst, src),
+            AccessSize::Word => common::and_16(core, dst, src),
+            AccessSize::Long => common::and_32(core, dst, src),
+        }
+    }
+}
+impl Operation for Or {
+    fn apply<A: AddressBus>(core: &mut Core<A>, size: AccessSize, dst: u32, src: u32) -> u32 {
+        match size {
+            AccessSize::Byte => common::or_8(core, dst, src),
+            AccessSize::Word => common::or_16(core, dst, src),
+            AccessSize::Long => common::or_32(core, dst, src),
+        }
+    }
+}
+impl Operation for Cmp {
+    const STORES: bool = false;
+    fn apply<A: AddressBus>(core: &mut Core<A>, size: AccessSize, dst: u32, src: u32) -> u32 {
+        match size {
+            AccessSize::Byte => common::cmp_8(core, dst, src),
+            AccessSize::Word => common::cmp_16(core, dst, src),
+            AccessSize::Long => common::cmp_32(core, dst, src),
+        }
+    }
+}
+
+// MOVE, and MOVEQ from Quick
+fn run_move<A: AddressBus, S: Size, Src: Mode, Dst: Mode>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    let value = mask(S::SIZE, Src::read(core, &operands.src, S::SIZE)?);
+    Dst::write(core, &operands.dst, S::SIZE, value)?;
+    common::move_flags(core, value, sign_shift(S::SIZE));
+    Ok(())
+}
+fn run_movea<A: AddressBus, S: Size, Src: Mode>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    let value = Src::read(core, &operands.src, S::SIZE)?;
+    core.dar[operands.dst.reg] = if S::SIZE == AccessSize::Word { value as i16 as u32 } else { value };
+    Ok(())
+}
+// the operations into data registers, ADDI and the like from Immediate,
+// and ADDQ and SUBQ from Quick
+fn run_operation<A: AddressBus, O: Operation, S: Size, Src: Mode>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    let src = Src::read(core, &operands.src, S::SIZE)?;
+    let dst = core.dar[operands.dst.reg];
+    let result = O::apply(core, S::SIZE, dst, src);
+    if O::STORES {
+        core.dar[operands.dst.reg] = merge(S::SIZE, dst, result);
+    }
+    Ok(())
+}
+// the flags are left alone, and the whole register is used
+fn run_addq_an<A: AddressBus>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    core.dar[operands.dst.reg] = core.dar[operands.dst.reg].wrapping_add(operands.src.value);
+    Ok(())
+}
+fn run_subq_an<A: AddressBus>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    core.dar[operands.dst.reg] = core.dar[operands.dst.reg].wrapping_sub(operands.src.value);
+    Ok(())
+}
+fn run_lea<A: AddressBus, Src: Mode>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    core.dar[operands.dst.reg] = Src::address(core, &operands.src, AccessSize::Long)?;
+    Ok(())
+}
+fn run_tst<A: AddressBus, S: Size, Src: Mode>(core: &mut Core<A>, operands: &Operands) -> Result<()> {
+    let value = mask(S::SIZE, Src::read(core, &operands.src, S::SIZE)?);
+    common::move_flags(core, value, sign_shift(S::SIZE));
+    Ok(())
+}
+
+// Call the generic function given with the type for a size, or for an
+// addressing mode as named in the handler names, as its last type
+// parameter
+macro_rules! by_size {
+    ($size:expr, $f:ident::<$($t:ty),*>($($arg:expr),*)) => (match $size {
+        AccessSize::Byte => $f::<$($t,)* Byte>($($arg),*),
+        AccessSize::Word => $f::<$($t,)* Word>($($arg),*),
+        AccessSize::Long => $f::<$($t,)* Long>($($arg),*),
+    })
+}
+macro_rules! by_mode {
+    ($mode:expr, $f:ident::<$($t:ty),*>($($arg:expr),*)) => (match $mode {
+        "an" => $f::<$($t,)* AddressRegister>($($arg),*),
+        "pcdi" => $f::<$($t,)* PcDisplacement>($($arg),*),
+        "pcix" => $f::<$($t,)* PcIndex>($($arg),*),
+        "imm" => $f::<$($t,)* Immediate>($($arg),*),
+        mode => by_destination!(mode, $f::<$($t),*>($($arg),*)),
+    })
+}
+// the modes a MOVE can write to
+macro_rules! by_destination {
+    ($mode:expr, $f:ident::<$($t:ty),*>($($arg:expr),*)) => (match $mode {
+        "dn" => $f::<$($t,)* DataRegister>($($arg),*),
+        "ai" => $f::<$($t,)* Indirect>($($arg),*),
+        "pi" => $f::<$($t,)* PostIncrement>($($arg),*),
+        "pd" => $f::<$($t,)* PreDecrement>($($arg),*),
+        "di" => $f::<$($t,)* Displacement>($($arg),*),
+        "ix" => $f::<$($t,)* Index>($($arg),*),
+        "aw" => $f::<$($t,)* AbsoluteWord>($($arg),*),
+        "al" => $f::<$($t,)* AbsoluteLong>($($arg),*),
+        _ => None,
+    })
+}
+
+fn move_from<A: AddressBus, S: Size>(src: &str, dst: &str) -> Option<Run<A>> {
+    by_mode!(src, move_to::<A, S>(dst))
+}
+fn move_to<A: AddressBus, S: Size, Src: Mode>(dst: &str) -> Option<Run<A>> {
+    by_destination!(dst, move_run::<A, S, Src>())
+}
+fn move_run<A: AddressBus, S: Size, Src: Mode, Dst: Mode>() -> Option<Run<A>> {
+    Some(run_move::<A, S, Src, Dst>)
+}
+fn movea_from<A: AddressBus, S: Size>(src: &str) -> Option<Run<A>> {
+    by_mode!(src, movea_run::<A, S>())
+}
+fn movea_run<A: AddressBus, S: Size, Src: Mode>() -> Option<Run<A>> {
+    Some(run_movea::<A, S, Src>)
+}
+fn operation_from<A: AddressBus, O: Operation, S: Size>(src: &str) -> Option<Run<A>> {
+    by_mode!(src, operation_run::<A, O, S>())
+}
+fn operation_run<A: AddressBus, O: Operation, S: Size, Src: Mode>() -> Option<Run<A>> {
+    Some(run_operation::<A, O, S, Src>)
+}
+fn quick_run<A: AddressBus, O: Operation, S: Size>() -> Option<Run<A>> {
+    Some(run_operation::<A, O, S, Quick>)
+}
+fn immediate_run<A: AddressBus, O: Operation, S: Size>() -> Option<Run<A>> {
+    Some(run_operation::<A, O, S, Immediate>)
+}
+fn lea_run<A: AddressBus, Src: Mode>() -> Option<Run<A>> {
+    Some(run_lea::<A, Src>)
+}
+fn tst_from<A: AddressBus, S: Size>(src: &str) -> Option<Run<A>> {
+    by_mode!(src, tst_run::<A, S>())
+}
+fn tst_run<A: AddressBus, S: Size, Src: Mode>() -> Option<Run<A>> {
+    Some(run_tst::<A, S, Src>)
+}
+
+// The extension words following an opcode, read without side effects
+struct Words<'a, A: AddressBus + 'a> {
+    mem: &'a A,
+    address_space: AddressSpace,
+    address: u32,
+}
+
+impl<'a, A: AddressBus> Words<'a, A> {
+    fn next(&mut self) -> u16 {
+        let word = self.mem.peek(self.address_space, self.address, AccessSize::Word) as u16;
+        self.address = self.address.wrapping_add(2);
+        word
+    }
+    fn long(&mut self) -> u32 {
+        let high = self.next() as u32;
+        high << 16 | self.next() as u32
+    }
+}
+
+// The operand of a mode, as named in the handler names, reading its
+// extension words; reg is the register field of the opcode
+fn operand<A: AddressBus>(mode: &str, reg: usize, size: AccessSize, words: &mut Words<A>) -> Option<Operand> {
+    // Brief Extension Word format, as effective_address::index reads it
+    let indexed = |reg: usize, extension: u16, base: u32| Operand {
+        reg, index: (extension >> 12) as usize, long: extension & 0x0800 != 0,
+        value: base.wrapping_add(extension as i8 as u32),
+    };
+    let register = |reg: usize| Operand { reg, ..Default::default() };
+    let value = |value: u32| Operand { value, ..Default::default() };
+    Some(match mode {
+        "dn" => register(reg),
+        "an" | "ai" | "pi" | "pd" => register(8 + reg),
+        "di" => Operand { reg: 8 + reg, value: words.next() as i16 as u32, ..Default::default() },
+        "ix" => indexed(8 + reg, words.next(), 0),
+        "aw" => value(words.next() as i16 as u32),
+        "al" => value(words.long()),
+        // relative to the extension word
+        "pcdi" => {
+            let base = words.address;
+            value(base.wrapping_add(words.next() as i16 as u32))
+        },
+        "pcix" => {
+            let base = words.address;
+            indexed(0, words.next(), base)
+        },
+        "imm" => value(match size {
+            AccessSize::Byte => words.next() as u8 as u32,
+            AccessSize::Word => words.next() as i16 as u32,
+            AccessSize::Long => words.long(),
+        }),
+        _ => return None,
+    })
+}
+
+// The function running the instruction at pc, and its operands, with
+// the extension words as they are in memory; None for instructions left
+// to their handlers
+pub fn decode<A: AddressBus>(mem: &A, address_space: AddressSpace, pc: u32, opcode: u16) -> Option<(Run<A>, Operands)> {
+    let name = HANDLER_NAMES[opcode as usize];
+    let (x, y) = ((opcode >> 9 & 7) as usize, (opcode & 7) as usize);
+    let quick = if x == 0 { 8 } else { x as u32 };
+    let mut parts = name.split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let size = match parts.next() {
+        Some("8") => AccessSize::Byte,
+        Some("16") => AccessSize::Word,
+        Some("32") => AccessSize::Long,
+        _ => return None,
+    };
+    let modes: Vec<&str> = parts.collect();
+    let mut words = Words { mem, address_space, address: pc.wrapping_add(2) };
+    let mut operands = Operands::default();
+    let run = match (mnemonic, &modes[..]) {
+        ("moveq", []) => {
+            operands.src.value = opcode as u8 as i8 as u32;
+            operands.dst.reg = x;
+            Some(run_move::<A, Long, Quick, DataRegister> as Run<A>)
+        },
+        ("move", [dst, src]) => {
+            // the source comes first, extension words included
+            operands.src = operand(src, y, size, &mut words)?;
+            operands.dst = operand(dst, x, size, &mut words)?;
+            by_size!(size, move_from::<A>(src, dst))
+        },
+        ("movea", [src]) => {
+            operands.src = operand(src, y, size, &mut words)?;
+            operands.dst.reg = 8 + x;
+            by_size!(size, movea_from::<A>(src))
+        },
+        ("add", ["er", src]) | ("sub", ["er", src]) | ("and", ["er", src]) | ("or", ["er", src]) | ("cmp", [src]) => {
+            operands.src = operand(src, y, size, &mut words)?;
+            operands.dst.reg = x;
+            match mnemonic {
+                "add" => by_size!(size, operation_from::<A, Add>(src)),
+                "sub" => by_size!(size, operation_from::<A, Sub>(src)),
+                "and" => by_size!(size, operation_from::<A, And>(src)),
+                "or" => by_size!(size, operation_from::<A, Or>(src)),
+                _ => by_size!(size, operation_from::<A, Cmp>(src)),
+            }
+        },
+        ("addi", ["dn"]) | ("subi", ["dn"]) | ("andi", ["dn"]) | ("ori", ["dn"]) | ("cmpi", ["dn"]) => {
+            operands.src = operand("imm", 0, size, &mut words)?;
+            operands.dst.reg = y;
+            match mnemonic {
+                "addi" => by_size!(size, immediate_run::<A, Add>()),
+                "subi" => by_size!(size, immediate_run::<A, Sub>()),
+                "andi" => by_size!(size, immediate_run::<A, And>()),
+                "ori" => by_size!(size, immediate_run::<A, Or>()),
+                _ => by_size!(size, immediate_run::<A, Cmp>()),
+            }
+        },
+        ("addq", ["dn"]) | ("subq", ["dn"]) => {
+            operands.src.value = quick;
+            operands.dst.reg = y;
+            if mnemonic == "addq" {
+                by_size!(size, quick_run::<A, Add>())
+            } else {
+                by_size!(size, quick_run::<A, Sub>())
+            }
+        },
+        ("addq", ["an"]) | ("subq", ["an"]) => {
+            operands.src.value = quick;
+            operands.dst.reg = 8 + y;
+            Some(if mnemonic == "addq" { run_addq_an::<A> as Run<A> } else { run_subq_an::<A> })
+        },
+        ("lea", [src]) => {
+            operands.src = operand(src, y, size, &mut words)?;
+            operands.dst.reg = 8 + x;
+            by_mode!(*src, lea_run::<A>())
+        },
+        ("tst", [src]) => {
+            operands.src = operand(src, y, size, &mut words)?;
+            by_size!(size, tst_from::<A>(src))
+        },
+        _ => None,
+    };
+    run.map(|run| (run, operands))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Operand, Operands};
+    use cpu::{Core, ProcessingState};
+    use cpu::blockcache::BlockCache;
+    use ram::{AddressBus, SUPERVISOR_PROGRAM};
+    use ram::loggingmem::{LoggingMem, OpsLogger};
+    use ram::pagedmem::PagedMem;
+    use ram::tracking::WriteTracker;
+    use testing::{core_on, load, PROGRAM_START};
+
+    #[test]
+    fn decodes_operands_and_extension_words() {
+        // MOVE.L 16(A0), D1; ADD.W -2(PC,D3.L), D2; LEA $12345678, A1; NOP
+        let mut mem = PagedMem::new(0);
+        load(&mut mem, 0x1000, &[0x22, 0x28, 0x00, 0x10, 0xd4, 0x7b, 0x38, 0xfe,
+            0x43, 0xf9, 0x12, 0x34, 0x56, 0x78, 0x4e, 0x71]);
+        let operands = |pc, opcode| decode(&mem, SUPERVISOR_PROGRAM, pc, opcode).map(|(_, operands)| operands);
+        assert_eq!(Some(Operands { src: Operand { reg: 8, value: 16, ..Default::default() }, dst: Operand { reg: 1, ..Default::default() } }),
+            operands(0x1000, 0x2228));
+        // relative to the extension word
+        assert_eq!(Some(Operands { src: Operand { reg: 0, index: 3, long: true, value: 0x1004 }, dst: Operand { reg: 2, ..Default::default() } }),
+            operands(0x1004, 0xd47b));
+        assert_eq!(Some(Operands { src: Operand { value: 0x12345678, ..Default::default() }, dst: Operand { reg: 9, ..Default::default() } }),
+            operands(0x1008, 0x43f9));
+        assert_eq!(None, operands(0x100e, 0x4e71));
+    }
+
+    // Puts a core back at the start of a fresh program, keeping its
+    // instruction set, which takes long to build
+    fn restart<A: AddressBus>(core: &mut Core<A>, mem: A) {
+        core.mem = mem;
+        core.pc = PROGRAM_START;
+        core.prefetch_addr = 0;
+        core.processing_state = ProcessingState::Normal;
+        core.sr_to_flags(0x2700);
+        for (reg, value) in core.dar.iter_mut().enumerate() {
+            *value = 0x2000 + 0x202 * reg as u32;
+        }
+    }
+
+    #[test]
+    fn runs_like_the_handlers() {
+        // index D3.W, and displacements and addresses in the data below
+        const EXTENSION_WORDS: [u8; 8] = [0x30, 0x04, 0x00, 0x10, 0x00, 0x20, 0x00, 0x30];
+        let logging_mem = |opcode: u16| {
+            let mut mem = LoggingMem::new(0xaaaaaaaa, OpsLogger::new());
+            load(&mut mem, PROGRAM_START, &[(opcode >> 8) as u8, opcode as u8]);
+            load(&mut mem, PROGRAM_START + 2, &EXTENSION_WORDS);
+            mem
+        };
+        let mut interpreted = core_on(logging_mem(0), &[]);
+        let mut cached = core_on(logging_mem(0), &[]).map_bus(WriteTracker::new);
+        let mut decoded = 0;
+        for opcode in 0..0x10000 {
+            let opcode = opcode as u16;
+            if decode(&interpreted.mem, SUPERVISOR_PROGRAM, PROGRAM_START, opcode).is_none() {
+                continue;
+            }
+            decoded += 1;
+            restart(&mut interpreted, logging_mem(opcode));
+            restart(&mut cached, WriteTracker::new(logging_mem(opcode)));
+            let mut cache = BlockCache::new();
+            // recorded the first time, run from the cache the second
+            for _ in 0..2 {
+                assert_eq!(interpreted.execute(1), cache.execute(&mut cached, 1), "{:04x}", opcode);
+                interpreted.pc = PROGRAM_START;
+                cached.pc = PROGRAM_START;
+            }
+            assert_eq!(interpreted.dar, cached.dar, "{:04x}", opcode);
+            assert_eq!(interpreted.status_register(), cached.status_register(), "{:04x}", opcode);
+            assert_eq!(interpreted.mem.logger.ops(), cached.mem.inner.logger.ops(), "{:04x}", opcode);
+        }
+        assert!(decoded > 19000);
+    }
+}
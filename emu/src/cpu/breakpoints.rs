@@ -0,0 +1,229 @@
+// PC breakpoints, optionally with a condition.
+//
+// Conditions are written in the expression language of the assembler,
+// extended with registers and memory dereference, like
+// D0 == $10 && (A0).W > 3. Values are 32-bit and signed, memory is read
+// from the data space of the current mode, and a condition that cannot
+// be evaluated (like one naming an unknown symbol) doesn't match.
+//
+// execute_with_state and run_until stop before running the instruction
+// at a matching breakpoint. Execution resumes from it without the same
+// breakpoint matching again.
+use std::collections::BTreeMap;
+use std::collections::btree_map::Iter;
+use std::mem;
+use cpu::Core;
+use ram::{AddressBus, SUPERVISOR_DATA, USER_DATA};
+use ram::watching::AccessSize;
+use r68k_tools::Size;
+use r68k_tools::assembler::parser::{Expr, Register, Environment, parse_condition};
+
+#[derive(Clone, Debug)]
+pub struct Breakpoint {
+    pub pc: u32,
+    pub condition: Option<Expr>,
+    pub enabled: bool,
+    // times it has matched, and the number of matches to let pass before
+    // stopping, like the ignore count of GDB
+    pub hits: u64,
+    pub ignore: u64,
+}
+
+impl Breakpoint {
+    pub fn new(pc: u32) -> Breakpoint {
+        Breakpoint { pc, condition: None, enabled: true, hits: 0, ignore: 0 }
+    }
+    // None if the condition doesn't parse
+    pub fn conditional(pc: u32, condition: &str) -> Option<Breakpoint> {
+        parse_condition(condition).map(|condition| Breakpoint { condition: Some(condition), ..Breakpoint::new(pc) })
+    }
+    fn matches<A: AddressBus>(&self, core: &Core<A>) -> bool {
+        self.enabled && self.pc == core.pc && match self.condition {
+            Some(ref condition) => condition.eval_in(core).is_some_and(|value| value != 0),
+            None => true
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Breakpoints {
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    next_id: usize,
+    // the breakpoint the core stopped at, and where
+    stopped: Option<(usize, u32)>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        Breakpoints { breakpoints: BTreeMap::new(), next_id: 0, stopped: None }
+    }
+    // returns the id to refer to the breakpoint by
+    pub fn add(&mut self, breakpoint: Breakpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.insert(id, breakpoint);
+        id
+    }
+    pub fn remove(&mut self, id: usize) -> Option<Breakpoint> {
+        self.breakpoints.remove(&id)
+    }
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+    }
+    pub fn get(&self, id: usize) -> Option<&Breakpoint> {
+        self.breakpoints.get(&id)
+    }
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Breakpoint> {
+        self.breakpoints.get_mut(&id)
+    }
+    pub fn enable(&mut self, id: usize) {
+        if let Some(breakpoint) = self.breakpoints.get_mut(&id) {
+            breakpoint.enabled = true;
+        }
+    }
+    pub fn disable(&mut self, id: usize) {
+        if let Some(breakpoint) = self.breakpoints.get_mut(&id) {
+            breakpoint.enabled = false;
+        }
+    }
+    pub fn iter(&self) -> Iter<'_, usize, Breakpoint> {
+        self.breakpoints.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.breakpoints.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty()
+    }
+    // The breakpoint execution last stopped at, until execution resumes
+    pub fn stopped_at(&self) -> Option<usize> {
+        self.stopped.map(|(id, _)| id)
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    // Called before running each instruction; the id of a breakpoint
+    // to stop at, if any
+    pub(crate) fn breakpoint_hit(&mut self) -> Option<usize> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+        // resuming from a breakpoint runs the instruction it stopped at
+        if let Some((_, pc)) = self.breakpoints.stopped.take() {
+            if pc == self.pc {
+                return None;
+            }
+        }
+        // taken out of the core, to evaluate conditions against it
+        let mut breakpoints = mem::take(&mut self.breakpoints.breakpoints);
+        let mut hit = None;
+        for (&id, breakpoint) in breakpoints.iter_mut() {
+            if breakpoint.matches(self) {
+                breakpoint.hits += 1;
+                if hit.is_none() && breakpoint.hits > breakpoint.ignore {
+                    hit = Some(id);
+                }
+            }
+        }
+        self.breakpoints.breakpoints = breakpoints;
+        self.breakpoints.stopped = hit.map(|id| (id, self.pc));
+        hit
+    }
+}
+
+impl<A: AddressBus> Environment for Core<A> {
+    fn register(&self, reg: Register) -> Option<i32> {
+        let value = match reg {
+            Register::Data(n) => self.dar[n as usize],
+            Register::Address(n) => self.dar[8 + n as usize],
+            Register::Pc => self.pc,
+            Register::Sr => self.status_register() as u32,
+            Register::Ccr => self.condition_code_register() as u32,
+            Register::Usp => self.usp(),
+            Register::Ssp => self.ssp(),
+        };
+        Some(value as i32)
+    }
+    fn memory(&self, address: u32, size: Size) -> Option<i32> {
+        let address_space = if self.s_flag != 0 { SUPERVISOR_DATA } else { USER_DATA };
+        let value = match size {
+            Size::Byte => self.mem.peek(address_space, address, AccessSize::Byte),
+            Size::Word => self.mem.peek(address_space, address, AccessSize::Word),
+            Size::Long | Size::Unsized => self.mem.peek(address_space, address, AccessSize::Long),
+        };
+        Some(value as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Breakpoint;
+    use cpu::StopReason;
+    use testing::core_running;
+
+    // ADDQ.L #1, D0 followed by BRA.S back to it
+    const COUNTING_LOOP: [u8; 4] = [0x52, 0x80, 0x60, 0xfc];
+
+    #[test]
+    fn execute_stops_before_the_instruction_at_a_breakpoint() {
+        let mut core = core_running(&COUNTING_LOOP);
+        let id = core.breakpoints.add(Breakpoint::new(0x1002));
+        let cycles = core.execute(1000);
+        assert_eq!(8, cycles.0);
+        assert_eq!(0x1002, core.pc);
+        assert_eq!(1, core.dar[0]);
+        assert_eq!(Some(id), core.breakpoints.stopped_at());
+        // and resumes from it, until it is hit again
+        core.execute(1000);
+        assert_eq!(0x1002, core.pc);
+        assert_eq!(2, core.dar[0]);
+        assert_eq!(2, core.breakpoints.get(id).unwrap().hits);
+    }
+
+    #[test]
+    fn disabled_breakpoints_do_not_stop_execution() {
+        let mut core = core_running(&COUNTING_LOOP);
+        let id = core.breakpoints.add(Breakpoint::new(0x1002));
+        core.breakpoints.disable(id);
+        core.execute(1000);
+        assert!(core.dar[0] > 50);
+        assert_eq!(None, core.breakpoints.stopped_at());
+        assert_eq!(0, core.breakpoints.get(id).unwrap().hits);
+        core.breakpoints.enable(id);
+        core.execute(1000);
+        assert_eq!(0x1002, core.pc);
+    }
+
+    #[test]
+    fn conditions_can_look_at_registers_and_memory() {
+        // MOVE.W D0, (A0) followed by the counting loop
+        let mut core = core_running(&[0x30, 0x80, 0x52, 0x80, 0x60, 0xfa]);
+        core.dar[8] = 0x2000;
+        let id = core.breakpoints.add(Breakpoint::conditional(0x1002, "D0 >= 3 && (A0).W == D0").unwrap());
+        core.execute(1000);
+        assert_eq!(0x1002, core.pc);
+        assert_eq!(3, core.dar[0]);
+        assert_eq!(Some(id), core.breakpoints.stopped_at());
+        assert!(Breakpoint::conditional(0x1002, "D0 >=").is_none());
+    }
+
+    #[test]
+    fn conditions_do_not_trigger_watchpoints() {
+        use ram::watching::{Watcher, Watchpoint, WatchKind};
+        let mut core = core_running(&COUNTING_LOOP).map_bus(Watcher::new);
+        core.mem.add(Watchpoint::new(WatchKind::Read, 0x2000, 0x2001));
+        let id = core.breakpoints.add(Breakpoint::conditional(0x1002, "D0 >= 3 && ($2000).W == 0").unwrap());
+        core.execute(1000);
+        assert_eq!(Some(id), core.breakpoints.stopped_at());
+        assert_eq!(None, core.watch_hit);
+    }
+
+    #[test]
+    fn ignored_hits_are_counted_but_do_not_stop() {
+        let mut core = core_running(&COUNTING_LOOP);
+        let id = core.breakpoints.add(Breakpoint { ignore: 4, ..Breakpoint::new(0x1000) });
+        assert_eq!(StopReason::Breakpoint(id), core.run_until(&Default::default()));
+        assert_eq!(5, core.breakpoints.get(id).unwrap().hits);
+        assert_eq!(4, core.dar[0]);
+    }
+}
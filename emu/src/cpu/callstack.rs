@@ -0,0 +1,282 @@
+// A shadow call stack of the guest, for backtraces.
+//
+// Once enabled, the core pushes a frame for every JSR, BSR and exception
+// taken, and pops frames when RTS, RTR or RTE moves the stack pointer
+// past them, so stacks unwound some other way are tidied up by the next
+// return. Frames are kept by the interpreters, but not by the JIT.
+//
+// frame_chain walks the LINK/UNLK frames linked through A6 instead, which
+// works without tracking from the start, but only for code using them.
+use std::collections::BTreeMap;
+use std::io::{self, BufRead};
+use cpu::{Core, Callbacks, Cycles, Exception, Result, EXCEPTION_ADDRESS_ERROR, EXCEPTION_BUS_ERROR, EXCEPTION_ILLEGAL_INSTRUCTION, EXCEPTION_PRIVILEGE_VIOLATION};
+use ram::{AddressBus, SUPERVISOR_DATA, USER_DATA};
+use ram::watching::AccessSize;
+
+// frames beyond this depth are dropped from the bottom
+const MAX_DEPTH: usize = 1024;
+// and frame chains are followed this far
+const MAX_LINKS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameKind {
+    Call,
+    Exception(u8), // vector
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    // the PC of the calling instruction, or where the exception was taken
+    pub call_site: u32,
+    // where execution went
+    pub function: u32,
+    // the stack pointer just after the call, and which one it was
+    pub sp: u32,
+    pub supervisor: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallStack {
+    enabled: bool,
+    frames: Vec<Frame>,
+    // of the instruction being run, set while enabled
+    instruction_pc: u32,
+}
+
+impl CallStack {
+    pub fn new() -> CallStack {
+        CallStack::default()
+    }
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+    // also forgets the frames, as they would go stale
+    pub fn disable(&mut self) {
+        self.enabled = false;
+        self.frames.clear();
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    // outermost first
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+    // of the instruction run last
+    pub(crate) fn instruction_pc(&self) -> u32 {
+        self.instruction_pc
+    }
+    fn push(&mut self, frame: Frame) {
+        if self.frames.len() == MAX_DEPTH {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+}
+
+// A link of a LINK/UNLK frame chain
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameLink {
+    pub frame_pointer: u32,
+    pub return_address: u32,
+}
+
+// Guest symbols, to show addresses as the function they are in
+#[derive(Clone, Debug, Default)]
+pub struct Symbols {
+    symbols: BTreeMap<u32, String>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+    // Reads the text symbols from the output of nm, like
+    // 00001000 T main
+    pub fn from_nm<R: BufRead>(reader: R) -> io::Result<Symbols> {
+        let mut symbols = Symbols::new();
+        for line in reader.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if let [address, kind, name] = fields[..] {
+                if let ("T" | "t" | "W" | "w", Ok(address)) = (kind, u32::from_str_radix(address, 16)) {
+                    symbols.insert(address, name);
+                }
+            }
+        }
+        Ok(symbols)
+    }
+    pub fn insert(&mut self, address: u32, name: &str) {
+        self.symbols.insert(address, name.to_string());
+    }
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+    // by address
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.symbols.iter().map(|(&address, name)| (address, name.as_str()))
+    }
+    // the symbol at or before the address, and the offset from it
+    pub fn lookup(&self, address: u32) -> Option<(&str, u32)> {
+        self.symbols.range(..=address).next_back().map(|(&start, name)| (name.as_str(), address - start))
+    }
+    // like main+0x12, or the address if there is no symbol before it
+    pub fn describe(&self, address: u32) -> String {
+        match self.lookup(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+0x{:x}", name, offset),
+            None => format!("{:08x}", address),
+        }
+    }
+}
+
+fn exception_vector(ex: &Exception) -> u8 {
+    match *ex {
+        Exception::AddressError { .. } => EXCEPTION_ADDRESS_ERROR,
+        Exception::BusError { .. } => EXCEPTION_BUS_ERROR,
+        Exception::IllegalInstruction(_, _) => EXCEPTION_ILLEGAL_INSTRUCTION,
+        Exception::Trap(vector, _) => vector,
+        Exception::PrivilegeViolation(_, _) => EXCEPTION_PRIVILEGE_VIOLATION,
+        Exception::UnimplementedInstruction(_, _, vector) => vector,
+        Exception::Interrupt(_, vector) => vector,
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    // complete_step, while the call stack is enabled
+    pub(crate) fn dispatch_tracking_calls<T: Callbacks<A>>(&mut self, result: Result<Cycles>, state: &mut T) -> Cycles {
+        let vector = result.as_ref().err().map(exception_vector);
+        let (pc, ssp) = (self.pc, self.ssp());
+        let cycles = self.dispatch_exception(result, state);
+        match vector {
+            // unless the host handled it, or the core halted
+            Some(vector) => if self.ssp() < ssp {
+                self.call_stack.push(Frame { kind: FrameKind::Exception(vector), call_site: pc, function: self.pc, sp: self.ssp(), supervisor: true });
+            },
+            None => match self.ir {
+                // JSR and BSR
+                ir if ir & 0xffc0 == 0x4e80 || ir & 0xff00 == 0x6100 => {
+                    let call_site = self.call_stack.instruction_pc;
+                    let (sp, supervisor) = (self.dar[15], self.s_flag != 0);
+                    self.call_stack.push(Frame { kind: FrameKind::Call, call_site, function: self.pc, sp, supervisor });
+                },
+                // RTE, RTS and RTR
+                0x4e73 | 0x4e75 | 0x4e77 => {
+                    while let Some(frame) = self.call_stack.frames.last().cloned() {
+                        let sp = if frame.supervisor { self.ssp() } else { self.usp() };
+                        if frame.sp >= sp {
+                            break;
+                        }
+                        self.call_stack.frames.pop();
+                    }
+                },
+                _ => (),
+            }
+        }
+        cycles
+    }
+    pub(crate) fn track_instruction_pc(&mut self) {
+        // the opcode has been read already
+        self.call_stack.instruction_pc = self.pc.wrapping_sub(2);
+    }
+    // The PC followed by the shadow call stack, innermost first, like
+    // #0  00001010 delay+0x4
+    // #1  00001006 main+0x6
+    pub fn backtrace(&self, symbols: &Symbols) -> String {
+        let mut backtrace = format!("#0  {:08x} {}\n", self.pc, symbols.describe(self.pc));
+        for (depth, frame) in self.call_stack.frames().iter().rev().enumerate() {
+            let how = match frame.kind {
+                FrameKind::Call => String::new(),
+                FrameKind::Exception(vector) => format!(" (exception, vector {})", vector),
+            };
+            backtrace.push_str(&format!("#{:<2} {:08x} {}{}\n", depth + 1, frame.call_site, symbols.describe(frame.call_site), how));
+        }
+        backtrace
+    }
+    // Follows the frames LINK A6 leaves, from A6 outwards
+    pub fn frame_chain(&self) -> Vec<FrameLink> {
+        let space = if self.s_flag != 0 { SUPERVISOR_DATA } else { USER_DATA };
+        let mut links = Vec::new();
+        let mut frame_pointer = self.dar[14];
+        while frame_pointer != 0 && frame_pointer & 1 == 0 && links.len() < MAX_LINKS {
+            let saved = self.mem.peek(space, frame_pointer, AccessSize::Long);
+            let return_address = self.mem.peek(space, frame_pointer.wrapping_add(4), AccessSize::Long);
+            links.push(FrameLink { frame_pointer, return_address });
+            // outer frames are higher up the stack
+            if saved <= frame_pointer {
+                break;
+            }
+            frame_pointer = saved;
+        }
+        links
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Symbols, Frame, FrameKind, FrameLink};
+    use cpu::Core;
+    use ram::pagedmem::PagedMem;
+    use testing::load;
+
+    fn core_tracking_calls(program: &[(u32, &[u8])]) -> Core<PagedMem> {
+        let mut mem = PagedMem::new(0);
+        for &(start, bytes) in program {
+            load(&mut mem, start, bytes);
+        }
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x8000;
+        core.call_stack.enable();
+        core
+    }
+
+    #[test]
+    fn calls_are_pushed_and_returns_pop_them() {
+        // main: BSR.S outer; NOP; outer: JSR inner; RTS; inner: NOP; RTS
+        let mut core = core_tracking_calls(&[(0x1000, &[0x61, 0x04, 0x4e, 0x71, 0x60, 0xfe,
+            0x4e, 0xb9, 0x00, 0x00, 0x10, 0x10, 0x4e, 0x75, 0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x75])]);
+        core.run_until_pc(0x1012);
+        let frames: Vec<(u32, u32)> = core.call_stack.frames().iter().map(|frame| (frame.call_site, frame.function)).collect();
+        assert_eq!(vec![(0x1000, 0x1006), (0x1006, 0x1010)], frames);
+        let mut symbols = Symbols::new();
+        symbols.insert(0x1000, "main");
+        symbols.insert(0x1006, "outer");
+        symbols.insert(0x1010, "inner");
+        assert_eq!("#0  00001012 inner+0x2\n#1  00001006 outer\n#2  00001000 main\n", core.backtrace(&symbols));
+        core.run_until_pc(0x1002);
+        assert!(core.call_stack.frames().is_empty());
+    }
+
+    #[test]
+    fn exceptions_are_frames_until_rte() {
+        // TRAP #0; NOP, with a handler of NOP; RTE
+        let mut core = core_tracking_calls(&[(0x80, &[0x00, 0x00, 0x11, 0x00]), (0x1000, &[0x4e, 0x40, 0x4e, 0x71]), (0x1100, &[0x4e, 0x71, 0x4e, 0x73])]);
+        core.run_until_pc(0x1102);
+        assert_eq!(&[Frame { kind: FrameKind::Exception(32), call_site: 0x1002, function: 0x1100, sp: 0x7ffa, supervisor: true }], core.call_stack.frames());
+        core.run_until_pc(0x1002);
+        assert!(core.call_stack.frames().is_empty());
+    }
+
+    #[test]
+    fn link_frames_can_be_walked() {
+        // LINK A6, #0; BSR.S to LINK A6, #-8
+        let mut core = core_tracking_calls(&[(0x1000, &[0x4e, 0x56, 0x00, 0x00, 0x61, 0x02, 0x4e, 0x71, 0x4e, 0x56, 0xff, 0xf8, 0x4e, 0x71])]);
+        core.call_stack.disable();
+        core.run_until_pc(0x100c);
+        let expected = vec![FrameLink { frame_pointer: 0x7ff4, return_address: 0x1006 }, FrameLink { frame_pointer: 0x7ffc, return_address: 0 }];
+        assert_eq!(expected, core.frame_chain());
+    }
+
+    #[test]
+    fn symbols_are_read_from_nm_output() {
+        let nm = "00001000 T main\n00000400 d data\n00001020 t helper\n         U undefined\n";
+        let symbols = Symbols::from_nm(nm.as_bytes()).unwrap();
+        assert_eq!("main+0x1f", symbols.describe(0x101f));
+        assert_eq!("helper", symbols.describe(0x1020));
+        assert_eq!("00000fff", symbols.describe(0xfff));
+    }
+}
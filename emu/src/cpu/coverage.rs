@@ -0,0 +1,267 @@
+// Coverage of guest code: which instructions ran, how often, and which
+// ways the conditional branches (Bcc and DBcc) went.
+//
+// run_covered counts the instruction run at each step, using the
+// instruction PC the shadow call stack keeps (so it enables it), which
+// also counts the first instruction of exception handlers correctly.
+//
+// write_lcov reports coverage against a LineMap, from the line map the
+// assembler gives or from symbols alone, as an lcov .info file. Branches
+// are only known to be branches once they have run, so branch totals
+// only count those.
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::io::{self, Write};
+use cpu::{Core, StopConditions, StopReason, Symbols};
+use ram::AddressBus;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Branch {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    hits: BTreeMap<u32, u64>,
+    branches: BTreeMap<u32, Branch>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+    // the number of times each instruction ran, by address
+    pub fn hits(&self) -> &BTreeMap<u32, u64> {
+        &self.hits
+    }
+    pub fn branches(&self) -> &BTreeMap<u32, Branch> {
+        &self.branches
+    }
+    pub fn is_executed(&self, address: u32) -> bool {
+        self.hits.contains_key(&address)
+    }
+    // adds the coverage of another run, like another test of a suite
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&address, &hits) in &other.hits {
+            *self.hits.entry(address).or_insert(0) += hits;
+        }
+        for (&address, branch) in &other.branches {
+            let ours = self.branches.entry(address).or_default();
+            ours.taken += branch.taken;
+            ours.not_taken += branch.not_taken;
+        }
+    }
+    pub fn clear(&mut self) {
+        self.hits.clear();
+        self.branches.clear();
+    }
+    // Writes a record per file of the line map, with the functions the
+    // symbols start in it, the branches and the lines
+    pub fn write_lcov<W: Write>(&self, test_name: &str, lines: &LineMap, symbols: &Symbols, writer: &mut W) -> io::Result<()> {
+        let mut files: BTreeMap<usize, BTreeMap<u32, Vec<u32>>> = BTreeMap::new();
+        for (&address, &(file, line)) in &lines.lines {
+            files.entry(file).or_default().entry(line).or_default().push(address);
+        }
+        for (file, file_lines) in files {
+            writeln!(writer, "TN:{}", test_name)?;
+            writeln!(writer, "SF:{}", lines.files[file])?;
+            let functions: Vec<(u32, &str, u64)> = symbols.iter()
+                .filter_map(|(address, name)| match lines.lines.get(&address) {
+                    Some(&(in_file, line)) if in_file == file => Some((line, name, self.hits.get(&address).cloned().unwrap_or(0))),
+                    _ => None,
+                })
+                .collect();
+            for &(line, name, _) in &functions {
+                writeln!(writer, "FN:{},{}", line, name)?;
+            }
+            for &(_, name, hits) in &functions {
+                writeln!(writer, "FNDA:{},{}", hits, name)?;
+            }
+            writeln!(writer, "FNF:{}", functions.len())?;
+            writeln!(writer, "FNH:{}", functions.iter().filter(|function| function.2 > 0).count())?;
+            let (mut found, mut hit) = (0, 0);
+            for (&line, addresses) in &file_lines {
+                let branches = addresses.iter().filter_map(|address| self.branches.get(address));
+                for (block, branch) in branches.enumerate() {
+                    for (number, &count) in [branch.taken, branch.not_taken].iter().enumerate() {
+                        writeln!(writer, "BRDA:{},{},{},{}", line, block, number, count)?;
+                        found += 1;
+                        if count > 0 {
+                            hit += 1;
+                        }
+                    }
+                }
+            }
+            writeln!(writer, "BRF:{}", found)?;
+            writeln!(writer, "BRH:{}", hit)?;
+            let (mut found, mut hit) = (0, 0);
+            for (&line, addresses) in &file_lines {
+                let count = addresses.iter().map(|address| self.hits.get(address).cloned().unwrap_or(0)).max().unwrap_or(0);
+                writeln!(writer, "DA:{},{}", line, count)?;
+                found += 1;
+                if count > 0 {
+                    hit += 1;
+                }
+            }
+            writeln!(writer, "LF:{}", found)?;
+            writeln!(writer, "LH:{}", hit)?;
+            writeln!(writer, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+// Source lines by instruction address
+#[derive(Clone, Debug, Default)]
+pub struct LineMap {
+    files: Vec<String>,
+    lines: BTreeMap<u32, (usize, u32)>,
+}
+
+impl LineMap {
+    pub fn new() -> LineMap {
+        LineMap::default()
+    }
+    // Without sources, functions can stand in for files, and the words
+    // from their start for lines
+    pub fn from_symbols(symbols: &Symbols, coverage: &Coverage) -> LineMap {
+        let mut map = LineMap::new();
+        let addresses = symbols.iter().map(|(address, _)| address).chain(coverage.hits.keys().cloned());
+        for address in addresses {
+            if let Some((name, offset)) = symbols.lookup(address) {
+                let name = name.to_string();
+                map.insert(address, &name, offset / 2 + 1);
+            }
+        }
+        map
+    }
+    // the addresses and lines the assembler maps, for one file
+    pub fn add_file(&mut self, file: &str, lines: &[(u32, usize)]) {
+        for &(address, line) in lines {
+            self.insert(address, file, line as u32);
+        }
+    }
+    pub fn insert(&mut self, address: u32, file: &str, line: u32) {
+        let file = match self.files.iter().position(|known| known == file) {
+            Some(index) => index,
+            None => {
+                self.files.push(file.to_string());
+                self.files.len() - 1
+            }
+        };
+        self.lines.insert(address, (file, line));
+    }
+    pub fn lookup(&self, address: u32) -> Option<(&str, u32)> {
+        self.lines.get(&address).map(|&(file, line)| (self.files[file].as_str(), line))
+    }
+}
+
+// The length of conditional branches (not BRA or BSR), by opcode
+fn conditional_branch_length(ir: u16) -> Option<u32> {
+    if ir & 0xf000 == 0x6000 && ir & 0x0e00 != 0 {
+        Some(if ir & 0xff == 0 { 4 } else { 2 })
+    } else if ir & 0xf0f8 == 0x50c8 {
+        Some(4)
+    } else {
+        None
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    // As run_until, but collecting coverage. Calls are tracked for the
+    // run, unless they already were.
+    pub fn run_covered(&mut self, conditions: &StopConditions, coverage: &mut Coverage) -> StopReason {
+        let tracking = self.call_stack.is_enabled();
+        self.call_stack.enable();
+        let result = self.run_stepwise::<Infallible, _>(conditions, |core, single| {
+            let executed = core.elapsed_instructions;
+            let reason = core.run_until(single);
+            if core.elapsed_instructions > executed {
+                let pc = core.call_stack.instruction_pc();
+                *coverage.hits.entry(pc).or_insert(0) += 1;
+                if let Some(length) = conditional_branch_length(core.ir) {
+                    let branch = coverage.branches.entry(pc).or_default();
+                    if core.pc == pc.wrapping_add(length) {
+                        branch.not_taken += 1;
+                    } else {
+                        branch.taken += 1;
+                    }
+                }
+            }
+            Ok(reason)
+        });
+        if !tracking {
+            self.call_stack.disable();
+        }
+        match result {
+            Ok(reason) => reason,
+            Err(never) => match never {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Branch, Coverage, LineMap};
+    use cpu::{StopConditions, StopReason, Symbols};
+    use testing::core_running;
+
+    // MOVEQ #2, D0; DBF D0, *; NOP; BEQ.S to itself, never taken
+    fn covered() -> Coverage {
+        let mut core = core_running(&[0x70, 0x02, 0x51, 0xc8, 0xff, 0xfe, 0x4e, 0x71, 0x67, 0xfe]);
+        core.dar[15] = 0x8000;
+        let mut coverage = Coverage::new();
+        assert_eq!(StopReason::Pc(0x100a), core.run_covered(&StopConditions { pc: Some(0x100a), ..Default::default() }, &mut coverage));
+        assert!(!core.call_stack.is_enabled());
+        coverage
+    }
+
+    fn lcov(coverage: &Coverage, lines: &LineMap, symbols: &Symbols) -> String {
+        let mut lcov = Vec::new();
+        coverage.write_lcov("test", lines, symbols, &mut lcov).unwrap();
+        String::from_utf8(lcov).unwrap()
+    }
+
+    #[test]
+    fn instructions_and_branch_directions_are_counted() {
+        let coverage = covered();
+        let hits: Vec<(u32, u64)> = coverage.hits().iter().map(|(&address, &hits)| (address, hits)).collect();
+        assert_eq!(vec![(0x1000, 1), (0x1002, 3), (0x1006, 1), (0x1008, 1)], hits);
+        let branches: Vec<(u32, Branch)> = coverage.branches().iter().map(|(&address, &branch)| (address, branch)).collect();
+        assert_eq!(vec![(0x1002, Branch { taken: 2, not_taken: 1 }), (0x1008, Branch { taken: 0, not_taken: 1 })], branches);
+    }
+
+    #[test]
+    fn runs_can_be_merged() {
+        let mut coverage = covered();
+        coverage.merge(&covered());
+        assert_eq!(Some(&6), coverage.hits().get(&0x1002));
+        assert_eq!(Some(&Branch { taken: 4, not_taken: 2 }), coverage.branches().get(&0x1002));
+    }
+
+    #[test]
+    fn lcov_reports_lines_from_the_assembler() {
+        let mut lines = LineMap::new();
+        lines.add_file("loop.s", &[(0x1000, 2), (0x1002, 3), (0x1006, 4), (0x1008, 5), (0x100a, 7)]);
+        let mut symbols = Symbols::new();
+        symbols.insert(0x1000, "start");
+        symbols.insert(0x100a, "unused");
+        let expected = "TN:test\nSF:loop.s\nFN:2,start\nFN:7,unused\nFNDA:1,start\nFNDA:0,unused\nFNF:2\nFNH:1\n\
+            BRDA:3,0,0,2\nBRDA:3,0,1,1\nBRDA:5,0,0,0\nBRDA:5,0,1,1\nBRF:4\nBRH:3\n\
+            DA:2,1\nDA:3,3\nDA:4,1\nDA:5,1\nDA:7,0\nLF:5\nLH:4\nend_of_record\n";
+        assert_eq!(expected, lcov(&covered(), &lines, &symbols));
+    }
+
+    #[test]
+    fn symbols_can_stand_in_for_sources() {
+        let coverage = covered();
+        let mut symbols = Symbols::new();
+        symbols.insert(0x1000, "start");
+        symbols.insert(0x1006, "tail");
+        let lines = LineMap::from_symbols(&symbols, &coverage);
+        assert_eq!(Some(("start", 2)), lines.lookup(0x1002));
+        assert_eq!(Some(("tail", 2)), lines.lookup(0x1008));
+        assert!(lcov(&coverage, &lines, &symbols).contains("SF:tail\nFN:1,tail\nFNDA:1,tail\n"));
+    }
+}
@@ -1,92 +1,93 @@
 use super::{Core, Result};
+use ram::AddressBus;
 use std::num::Wrapping;
 
-pub fn absolute_word(core: &mut Core) -> Result<u32> {
+pub fn absolute_word<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     core.read_imm_i16().map(|res| res as u32)
 }
-pub fn absolute_long(core: &mut Core) -> Result<u32> {
+pub fn absolute_long<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     core.read_imm_u32()
 }
-pub fn predecrement_ay_8(core: &mut Core) -> Result<u32> {
+pub fn predecrement_ay_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
     Ok(predecrement_8(core, reg_ndx))
 }
-pub fn postincrement_ay_8(core: &mut Core) -> Result<u32> {
+pub fn postincrement_ay_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
     Ok(postincrement_8(core, reg_ndx))
 }
-pub fn predecrement_ay_16(core: &mut Core) -> Result<u32> {
+pub fn predecrement_ay_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
     Ok(predecrement_16(core, reg_ndx))
 }
-pub fn postincrement_ay_16(core: &mut Core) -> Result<u32> {
+pub fn postincrement_ay_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
     Ok(postincrement_16(core, reg_ndx))
 }
-pub fn predecrement_ay_32(core: &mut Core) -> Result<u32> {
+pub fn predecrement_ay_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
     Ok(predecrement_32(core, reg_ndx))
 }
-pub fn postincrement_ay_32(core: &mut Core) -> Result<u32> {
+pub fn postincrement_ay_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ay!(core);
     Ok(postincrement_32(core, reg_ndx))
 }
-pub fn address_indirect_ay(core: &mut Core) -> Result<u32> {
+pub fn address_indirect_ay<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok(ay!(core))
 }
-pub fn address_indirect_ax(core: &mut Core) -> Result<u32> {
+pub fn address_indirect_ax<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok(ax!(core))
 }
-pub fn displacement_ay(core: &mut Core) -> Result<u32> {
+pub fn displacement_ay<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_val = core.dar[ir_ay!(core)];
     displacement(core, reg_val)
 }
-pub fn displacement_ax(core: &mut Core) -> Result<u32> {
+pub fn displacement_ax<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_val = core.dar[ir_ax!(core)];
     displacement(core, reg_val)
 }
-pub fn displacement_pc(core: &mut Core) -> Result<u32> {
+pub fn displacement_pc<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let old_pc = core.pc;
     displacement(core, old_pc)
 }
-pub fn index_ay(core: &mut Core) -> Result<u32> {
+pub fn index_ay<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_val = core.dar[ir_ay!(core)];
     index(core, reg_val)
 }
-pub fn index_ax(core: &mut Core) -> Result<u32> {
+pub fn index_ax<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_val = core.dar[ir_ax!(core)];
     index(core, reg_val)
 }
-pub fn index_pc(core: &mut Core) -> Result<u32> {
+pub fn index_pc<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let pc = core.pc;
     index(core, pc)
 }
-pub fn predecrement_ax_8(core: &mut Core) -> Result<u32> {
+pub fn predecrement_ax_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
     Ok(predecrement_8(core, reg_ndx))
 }
-pub fn predecrement_ax_16(core: &mut Core) -> Result<u32> {
+pub fn predecrement_ax_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
     Ok(predecrement_16(core, reg_ndx))
 }
-pub fn predecrement_ax_32(core: &mut Core) -> Result<u32> {
+pub fn predecrement_ax_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
     Ok(predecrement_32(core, reg_ndx))
 }
-pub fn postincrement_ax_8(core: &mut Core) -> Result<u32> {
+pub fn postincrement_ax_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
     Ok(postincrement_8(core, reg_ndx))
 }
-pub fn postincrement_ax_16(core: &mut Core) -> Result<u32> {
+pub fn postincrement_ax_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
     Ok(postincrement_16(core, reg_ndx))
 }
-pub fn postincrement_ax_32(core: &mut Core) -> Result<u32> {
+pub fn postincrement_ax_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     let reg_ndx = ir_ax!(core);
     Ok(postincrement_32(core, reg_ndx))
 }
 
-fn predecrement_8(core: &mut Core, reg_ndx: usize) -> u32 {
+fn predecrement_8<A: AddressBus>(core: &mut Core<A>, reg_ndx: usize) -> u32 {
     // pre-decrement
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) - match reg_ndx {
         15 => Wrapping(2), // A7 is kept even
@@ -94,7 +95,7 @@ fn predecrement_8(core: &mut Core, reg_ndx: usize) -> u32 {
     }).0;
     core.dar[reg_ndx]
 }
-fn postincrement_8(core: &mut Core, reg_ndx: usize) -> u32 {
+fn postincrement_8<A: AddressBus>(core: &mut Core<A>, reg_ndx: usize) -> u32 {
     // post-increment
     let ea = core.dar[reg_ndx];
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) + match reg_ndx {
@@ -103,36 +104,36 @@ fn postincrement_8(core: &mut Core, reg_ndx: usize) -> u32 {
     }).0;
     ea
 }
-fn predecrement_16(core: &mut Core, reg_ndx: usize) -> u32 {
+fn predecrement_16<A: AddressBus>(core: &mut Core<A>, reg_ndx: usize) -> u32 {
     // pre-decrement
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) - Wrapping(2)).0;
     core.dar[reg_ndx]
 }
-fn postincrement_16(core: &mut Core, reg_ndx: usize) -> u32 {
+fn postincrement_16<A: AddressBus>(core: &mut Core<A>, reg_ndx: usize) -> u32 {
     // post-increment
     let ea = core.dar[reg_ndx];
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) + Wrapping(2)).0;
     ea
 }
-fn predecrement_32(core: &mut Core, reg_ndx: usize) -> u32 {
+fn predecrement_32<A: AddressBus>(core: &mut Core<A>, reg_ndx: usize) -> u32 {
     // pre-decrement
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) - Wrapping(4)).0;
     core.dar[reg_ndx]
 }
-fn postincrement_32(core: &mut Core, reg_ndx: usize) -> u32 {
+fn postincrement_32<A: AddressBus>(core: &mut Core<A>, reg_ndx: usize) -> u32 {
     // post-increment
     let ea = core.dar[reg_ndx];
     core.dar[reg_ndx] = (Wrapping(core.dar[reg_ndx]) + Wrapping(4)).0;
     ea
 }
-pub fn displacement(core: &mut Core, reg_val: u32) -> Result<u32> {
+pub fn displacement<A: AddressBus>(core: &mut Core<A>, reg_val: u32) -> Result<u32> {
     let displacement = try!(core.read_imm_i16());
     let ea = (Wrapping(reg_val) + Wrapping(displacement as u32)).0;
     Ok(ea)
 }
 // Brief Extension Word format (see M68000 PRM section 2.1)
 const LONG_INDEX_MASK: u16 = 0x0800;
-fn index(core: &mut Core, reg_val: u32) -> Result<u32> {
+fn index<A: AddressBus>(core: &mut Core<A>, reg_val: u32) -> Result<u32> {
     let extension = try!(core.read_imm_u16());
     // top four bits = (D/A RRR) matches our register array layout
     let xreg_ndx = (extension>>12) as usize;
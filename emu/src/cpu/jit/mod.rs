@@ -0,0 +1,423 @@
+// Dynamic recompiler for x86-64 hosts.
+//
+// Straight-line runs of guest instructions are recorded while being
+// interpreted, and then translated into native blocks. The common
+// register-only instructions (see native) become host code operating on
+// the fields of the core directly; anything else becomes a call into
+// the interpreter step for that PC. A branch back to the start of the
+// block loops within it. The opcode fetch is done inline when the
+// prefetched long already holds it, and through the bus otherwise, so
+// instructions, exceptions, cycle counts and bus accesses are all
+// identical to the interpreter. The interpreter is used for anything not
+// yet translated, and to take interrupts.
+//
+// Blocks are kept in a ram::tracking::CodeBlocks, so the bus has to
+// implement CodeWrites.
+mod native;
+mod x86_64;
+
+use std::marker::PhantomData;
+use std::mem;
+use cpu::{Core, Cycles, EmulateAllExceptions};
+use ram::AddressBus;
+use ram::tracking::{CodeWrites, CodeBlocks, MAX_INSTRUCTION_BYTES, watch_instruction};
+use self::native::{Layout, Op};
+use self::x86_64::{Cond, Emitter, ExecutableBlock, RAX, RBX, R12};
+
+const MAX_BLOCK_INSTRUCTIONS: usize = 64;
+
+const CONTINUE: u32 = 0;
+const EXIT: u32 = 1;
+
+// Passed by pointer through the translated code to every helper
+#[repr(C)]
+struct Context<A: AddressBus> {
+    core: *mut Core<A>,
+    remaining: i32,
+    // the mode the block was recorded in
+    supervisor: bool,
+}
+
+// Runs an instruction not translated
+extern "C" fn step<A: AddressBus + CodeWrites>(context: *mut Context<A>, pc: u32) -> u32 {
+    let context = unsafe { &mut *context };
+    let core = unsafe { &mut *context.core };
+    // leave the block whenever the interpreter would have stopped, or
+    // control didn't flow the way it did when the block was recorded
+    if context.remaining <= 0 || core.pc != pc || (core.s_flag != 0) != context.supervisor
+        || core.is_idle() || core.mem.code_written() {
+        return EXIT;
+    }
+    context.remaining -= core.step(&mut EmulateAllExceptions).0;
+    CONTINUE
+}
+
+// Checks that translated instructions can run, on entering the block
+// and after instructions run by step
+extern "C" fn sync<A: AddressBus + CodeWrites>(context: *mut Context<A>, pc: u32) -> u32 {
+    let context = unsafe { &mut *context };
+    let core = unsafe { &mut *context.core };
+    if core.pc != pc || (core.s_flag != 0) != context.supervisor || core.is_idle()
+        || core.mem.code_written() || core.pending_interrupt().is_some() {
+        return EXIT;
+    }
+    CONTINUE
+}
+
+// Fetches the opcode at PC through the bus, as read_instruction would,
+// for translated instructions not in the prefetched long
+extern "C" fn fetch<A: AddressBus + CodeWrites>(context: *mut Context<A>, pc: u32) -> u32 {
+    let context = unsafe { &mut *context };
+    let core = unsafe { &mut *context.core };
+    core.pc = pc;
+    match core.read_imm_u16() {
+        Ok(_) => CONTINUE,
+        Err(ex) => {
+            context.remaining -= core.complete_step(Err(ex), &mut EmulateAllExceptions).0;
+            EXIT
+        }
+    }
+}
+
+pub struct Jit<A: AddressBus + CodeWrites> {
+    blocks: CodeBlocks<ExecutableBlock>,
+    layout: Layout,
+    bus: PhantomData<A>,
+}
+
+impl<A: AddressBus + CodeWrites> Jit<A> {
+    pub fn new() -> Jit<A> {
+        Jit { blocks: CodeBlocks::new(), layout: Layout::of::<A>(), bus: PhantomData }
+    }
+    // number of blocks currently translated
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+    // number of blocks ever translated, and dropped due to code writes
+    pub fn translated(&self) -> usize {
+        self.blocks.built()
+    }
+    pub fn invalidated(&self) -> usize {
+        self.blocks.invalidated()
+    }
+    // Watches the pages of all translated blocks on the bus of another
+    // core, so that cores running the same code can share translations
+    pub fn attach(&self, core: &mut Core<A>) {
+        self.blocks.attach(&mut core.mem);
+    }
+    // Drops all blocks, like after loading new code without going
+    // through the bus
+    pub fn flush(&mut self, core: &mut Core<A>) {
+        self.blocks.flush(&mut core.mem);
+    }
+
+    // Same contract as Core::execute
+    pub fn execute(&mut self, core: &mut Core<A>, cycles: i32) -> Cycles {
+        let cycles = Cycles(cycles);
+        let mut remaining_cycles = cycles;
+        while remaining_cycles.any() && !core.is_idle() {
+            self.blocks.invalidate_written(&mut core.mem);
+            // the shadow call stack is tracked by the interpreter only
+            if core.call_stack.is_enabled() {
+                remaining_cycles = remaining_cycles - core.step(&mut EmulateAllExceptions);
+                continue;
+            }
+            let key = (core.pc, core.s_flag != 0);
+            remaining_cycles = match self.blocks.get(&key) {
+                Some(block) => {
+                    let mut context = Context { core: core as *mut Core<A>, remaining: remaining_cycles.0, supervisor: key.1 };
+                    unsafe { block.run(&mut context as *mut Context<A> as *mut u8) };
+                    if context.remaining == remaining_cycles.0 {
+                        // left without running anything, like when an
+                        // interrupt is to be taken
+                        remaining_cycles - core.step(&mut EmulateAllExceptions)
+                    } else {
+                        Cycles(context.remaining)
+                    }
+                }
+                None => self.record(core, remaining_cycles)
+            };
+        }
+        core.cycles_consumed(cycles, remaining_cycles)
+    }
+
+    // Interprets from PC until control flow leaves the straight line,
+    // and translates what was run
+    fn record(&mut self, core: &mut Core<A>, mut remaining_cycles: Cycles) -> Cycles {
+        let key = (core.pc, core.s_flag != 0);
+        let mut instructions = Vec::new();
+        let mut pages = Vec::new();
+        loop {
+            let pc = core.pc;
+            watch_instruction(&mut core.mem, pc, &mut pages);
+            let executed = core.elapsed_instructions;
+            remaining_cycles = remaining_cycles - core.step(&mut EmulateAllExceptions);
+            // the opcode, unless an interrupt was taken instead
+            let opcode = if core.elapsed_instructions > executed { Some(core.ir) } else { None };
+            instructions.push((pc, opcode));
+            let falls_through = core.pc > pc && core.pc - pc <= MAX_INSTRUCTION_BYTES;
+            if !falls_through || (core.s_flag != 0) != key.1 || instructions.len() == MAX_BLOCK_INSTRUCTIONS
+                || !remaining_cycles.any() || core.is_idle() || core.mem.code_written() {
+                break;
+            }
+        }
+        // code that modified itself while being recorded is left to the
+        // interpreter until it settles down
+        if core.mem.code_written() {
+            self.blocks.discard(&mut core.mem, pages);
+        } else {
+            let code = ExecutableBlock::new(&self.translate(key.0, &instructions));
+            self.blocks.insert(key, code, pages);
+        }
+        remaining_cycles
+    }
+
+    fn translate(&self, start: u32, instructions: &[(u32, Option<u16>)]) -> Vec<u8> {
+        let l = &self.layout;
+        let mut e = Emitter::new();
+        let exit = e.exit();
+        let remaining = mem::offset_of!(Context<A>, remaining) as i32;
+        e.load64(R12, RBX, mem::offset_of!(Context<A>, core) as i32);
+        let call_checked = |e: &mut Emitter, function: usize, pc: u32| {
+            e.call(function, Some(pc));
+            e.test32(RAX, RAX);
+            e.jump_if(Cond::NotEqual, exit);
+        };
+        let top = e.label();
+        // whether the state of the core is known to be what translated
+        // code expects
+        let mut synced = false;
+        for (index, &(pc, opcode)) in instructions.iter().enumerate() {
+            let native = match opcode {
+                Some(opcode) if pc & 1 == 0 => native::decode(opcode).map(|(op, cycles)| (opcode, op, cycles)),
+                _ => None,
+            };
+            let (opcode, op, cycles) = match native {
+                Some(native) => native,
+                None => {
+                    if index == 0 {
+                        e.bind(top);
+                    }
+                    call_checked(&mut e, step::<A> as *const () as usize, pc);
+                    synced = false;
+                    continue;
+                }
+            };
+            if !synced {
+                call_checked(&mut e, sync::<A> as *const () as usize, pc);
+                synced = true;
+            }
+            if index == 0 {
+                e.bind(top);
+            }
+            e.cmp32_mem_imm(RBX, remaining, 0);
+            e.jump_if(Cond::LessOrEqual, exit);
+            // what read_instruction does
+            let prefetched = e.label();
+            e.cmp32_mem_imm(R12, l.prefetch_addr, (pc & !3) as i32);
+            e.jump_if(Cond::Equal, prefetched);
+            call_checked(&mut e, fetch::<A> as *const () as usize, pc);
+            e.bind(prefetched);
+            // and start_instruction
+            e.store16_imm(R12, l.ir, opcode);
+            e.add64_mem_imm(R12, l.elapsed_instructions, 1);
+            let next = pc.wrapping_add(2);
+            let account = |e: &mut Emitter, cycles: i32, pc: u32| {
+                e.add64_mem_imm(R12, l.elapsed_cycles, cycles);
+                e.sub32_mem_imm(RBX, remaining, cycles);
+                e.store32_imm(R12, l.pc, pc);
+            };
+            match op {
+                Op::Branch { taken, offset } => {
+                    let target = next.wrapping_add(offset as u32);
+                    // either way, control continues with the instruction
+                    // recorded next, loops back, or leaves the block
+                    let continues = instructions.get(index + 1).map(|&(pc, _)| pc);
+                    let done = e.label();
+                    let follow = |e: &mut Emitter, pc: u32| {
+                        if continues == Some(pc) {
+                            e.jump(done);
+                        } else if pc == start {
+                            e.jump(top);
+                        } else {
+                            e.jump(exit);
+                        }
+                    };
+                    let not_taken = e.label();
+                    if taken != 0xffff {
+                        native::emit_condition(&mut e, l, taken);
+                        e.jump_if(Cond::AboveOrEqual, not_taken);
+                    }
+                    account(&mut e, cycles, target);
+                    follow(&mut e, target);
+                    e.bind(not_taken);
+                    if taken != 0xffff {
+                        account(&mut e, 8, next);
+                        follow(&mut e, next);
+                    }
+                    e.bind(done);
+                }
+                op => {
+                    native::emit_op(&mut e, l, op);
+                    account(&mut e, cycles, next);
+                }
+            }
+        }
+        e.finish()
+    }
+}
+
+impl<A: AddressBus + CodeWrites> Default for Jit<A> {
+    fn default() -> Jit<A> {
+        Jit::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Jit;
+    use super::native::{self, Op};
+    use cpu::Core;
+    use ram::AddressBus;
+    use ram::loggingmem::{LoggingMem, OpsLogger};
+    use ram::tracking::WriteTracker;
+    use ram::SUPERVISOR_PROGRAM;
+    use testing::{core_on, core_running};
+
+    // MOVE.B D0, (A0)+ followed by ADDQ.B #1, D0 and BRA.S back to the move
+    const FILLING_LOOP: [u8; 6] = [0x10, 0xc0, 0x52, 0x00, 0x60, 0xfa];
+    // MOVE.W D1, (A0) overwriting the following ADDQ.L #1, D0, then
+    // BRA.S back to the move
+    const PATCHING_LOOP: [u8; 6] = [0x30, 0x81, 0x52, 0x80, 0x60, 0xfa];
+
+    fn assert_same_state<A: AddressBus, B: AddressBus>(expected: &Core<A>, actual: &Core<B>) {
+        assert_eq!(expected.pc, actual.pc);
+        assert_eq!(expected.dar, actual.dar);
+        assert_eq!(expected.status_register(), actual.status_register());
+        assert_eq!(expected.processing_state, actual.processing_state);
+    }
+
+    #[test]
+    fn runs_like_the_interpreter() {
+        let mut interpreted = core_running(&FILLING_LOOP);
+        interpreted.dar[8] = 0x4000;
+        let mut jitted = core_running(&FILLING_LOOP).map_bus(WriteTracker::new);
+        jitted.dar[8] = 0x4000;
+        let mut jit = Jit::new();
+        for quantum in 1..50 {
+            assert_eq!(interpreted.execute(quantum * 7), jit.execute(&mut jitted, quantum * 7));
+            assert_same_state(&interpreted, &jitted);
+        }
+        assert!(!jit.is_empty());
+        let jitted = jitted.map_bus(WriteTracker::into_inner);
+        assert_eq!(interpreted.mem.read_u8(0x4100), jitted.mem.read_u8(0x4100));
+    }
+
+    #[test]
+    fn makes_the_same_bus_accesses_as_the_interpreter() {
+        let logging_core = || {
+            core_on(LoggingMem::new(0xaaaaaaaa, OpsLogger::new()), &FILLING_LOOP)
+        };
+        let mut interpreted = logging_core();
+        interpreted.dar[8] = 0x4000;
+        let mut jitted = logging_core().map_bus(WriteTracker::new);
+        jitted.dar[8] = 0x4000;
+        let mut jit = Jit::new();
+        for _ in 0..10 {
+            interpreted.execute(100);
+            jit.execute(&mut jitted, 100);
+        }
+        let jitted: Core<LoggingMem<OpsLogger>> = jitted.map_bus(WriteTracker::into_inner);
+        assert_eq!(interpreted.mem.logger.ops(), jitted.mem.logger.ops());
+    }
+
+    #[test]
+    fn retranslates_code_written_by_the_host() {
+        let mut interpreted = core_running(&FILLING_LOOP);
+        let mut jitted = core_running(&FILLING_LOOP).map_bus(WriteTracker::new);
+        let mut jit = Jit::new();
+        jit.execute(&mut jitted, 1000);
+        interpreted.execute(1000);
+        // ADDQ.B #2, D0
+        interpreted.mem.write_word(SUPERVISOR_PROGRAM, 0x1002, 0x5400);
+        jitted.mem.write_word(SUPERVISOR_PROGRAM, 0x1002, 0x5400);
+        jit.execute(&mut jitted, 1000);
+        interpreted.execute(1000);
+        assert_same_state(&interpreted, &jitted);
+        assert!(jit.invalidated() > 0);
+    }
+
+    #[test]
+    fn runs_self_modifying_code_like_the_interpreter() {
+        let mut interpreted = core_running(&PATCHING_LOOP);
+        let mut jitted = core_running(&PATCHING_LOOP).map_bus(WriteTracker::new);
+        // ADDQ.L #2, D0
+        interpreted.dar[1] = 0x5480;
+        interpreted.dar[8] = 0x1002;
+        jitted.dar[1] = 0x5480;
+        jitted.dar[8] = 0x1002;
+        let mut jit = Jit::new();
+        for _ in 0..10 {
+            assert_eq!(interpreted.execute(100), jit.execute(&mut jitted, 100));
+            assert_same_state(&interpreted, &jitted);
+        }
+    }
+
+    #[test]
+    fn translates_register_instructions_like_the_interpreter() {
+        let body: Vec<u16> = (0..0x10000).map(|opcode| opcode as u16)
+            .filter(|&opcode| match native::decode(opcode) {
+                Some((Op::Branch { .. }, _)) | None => false,
+                Some(_) => true
+            }).collect();
+        let conditions: Vec<u16> = (0..16).map(|condition| 0x6002 | condition << 8)
+            .filter(|&opcode| native::decode(opcode).is_some()).collect();
+        // xorshift, so that failures reproduce
+        let mut seed = 0x2545f491u32;
+        let mut random = move |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize % n
+        };
+        for _ in 0..100 {
+            let mut program = Vec::new();
+            for _ in 0..16 {
+                // a Bcc.S skipping the next instruction now and then
+                if random(4) == 0 {
+                    program.push(conditions[random(conditions.len())]);
+                }
+                program.push(body[random(body.len())]);
+            }
+            let back = -2 * (program.len() as i32 + 1);
+            program.push(0x6000 | (back as u16 & 0xff));
+            let bytes: Vec<u8> = program.iter().flat_map(|word| vec![(word >> 8) as u8, *word as u8]).collect();
+            let logging_core = || {
+                core_on(LoggingMem::new(0xaaaaaaaa, OpsLogger::new()), &bytes)
+            };
+            let mut interpreted = logging_core();
+            for reg in 0..16 {
+                interpreted.dar[reg] = (random(0x10000) << 16 | random(0x10000)) as u32;
+            }
+            interpreted.sr_to_flags(0x2700 | random(32) as u16);
+            let mut jitted = logging_core().map_bus(WriteTracker::new);
+            jitted.dar = interpreted.dar;
+            jitted.sr_to_flags(interpreted.status_register());
+            let mut jit = Jit::new();
+            for _ in 0..10 {
+                // some quanta end in the middle of blocks
+                let quantum = 1 + random(200) as i32;
+                assert_eq!(interpreted.execute(quantum), jit.execute(&mut jitted, quantum));
+                assert_same_state(&interpreted, &jitted);
+                assert_eq!(interpreted.x_flag & 0x100, jitted.x_flag & 0x100);
+                assert_eq!(interpreted.elapsed_cycles, jitted.elapsed_cycles);
+                assert_eq!(interpreted.elapsed_instructions, jitted.elapsed_instructions);
+            }
+            let jitted: Core<LoggingMem<OpsLogger>> = jitted.map_bus(WriteTracker::into_inner);
+            assert_eq!(interpreted.mem.logger.ops(), jitted.mem.logger.ops());
+        }
+    }
+}
@@ -0,0 +1,353 @@
+// Host code for the most common register-only instructions: MOVEQ,
+// MOVE and MOVEA between registers, ADD, SUB, CMP, ADDQ, SUBQ, AND,
+// OR, EOR, TST and CLR on data registers, ADDQ and SUBQ on address
+// registers, and BRA and Bcc with 8-bit displacements.
+//
+// The code sets the same fields to the same values as their handlers
+// would, in Musashi's representation of the flags.
+use cpu::Core;
+use cpu::ops::handlers::HANDLER_NAMES;
+use ram::AddressBus;
+use super::x86_64::{Alu, Cond, Emitter, Reg, RAX, RCX, RSI, R12};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Size {
+    Byte,
+    Word,
+    Long,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Register(usize),
+    Quick(u32),
+}
+
+// Registers are indices into dar, so address registers are 8 and up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    MoveQ { value: u32, dst: usize },
+    Move { size: Size, src: usize, dst: usize },
+    MoveA { size: Size, src: usize, dst: usize },
+    // ADD, SUB, ADDQ and SUBQ, and CMP, which doesn't store the result
+    Arithmetic { sub: bool, size: Size, src: Source, dst: usize, store: bool },
+    AddressQuick { sub: bool, value: u32, dst: usize },
+    Logic { op: Alu, size: Size, src: usize, dst: usize },
+    Test { size: Size, reg: usize },
+    Clear { size: Size, reg: usize },
+    // taken for the combinations of N, Z, V and C (as bits 3 to 0) set
+    // in the mask
+    Branch { taken: u16, offset: i8 },
+}
+
+// The op of an opcode and the cycles it takes (when taken, for
+// branches), or None for those left to the interpreter
+pub fn decode(opcode: u16) -> Option<(Op, i32)> {
+    let name = HANDLER_NAMES[opcode as usize];
+    let (x, y) = ((opcode >> 9 & 7) as usize, (opcode & 7) as usize);
+    let quick = if x == 0 { 8 } else { x as u32 };
+    let mut parts = name.split('_');
+    let mnemonic = parts.next().unwrap_or("");
+    let size = match parts.next() {
+        Some("8") => Size::Byte,
+        Some("16") => Size::Word,
+        Some("32") => Size::Long,
+        _ => return None,
+    };
+    let operands: Vec<&str> = parts.collect();
+    let long = size == Size::Long;
+    Some(match (mnemonic, &operands[..]) {
+        ("moveq", []) => (Op::MoveQ { value: opcode as u8 as i8 as u32, dst: x }, 4),
+        ("move", ["dn", "dn"]) => (Op::Move { size, src: y, dst: x }, 4),
+        ("movea", ["dn"]) => (Op::MoveA { size, src: y, dst: 8 + x }, 4),
+        ("movea", ["an"]) => (Op::MoveA { size, src: 8 + y, dst: 8 + x }, 4),
+        ("add", ["er", "dn"]) | ("sub", ["er", "dn"]) =>
+            (Op::Arithmetic { sub: mnemonic == "sub", size, src: Source::Register(y), dst: x, store: true }, if long { 6 } else { 4 }),
+        ("cmp", ["dn"]) =>
+            (Op::Arithmetic { sub: true, size, src: Source::Register(y), dst: x, store: false }, if long { 6 } else { 4 }),
+        ("addq", ["dn"]) | ("subq", ["dn"]) =>
+            (Op::Arithmetic { sub: mnemonic == "subq", size, src: Source::Quick(quick), dst: y, store: true }, if long { 8 } else { 4 }),
+        ("addq", ["an"]) => (Op::AddressQuick { sub: false, value: quick, dst: 8 + y }, if long { 8 } else { 4 }),
+        ("subq", ["an"]) => (Op::AddressQuick { sub: true, value: quick, dst: 8 + y }, 8),
+        ("and", ["er", "dn"]) => (Op::Logic { op: Alu::And, size, src: y, dst: x }, if long { 6 } else { 4 }),
+        ("or", ["er", "dn"]) => (Op::Logic { op: Alu::Or, size, src: y, dst: x }, if long { 6 } else { 4 }),
+        ("eor", ["dn"]) => (Op::Logic { op: Alu::Xor, size, src: x, dst: y }, if long { 8 } else { 4 }),
+        ("tst", ["dn"]) => (Op::Test { size, reg: y }, 4),
+        ("clr", ["dn"]) => (Op::Clear { size, reg: y }, if long { 6 } else { 4 }),
+        (condition, []) if size == Size::Byte =>
+            (Op::Branch { taken: taken_mask(condition)?, offset: opcode as u8 as i8 }, 10),
+        _ => return None,
+    })
+}
+
+fn taken_mask(mnemonic: &str) -> Option<u16> {
+    let holds: fn(bool, bool, bool, bool) -> bool = match mnemonic {
+        "bra" => |_, _, _, _| true,
+        "bhi" => |_, z, _, c| !c && !z,
+        "bls" => |_, z, _, c| c || z,
+        "bcc" => |_, _, _, c| !c,
+        "bcs" => |_, _, _, c| c,
+        "bne" => |_, z, _, _| !z,
+        "beq" => |_, z, _, _| z,
+        "bvc" => |_, _, v, _| !v,
+        "bvs" => |_, _, v, _| v,
+        "bpl" => |n, _, _, _| !n,
+        "bmi" => |n, _, _, _| n,
+        "bge" => |n, _, v, _| n == v,
+        "blt" => |n, _, v, _| n != v,
+        "bgt" => |n, z, v, _| !z && n == v,
+        "ble" => |n, z, v, _| z || n != v,
+        _ => return None,
+    };
+    Some((0..16).filter(|&i| holds(i & 8 != 0, i & 4 != 0, i & 2 != 0, i & 1 != 0)).fold(0, |mask, i| mask | 1 << i))
+}
+
+// Where the fields of the core are, for translated code to reach them
+// through r12
+#[derive(Clone, Copy, Debug)]
+pub struct Layout {
+    pub pc: i32,
+    pub ir: i32,
+    dar: i32,
+    x_flag: i32,
+    c_flag: i32,
+    v_flag: i32,
+    n_flag: i32,
+    not_z_flag: i32,
+    pub prefetch_addr: i32,
+    pub elapsed_cycles: i32,
+    pub elapsed_instructions: i32,
+}
+
+impl Layout {
+    pub fn of<A: AddressBus>() -> Layout {
+        macro_rules! offset {
+            ($field:ident) => (::std::mem::offset_of!(Core<A>, $field) as i32)
+        }
+        Layout {
+            pc: offset!(pc), ir: offset!(ir), dar: offset!(dar), x_flag: offset!(x_flag), c_flag: offset!(c_flag),
+            v_flag: offset!(v_flag), n_flag: offset!(n_flag), not_z_flag: offset!(not_z_flag),
+            prefetch_addr: offset!(prefetch_addr),
+            elapsed_cycles: offset!(elapsed_cycles), elapsed_instructions: offset!(elapsed_instructions),
+        }
+    }
+    fn reg(&self, index: usize) -> i32 {
+        self.dar + 4 * index as i32
+    }
+}
+
+fn load(e: &mut Emitter, size: Size, dst: Reg, disp: i32) {
+    match size {
+        Size::Byte => e.load8_zx(dst, R12, disp),
+        Size::Word => e.load16_zx(dst, R12, disp),
+        Size::Long => e.load32(dst, R12, disp),
+    }
+}
+
+// stores the low byte or word only, merging it into the register
+fn store(e: &mut Emitter, size: Size, disp: i32, src: Reg) {
+    match size {
+        Size::Byte => e.store8(R12, disp, src),
+        Size::Word => e.store16(R12, disp, src),
+        Size::Long => e.store32(R12, disp, src),
+    }
+}
+
+// as move_flags and the logic ops do, for the result in eax
+fn logic_flags(e: &mut Emitter, l: &Layout, size: Size) {
+    e.store32(R12, l.not_z_flag, RAX);
+    let shift = match size {
+        Size::Byte => 0,
+        Size::Word => 8,
+        Size::Long => 24,
+    };
+    e.mov32(RCX, RAX);
+    if shift > 0 {
+        e.shr32(RCX, shift);
+    }
+    e.store32(R12, l.n_flag, RCX);
+    e.store32_imm(R12, l.v_flag, 0);
+    e.store32_imm(R12, l.c_flag, 0);
+}
+
+// shifts the sign (and carry) of a result to bit 7 (and 8), as the
+// flag fields have them
+fn high_bits(e: &mut Emitter, size: Size, reg: Reg) {
+    match size {
+        Size::Byte => (),
+        Size::Word => e.shr32(reg, 8),
+        Size::Long => e.shr64(reg, 24),
+    }
+}
+
+// Emits everything an op does but branching; the core is in r12
+pub fn emit_op(e: &mut Emitter, l: &Layout, op: Op) {
+    match op {
+        Op::MoveQ { value, dst } => {
+            e.store32_imm(R12, l.reg(dst), value);
+            e.store32_imm(R12, l.n_flag, value >> 24);
+            e.store32_imm(R12, l.not_z_flag, value);
+            e.store32_imm(R12, l.v_flag, 0);
+            e.store32_imm(R12, l.c_flag, 0);
+        }
+        Op::Move { size, src, dst } => {
+            load(e, size, RAX, l.reg(src));
+            store(e, size, l.reg(dst), RAX);
+            logic_flags(e, l, size);
+        }
+        Op::MoveA { size, src, dst } => {
+            if size == Size::Word {
+                e.load16_sx(RAX, R12, l.reg(src));
+            } else {
+                e.load32(RAX, R12, l.reg(src));
+            }
+            e.store32(R12, l.reg(dst), RAX);
+        }
+        Op::Arithmetic { sub, size, src, dst, store: stores } => {
+            load(e, size, RAX, l.reg(dst));
+            match src {
+                Source::Register(src) => load(e, size, RCX, l.reg(src)),
+                Source::Quick(value) => e.mov32_imm(RCX, value),
+            }
+            // the result in esi, unmasked, and for longs in 64 bits
+            let alu = if size == Size::Long { Emitter::alu64 } else { Emitter::alu32 };
+            e.mov32(RSI, RAX);
+            alu(e, if sub { Alu::Sub } else { Alu::Add }, RSI, RCX);
+            if stores {
+                store(e, size, l.reg(dst), RSI);
+            }
+            // V from (src ^ res) & (dst ^ res) when adding, and from
+            // (src ^ dst) & (res ^ dst) when subtracting
+            alu(e, Alu::Xor, RCX, if sub { RAX } else { RSI });
+            alu(e, Alu::Xor, RAX, RSI);
+            alu(e, Alu::And, RAX, RCX);
+            high_bits(e, size, RAX);
+            e.store32(R12, l.v_flag, RAX);
+            e.mov32(RAX, RSI);
+            match size {
+                Size::Byte => e.and32_imm(RAX, 0xff),
+                Size::Word => e.and32_imm(RAX, 0xffff),
+                Size::Long => (),
+            }
+            e.store32(R12, l.not_z_flag, RAX);
+            // N, C and X alike, though CMP leaves X alone
+            high_bits(e, size, RSI);
+            e.store32(R12, l.n_flag, RSI);
+            e.store32(R12, l.c_flag, RSI);
+            if stores {
+                e.store32(R12, l.x_flag, RSI);
+            }
+        }
+        Op::AddressQuick { sub, value, dst } => {
+            if sub {
+                e.sub32_mem_imm(R12, l.reg(dst), value as i32);
+            } else {
+                e.add32_mem_imm(R12, l.reg(dst), value as i32);
+            }
+        }
+        Op::Logic { op, size, src, dst } => {
+            load(e, size, RAX, l.reg(dst));
+            load(e, size, RCX, l.reg(src));
+            e.alu32(op, RAX, RCX);
+            store(e, size, l.reg(dst), RAX);
+            logic_flags(e, l, size);
+        }
+        Op::Test { size, reg } => {
+            load(e, size, RAX, l.reg(reg));
+            logic_flags(e, l, size);
+        }
+        Op::Clear { size, reg } => {
+            match size {
+                Size::Byte => e.store8_imm(R12, l.reg(reg), 0),
+                Size::Word => e.store16_imm(R12, l.reg(reg), 0),
+                Size::Long => e.store32_imm(R12, l.reg(reg), 0),
+            }
+            for &flag in &[l.n_flag, l.v_flag, l.c_flag, l.not_z_flag] {
+                e.store32_imm(R12, flag, 0);
+            }
+        }
+        Op::Branch { .. } => unreachable!("branches are emitted by the block"),
+    }
+}
+
+// Sets the carry if the evaluated flags are in the mask
+pub fn emit_condition(e: &mut Emitter, l: &Layout, taken: u16) {
+    // N from bit 7, V from bit 7 and C from bit 8 of their fields, Z
+    // from not_z_flag being 0
+    e.load32(RAX, R12, l.n_flag);
+    e.shr32(RAX, 4);
+    e.and32_imm(RAX, 8);
+    e.load32(RCX, R12, l.v_flag);
+    e.shr32(RCX, 6);
+    e.and32_imm(RCX, 2);
+    e.alu32(Alu::Or, RAX, RCX);
+    e.load32(RCX, R12, l.c_flag);
+    e.shr32(RCX, 8);
+    e.and32_imm(RCX, 1);
+    e.alu32(Alu::Or, RAX, RCX);
+    e.cmp32_mem_imm(R12, l.not_z_flag, 0);
+    e.set(Cond::Equal, RCX);
+    e.zx8(RCX);
+    e.shl32(RCX, 2);
+    e.alu32(Alu::Or, RAX, RCX);
+    e.mov32_imm(RCX, taken as u32);
+    e.bt32(RCX, RAX);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, emit_condition, Layout, Op, Size, Source};
+    use cpu::Core;
+    use ram::pagedmem::PagedMem;
+    use super::super::x86_64::{Alu, Cond, Emitter, ExecutableBlock, RBX, R12};
+
+    #[test]
+    fn register_instructions_decode_to_ops() {
+        // MOVEQ #-2, D3
+        assert_eq!(Some((Op::MoveQ { value: 0xfffffffe, dst: 3 }, 4)), decode(0x76fe));
+        // ADD.L D1, D0
+        assert_eq!(Some((Op::Arithmetic { sub: false, size: Size::Long, src: Source::Register(1), dst: 0, store: true }, 6)), decode(0xd081));
+        // SUBQ.W #8, D2
+        assert_eq!(Some((Op::Arithmetic { sub: true, size: Size::Word, src: Source::Quick(8), dst: 2, store: true }, 4)), decode(0x5142));
+        // EOR.B D1, D4
+        assert_eq!(Some((Op::Logic { op: Alu::Xor, size: Size::Byte, src: 1, dst: 4 }, 4)), decode(0xb304));
+        // MOVEA.W A1, A2
+        assert_eq!(Some((Op::MoveA { size: Size::Word, src: 9, dst: 10 }, 4)), decode(0x3449));
+        // NOP, ADD.B (A1), D2, BSR.S and BRA.W are left to the interpreter
+        for &opcode in &[0x4e71, 0xd411, 0x6102, 0x6000] {
+            assert_eq!(None, decode(opcode));
+        }
+    }
+
+    #[test]
+    fn branches_are_taken_for_the_flags_the_interpreter_takes_them_for() {
+        let mut core = Core::new_with_bus(0x1000, PagedMem::new(0));
+        let layout = Layout::of::<PagedMem>();
+        for condition in 2..16 {
+            let opcode = 0x6000 | condition << 8 | 0x10;
+            let taken = match decode(opcode) {
+                Some((Op::Branch { taken, offset: 0x10 }, 10)) => taken,
+                other => panic!("{:04x} decoded to {:?}", opcode, other),
+            };
+            // D0 = whether the translated condition holds
+            let mut e = Emitter::new();
+            let not_taken = e.label();
+            e.load64(R12, RBX, 0);
+            e.store32_imm(R12, layout.reg(0), 0);
+            emit_condition(&mut e, &layout, taken);
+            e.jump_if(Cond::AboveOrEqual, not_taken);
+            e.store32_imm(R12, layout.reg(0), 1);
+            e.bind(not_taken);
+            let block = ExecutableBlock::new(&e.finish());
+            for flags in 0..32 {
+                core.sr_to_flags(0x2700 | flags);
+                let expected = [core.cond_hi(), core.cond_ls(), core.cond_cc(), core.cond_cs(), core.cond_ne(), core.cond_eq(),
+                    core.cond_vc(), core.cond_vs(), core.cond_pl(), core.cond_mi(), core.cond_ge(), core.cond_lt(),
+                    core.cond_gt(), core.cond_le()][condition as usize - 2];
+                let mut pointer = &mut core as *mut Core<PagedMem>;
+                unsafe { block.run(&mut pointer as *mut *mut Core<PagedMem> as *mut u8) };
+                assert_eq!(expected, core.dar[0] == 1, "{:04x} with flags {:05b}", opcode, flags);
+            }
+        }
+    }
+}
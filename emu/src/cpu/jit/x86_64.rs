@@ -0,0 +1,365 @@
+use libc;
+use std::mem;
+use std::ptr;
+
+// Executable copy of emitted machine code, mapped in its own pages
+pub struct ExecutableBlock {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl ExecutableBlock {
+    pub fn new(code: &[u8]) -> ExecutableBlock {
+        let len = code.len();
+        unsafe {
+            let ptr = libc::mmap(ptr::null_mut(), len, libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON, -1, 0);
+            assert!(ptr != libc::MAP_FAILED, "could not map memory for translated code");
+            ptr::copy_nonoverlapping(code.as_ptr(), ptr as *mut u8, len);
+            let result = libc::mprotect(ptr, len, libc::PROT_READ | libc::PROT_EXEC);
+            assert_eq!(0, result, "could not make translated code executable");
+            ExecutableBlock { ptr: ptr as *mut u8, len }
+        }
+    }
+    // the block gets the context pointer in rbx
+    pub unsafe fn run(&self, context: *mut u8) {
+        let entry: extern "C" fn(*mut u8) = mem::transmute(self.ptr);
+        entry(context)
+    }
+}
+
+impl Drop for ExecutableBlock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+// registers, by their encoding
+pub type Reg = u8;
+pub const RAX: Reg = 0;
+pub const RCX: Reg = 1;
+pub const RBX: Reg = 3;
+pub const RSI: Reg = 6;
+pub const R12: Reg = 12;
+
+// condition codes of jcc and setcc
+#[derive(Clone, Copy)]
+pub enum Cond {
+    AboveOrEqual = 0x3,
+    Equal = 0x4,
+    NotEqual = 0x5,
+    LessOrEqual = 0xe,
+}
+
+// two-operand ALU instructions, by their r/m, reg opcode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alu {
+    Add = 0x01,
+    Or = 0x09,
+    And = 0x21,
+    Sub = 0x29,
+    Xor = 0x31,
+}
+
+#[derive(Clone, Copy)]
+pub struct Label(usize);
+
+// Just enough of an x86-64 assembler for translated blocks. Memory
+// operands are all [base + disp32]. Blocks are called with a context
+// pointer, kept in rbx; r12 is free for the block to keep a pointer in,
+// as both are callee-saved, and rsp is aligned for calls.
+pub struct Emitter {
+    code: Vec<u8>,
+    labels: Vec<Option<usize>>,
+    fixups: Vec<(usize, Label)>,
+    exit: Label,
+}
+
+impl Emitter {
+    pub fn new() -> Emitter {
+        let mut emitter = Emitter { code: Vec::new(), labels: Vec::new(), fixups: Vec::new(), exit: Label(0) };
+        emitter.exit = emitter.label();
+        emitter.emit(&[0x53]);             // push rbx
+        emitter.emit(&[0x41, 0x54]);       // push r12
+        emitter.emit(&[0x41, 0x55]);       // push r13, to align rsp
+        emitter.emit(&[0x48, 0x89, 0xfb]); // mov rbx, rdi
+        emitter
+    }
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+    fn emit_u32(&mut self, value: u32) {
+        self.emit(&value.to_le_bytes());
+    }
+    fn rex(&mut self, wide: bool, reg: Reg, rm: Reg) {
+        let rex = 0x40 | if wide { 8 } else { 0 } | (reg >> 3) << 2 | rm >> 3;
+        if rex != 0x40 {
+            self.emit(&[rex]);
+        }
+    }
+    // opcode with a [base + disp32] operand
+    fn op_mem(&mut self, prefix: Option<u8>, wide: bool, opcode: &[u8], reg: Reg, base: Reg, disp: i32) {
+        if let Some(prefix) = prefix {
+            self.emit(&[prefix]);
+        }
+        self.rex(wide, reg, base);
+        self.emit(opcode);
+        self.emit(&[0x80 | (reg & 7) << 3 | base & 7]);
+        if base & 7 == 4 {
+            self.emit(&[0x24]);            // SIB, for rsp and r12
+        }
+        self.emit_u32(disp as u32);
+    }
+    // opcode with a register operand
+    fn op_reg(&mut self, wide: bool, opcode: &[u8], reg: Reg, rm: Reg) {
+        self.rex(wide, reg, rm);
+        self.emit(opcode);
+        self.emit(&[0xc0 | (reg & 7) << 3 | rm & 7]);
+    }
+
+    pub fn load64(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_mem(None, true, &[0x8b], dst, base, disp);
+    }
+    pub fn load32(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_mem(None, false, &[0x8b], dst, base, disp);
+    }
+    // zero or sign extended to 32 bits
+    pub fn load8_zx(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_mem(None, false, &[0x0f, 0xb6], dst, base, disp);
+    }
+    pub fn load16_zx(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_mem(None, false, &[0x0f, 0xb7], dst, base, disp);
+    }
+    pub fn load16_sx(&mut self, dst: Reg, base: Reg, disp: i32) {
+        self.op_mem(None, false, &[0x0f, 0xbf], dst, base, disp);
+    }
+    // byte stores only from al, cl and dl
+    pub fn store8(&mut self, base: Reg, disp: i32, src: Reg) {
+        self.op_mem(None, false, &[0x88], src, base, disp);
+    }
+    pub fn store16(&mut self, base: Reg, disp: i32, src: Reg) {
+        self.op_mem(Some(0x66), false, &[0x89], src, base, disp);
+    }
+    pub fn store32(&mut self, base: Reg, disp: i32, src: Reg) {
+        self.op_mem(None, false, &[0x89], src, base, disp);
+    }
+    pub fn store8_imm(&mut self, base: Reg, disp: i32, value: u8) {
+        self.op_mem(None, false, &[0xc6], 0, base, disp);
+        self.emit(&[value]);
+    }
+    pub fn store16_imm(&mut self, base: Reg, disp: i32, value: u16) {
+        self.op_mem(Some(0x66), false, &[0xc7], 0, base, disp);
+        self.emit(&value.to_le_bytes());
+    }
+    pub fn store32_imm(&mut self, base: Reg, disp: i32, value: u32) {
+        self.op_mem(None, false, &[0xc7], 0, base, disp);
+        self.emit_u32(value);
+    }
+    // add, sub and cmp of a sign extended immediate to memory
+    pub fn add32_mem_imm(&mut self, base: Reg, disp: i32, value: i32) {
+        self.op_mem(None, false, &[0x81], 0, base, disp);
+        self.emit_u32(value as u32);
+    }
+    pub fn add64_mem_imm(&mut self, base: Reg, disp: i32, value: i32) {
+        self.op_mem(None, true, &[0x81], 0, base, disp);
+        self.emit_u32(value as u32);
+    }
+    pub fn sub32_mem_imm(&mut self, base: Reg, disp: i32, value: i32) {
+        self.op_mem(None, false, &[0x81], 5, base, disp);
+        self.emit_u32(value as u32);
+    }
+    pub fn cmp32_mem_imm(&mut self, base: Reg, disp: i32, value: i32) {
+        self.op_mem(None, false, &[0x81], 7, base, disp);
+        self.emit_u32(value as u32);
+    }
+    pub fn mov32_imm(&mut self, dst: Reg, value: u32) {
+        self.rex(false, 0, dst);
+        self.emit(&[0xb8 | dst & 7]);
+        self.emit_u32(value);
+    }
+    pub fn mov32(&mut self, dst: Reg, src: Reg) {
+        self.op_reg(false, &[0x89], src, dst);
+    }
+    pub fn alu32(&mut self, op: Alu, dst: Reg, src: Reg) {
+        self.op_reg(false, &[op as u8], src, dst);
+    }
+    pub fn alu64(&mut self, op: Alu, dst: Reg, src: Reg) {
+        self.op_reg(true, &[op as u8], src, dst);
+    }
+    pub fn and32_imm(&mut self, dst: Reg, value: u32) {
+        self.op_reg(false, &[0x81], 4, dst);
+        self.emit_u32(value);
+    }
+    pub fn shl32(&mut self, dst: Reg, count: u8) {
+        self.op_reg(false, &[0xc1], 4, dst);
+        self.emit(&[count]);
+    }
+    pub fn shr32(&mut self, dst: Reg, count: u8) {
+        self.op_reg(false, &[0xc1], 5, dst);
+        self.emit(&[count]);
+    }
+    pub fn shr64(&mut self, dst: Reg, count: u8) {
+        self.op_reg(true, &[0xc1], 5, dst);
+        self.emit(&[count]);
+    }
+    // zero extends the low byte of a register
+    pub fn zx8(&mut self, reg: Reg) {
+        self.op_reg(false, &[0x0f, 0xb6], reg, reg);
+    }
+    pub fn set(&mut self, cond: Cond, dst: Reg) {
+        self.op_reg(false, &[0x0f, 0x90 | cond as u8], 0, dst);
+    }
+    // carry = bit of bits numbered by index
+    pub fn bt32(&mut self, bits: Reg, index: Reg) {
+        self.op_reg(false, &[0x0f, 0xa3], index, bits);
+    }
+    pub fn test32(&mut self, a: Reg, b: Reg) {
+        self.op_reg(false, &[0x85], b, a);
+    }
+
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+    pub fn bind(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.code.len());
+    }
+    // leaves the block
+    pub fn exit(&self) -> Label {
+        self.exit
+    }
+    pub fn jump_if(&mut self, cond: Cond, target: Label) {
+        self.emit(&[0x0f, 0x80 | cond as u8]);
+        self.fixups.push((self.code.len(), target));
+        self.emit_u32(0);
+    }
+    pub fn jump(&mut self, target: Label) {
+        self.emit(&[0xe9]);
+        self.fixups.push((self.code.len(), target));
+        self.emit_u32(0);
+    }
+    // calls function(context), or function(context, arg), result in eax
+    pub fn call(&mut self, function: usize, arg: Option<u32>) {
+        self.emit(&[0x48, 0x89, 0xdf]);    // mov rdi, rbx
+        if let Some(arg) = arg {
+            self.mov32_imm(RSI, arg);
+        }
+        self.emit(&[0x48, 0xb8]);          // mov rax, imm64
+        self.emit(&(function as u64).to_le_bytes());
+        self.emit(&[0xff, 0xd0]);          // call rax
+    }
+    pub fn finish(mut self) -> Vec<u8> {
+        let exit = self.exit;
+        self.bind(exit);
+        self.emit(&[0x41, 0x5d]);          // pop r13
+        self.emit(&[0x41, 0x5c]);          // pop r12
+        self.emit(&[0x5b]);                // pop rbx
+        self.emit(&[0xc3]);                // ret
+        for &(at, label) in &self.fixups {
+            let target = self.labels[label.0].expect("jump to a label never bound");
+            let rel = target.wrapping_sub(at + 4) as u32;
+            self.code[at..at + 4].copy_from_slice(&rel.to_le_bytes());
+        }
+        self.code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Alu, Cond, Emitter, ExecutableBlock, RAX, RBX, RCX, RSI, R12};
+
+    struct Counter {
+        sum: u32,
+        limit: u32,
+    }
+
+    extern "C" fn count(counter: *mut Counter, pc: u32) -> u32 {
+        let counter = unsafe { &mut *counter };
+        counter.sum += pc;
+        if counter.sum >= counter.limit { 1 } else { 0 }
+    }
+
+    #[test]
+    fn calls_functions_until_one_asks_to_exit() {
+        let mut emitter = Emitter::new();
+        for pc in 1..5 {
+            emitter.call(count as *const () as usize, Some(pc));
+            emitter.test32(RAX, RAX);
+            let exit = emitter.exit();
+            emitter.jump_if(Cond::NotEqual, exit);
+        }
+        let block = ExecutableBlock::new(&emitter.finish());
+
+        let mut counter = Counter { sum: 0, limit: 6 };
+        unsafe { block.run(&mut counter as *mut Counter as *mut u8) };
+        assert_eq!(6, counter.sum);
+
+        let mut counter = Counter { sum: 0, limit: 100 };
+        unsafe { block.run(&mut counter as *mut Counter as *mut u8) };
+        assert_eq!(10, counter.sum);
+    }
+
+    #[repr(C)]
+    struct Registers {
+        pointer: *mut Registers,
+        regs: [u32; 8],
+    }
+
+    fn run(emit: &dyn Fn(&mut Emitter), regs: [u32; 8]) -> [u32; 8] {
+        let mut emitter = Emitter::new();
+        emitter.load64(R12, RBX, 0);
+        emit(&mut emitter);
+        let block = ExecutableBlock::new(&emitter.finish());
+        let mut registers = Registers { pointer: std::ptr::null_mut(), regs };
+        registers.pointer = &mut registers;
+        unsafe { block.run(&mut registers as *mut Registers as *mut u8) };
+        registers.regs
+    }
+
+    #[test]
+    fn loads_operates_and_stores_through_r12() {
+        let regs = run(&|e: &mut Emitter| {
+            // regs[2] = regs[0] + regs[1], and the byte, word and sign
+            // extended word of regs[3] into regs[4..7]
+            e.load32(RAX, R12, 8);
+            e.load32(RCX, R12, 12);
+            e.alu32(Alu::Add, RAX, RCX);
+            e.store32(R12, 16, RAX);
+            e.load8_zx(RSI, R12, 20);
+            e.store32(R12, 24, RSI);
+            e.load16_zx(RSI, R12, 20);
+            e.store32(R12, 28, RSI);
+            e.load16_sx(RSI, R12, 20);
+            e.store32(R12, 32, RSI);
+            // a word store leaves the upper half alone
+            e.store16_imm(R12, 36, 0xbeef);
+        }, [40, 2, 0, 0x1234_8765, 0, 0, 0, 0xdead_0000]);
+        assert_eq!([40, 2, 42, 0x1234_8765, 0x65, 0x8765, 0xffff_8765, 0xdead_beef], regs);
+    }
+
+    #[test]
+    fn branches_on_conditions_and_counts_loops() {
+        let regs = run(&|e: &mut Emitter| {
+            // count regs[0] down to zero, adding 3 to regs[1] each time
+            let top = e.label();
+            let done = e.label();
+            e.bind(top);
+            e.cmp32_mem_imm(R12, 8, 0);
+            e.jump_if(Cond::Equal, done);
+            e.sub32_mem_imm(R12, 8, 1);
+            e.add32_mem_imm(R12, 12, 3);
+            e.jump(top);
+            e.bind(done);
+            // regs[2] = whether bit 5 of regs[3] is clear, by bt
+            e.load32(RCX, R12, 20);
+            e.mov32_imm(RAX, 5);
+            e.bt32(RCX, RAX);
+            e.set(Cond::AboveOrEqual, RSI);
+            e.zx8(RSI);
+            e.store32(R12, 16, RSI);
+        }, [7, 0, 9, 0x20, 0, 0, 0, 0]);
+        assert_eq!([0, 21, 0, 0x20], regs[..4]);
+    }
+}
@@ -1,16 +1,32 @@
 // type alias for exception handling
 use std::result;
+use std::sync::Arc;
 pub type Result<T> = result::Result<T, Exception>;
 mod interrupts;
 use self::interrupts::{InterruptController, AutoInterruptController, SPURIOUS_INTERRUPT};
 use ram::loggingmem::{LoggingMem, OpsLogger};
-pub type Core = ConfiguredCore<AutoInterruptController, LoggingMem<OpsLogger>>;
-pub type Handler = fn(&mut Core) -> Result<Cycles>;
-pub type InstructionSet = Vec<Handler>;
-use ram::{AddressBus, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA};
+pub type Core<A = LoggingMem<OpsLogger>> = ConfiguredCore<AutoInterruptController, A>;
+pub type Handler<A = LoggingMem<OpsLogger>> = fn(&mut Core<A>) -> Result<Cycles>;
+pub type InstructionSet<A = LoggingMem<OpsLogger>> = Vec<Handler<A>>;
+use ram::{AddressBus, Fork, SUPERVISOR_PROGRAM, SUPERVISOR_DATA, USER_PROGRAM, USER_DATA};
+use ram::watching::WatchHit;
 pub mod ops;
 mod effective_address;
 mod operator;
+mod rununtil;
+pub use self::rununtil::{StopConditions, StopReason};
+mod breakpoints;
+pub use self::breakpoints::{Breakpoint, Breakpoints};
+mod callstack;
+pub use self::callstack::{CallStack, Frame, FrameKind, FrameLink, Symbols};
+pub mod blockcache;
+pub mod tracing;
+pub mod vcd;
+pub mod profiler;
+pub mod coverage;
+pub mod opstats;
+#[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+pub mod jit;
 
 pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     pub pc: u32,
@@ -18,7 +34,8 @@ pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     pub inactive_usp: u32, // when in supervisor mode
     pub ir: u16,
     pub dar: [u32; 16],
-    pub ophandlers: InstructionSet,
+    // shared by forks
+    pub ophandlers: Arc<InstructionSet<A>>,
     pub s_flag: u32,
     pub irq_level: u8,
     pub int_mask: u32,
@@ -31,6 +48,13 @@ pub struct ConfiguredCore<T: InterruptController, A: AddressBus> {
     pub prefetch_data: u32,
     pub not_z_flag: u32,
     pub processing_state: ProcessingState,
+    // running totals, across all ways of executing
+    pub elapsed_cycles: u64,
+    pub elapsed_instructions: u64,
+    pub breakpoints: Breakpoints,
+    // the watchpoint execution last stopped at, until execution resumes
+    pub watch_hit: Option<WatchHit>,
+    pub call_stack: CallStack,
     pub mem: A,
 }
 pub const STACK_POINTER_REG: usize = 15;
@@ -60,13 +84,13 @@ impl Cycles {
     }
 }
 
-pub trait Callbacks {
-    fn exception_callback(&mut self, core: &mut Core, ex: Exception) -> Result<Cycles>;
+pub trait Callbacks<A: AddressBus = LoggingMem<OpsLogger>> {
+    fn exception_callback(&mut self, core: &mut Core<A>, ex: Exception) -> Result<Cycles>;
 }
 
 struct EmulateAllExceptions;
-impl Callbacks for EmulateAllExceptions {
-    fn exception_callback(&mut self, _: &mut Core, ex: Exception) -> Result<Cycles> {
+impl<A: AddressBus> Callbacks<A> for EmulateAllExceptions {
+    fn exception_callback(&mut self, _: &mut Core<A>, ex: Exception) -> Result<Cycles> {
         Err(ex)
     }
 }
@@ -109,6 +133,7 @@ use ram::AddressSpace;
 #[derive(Debug)]
 pub enum Exception {
     AddressError { address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace},
+    BusError { address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace},
     IllegalInstruction(u16, u32), // ir, pc
     Trap(u8, i32),                // trap no, exception cycles
     PrivilegeViolation(u16, u32), // ir, pc
@@ -122,6 +147,9 @@ impl fmt::Display for Exception {
             Exception::AddressError {
                 address, access_type, processing_state, address_space
                 } => write!(f, "Address Error: {:?} {:?} at {:08x} during {:?} processing", access_type, address_space, address, processing_state),
+            Exception::BusError {
+                address, access_type, processing_state, address_space
+                } => write!(f, "Bus Error: {:?} {:?} at {:08x} during {:?} processing", access_type, address_space, address, processing_state),
             Exception::IllegalInstruction(ir, pc) => write!(f, "Illegal Instruction {:04x} at {:08x}", ir, pc),
             Exception::Trap(num, ea_cyc) => write!(f, "Trap: {:02x} (ea cyc {})", num, ea_cyc),
             Exception::PrivilegeViolation(ir, pc) => write!(f, "Privilege Violation {:04x} at {:08x}", ir, pc),
@@ -135,6 +163,7 @@ impl error::Error for Exception {
     fn description(&self) -> &str {
          match *self {
             Exception::AddressError{..} => "Address Error",
+            Exception::BusError{..} => "Bus Error",
             Exception::IllegalInstruction(_, _) => "Illegal Instruction",
             Exception::Trap(_, _) => "Trap",
             Exception::PrivilegeViolation(_, _) => "PrivilegeViolation",
@@ -167,7 +196,7 @@ const SFLAG_CLEAR: u32 =  0x00;
 const ZFLAG_CLEAR: u32 =  0xffffffff; // used as "non-z-flag"
 
 // Exception Vectors
-//pub const EXCEPTION_BUS_ERROR: u8               =  2;
+pub const EXCEPTION_BUS_ERROR: u8               =  2;
 pub const EXCEPTION_ADDRESS_ERROR: u8           =  3;
 pub const EXCEPTION_ILLEGAL_INSTRUCTION: u8     =  4;
 pub const EXCEPTION_ZERO_DIVIDE: u8             =  5;
@@ -189,7 +218,8 @@ impl Core {
             pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Group0Exception,
             dar: [0u32; 16], mem: LoggingMem::new(0xaaaaaaaa, OpsLogger::new()), ophandlers: ops::fake::instruction_set(),
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffffffff
+            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffffffff,
+            elapsed_cycles: 0, elapsed_instructions: 0, breakpoints: Breakpoints::new(), watch_hit: None, call_stack: CallStack::new()
         }
     }
     pub fn new_mem(base: u32, contents: &[u8]) -> Core {
@@ -197,27 +227,65 @@ impl Core {
     }
     pub fn new_mem_init(base: u32, contents: &[u8], initializer: u32) -> Core {
         let mut lm = LoggingMem::new(initializer, OpsLogger::new());
-        for (offset, byte) in contents.iter().enumerate() {
-            lm.write_u8(base + offset as u32, *byte as u32);
-        }
+        lm.load(base, contents);
         Core {
             pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
             dar: [0u32; 16], mem: lm, ophandlers: ops::fake::instruction_set(),
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffffffff
+            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffffffff,
+            elapsed_cycles: 0, elapsed_instructions: 0, breakpoints: Breakpoints::new(), watch_hit: None, call_stack: CallStack::new()
+        }
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    // Creates a core running the full instruction set on the given bus,
+    // for hosts that don't want every access logged
+    pub fn new_with_bus(base: u32, mem: A) -> Core<A> {
+        Core {
+            pc: base, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: 0, inactive_usp: 0, ir: 0, processing_state: ProcessingState::Normal,
+            dar: [0u32; 16], mem: mem, ophandlers: ops::instruction_set(),
+            irq_level: 0, int_ctrl: AutoInterruptController::new(),
+            s_flag: SFLAG_SET, int_mask: CPU_SR_INT_MASK, x_flag: 0, v_flag: 0, c_flag: 0, n_flag: 0, not_z_flag: 0xffffffff,
+            elapsed_cycles: 0, elapsed_instructions: 0, breakpoints: Breakpoints::new(), watch_hit: None, call_stack: CallStack::new()
+        }
+    }
+    // Creates a core on the given bus and resets it, fetching the SSP
+    // and PC from the reset vectors, as a machine powering on does
+    pub fn power_on(mem: A) -> Core<A> {
+        let mut core = Core::new_with_bus(0, mem);
+        core.reset();
+        core
+    }
+    // Moves the core onto another bus, keeping all register and
+    // processing state. The full instruction set is installed.
+    pub fn map_bus<B: AddressBus, F: FnOnce(A) -> B>(self, f: F) -> Core<B> {
+        Core {
+            pc: self.pc, prefetch_addr: self.prefetch_addr, prefetch_data: self.prefetch_data, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
+            dar: self.dar, mem: f(self.mem), ophandlers: ops::instruction_set(),
+            irq_level: self.irq_level, int_ctrl: self.int_ctrl,
+            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag,
+            elapsed_cycles: self.elapsed_cycles, elapsed_instructions: self.elapsed_instructions, breakpoints: self.breakpoints, watch_hit: self.watch_hit, call_stack: self.call_stack
         }
     }
     pub fn reset(&mut self) {
+        // the bus is reset first, to map boot ROM at 0 where boards do
+        self.mem.reset();
         self.processing_state = ProcessingState::Group0Exception;
         self.s_flag = SFLAG_SET;
         self.int_mask = CPU_SR_INT_MASK;
         self.prefetch_addr = 1; // non-zero, or the prefetch won't kick in
         self.jump(0);
-        // these reads cannot possibly cause AddressError, as we forced PC to 0
-        sp!(self) = self.read_imm_u32().unwrap();
-        let new_pc = self.read_imm_u32().unwrap();
-        self.jump(new_pc);
-        self.processing_state = ProcessingState::Normal;
+        // these reads cannot possibly cause AddressError, as we forced PC
+        // to 0, but a bus error halts the processor, as a double fault
+        match self.read_imm_u32().and_then(|ssp| self.read_imm_u32().map(|pc| (ssp, pc))) {
+            Ok((ssp, new_pc)) => {
+                sp!(self) = ssp;
+                self.jump(new_pc);
+                self.processing_state = ProcessingState::Normal;
+            }
+            Err(_) => self.processing_state = ProcessingState::Halted,
+        }
     }
     pub fn x_flag_as_1(&self) -> u32 {
         (self.x_flag>>8)&1
@@ -294,27 +362,32 @@ impl Core {
         if 0 < (sr >> 1) & 1 {'V'} else {'-'},
         if 0 < (sr     ) & 1 {'C'} else {'-'})
     }
-    fn prefetch_if_needed(&mut self) -> bool {
+    fn prefetch_if_needed(&mut self) -> Result<bool> {
         // does current PC overlap with fetched data
         let fetched = if self.pc & !3 != self.prefetch_addr {
             self.prefetch_addr = self.pc & !3;
             let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
             self.prefetch_data = self.mem.read_long(address_space, self.prefetch_addr);
+            if let Err(fault) = self.bus_fault(AccessType::Read) {
+                // nothing was fetched, so fetch again next time
+                self.prefetch_addr = 1;
+                return Err(fault);
+            }
             true
         } else {
             false
         };
         self.pc = self.pc.wrapping_add(2);
-        fetched
+        Ok(fetched)
     }
     pub fn read_imm_u32(&mut self) -> Result<u32> {
         if self.pc & 1 > 0 {
             let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
             return Err(Exception::AddressError{address: self.pc, access_type: AccessType::Read, address_space: address_space, processing_state: self.processing_state})
         }
-        self.prefetch_if_needed();
+        self.prefetch_if_needed()?;
         let prev_prefetch_data = self.prefetch_data;
-        Ok(if self.prefetch_if_needed() {
+        Ok(if self.prefetch_if_needed()? {
             ((prev_prefetch_data << 16) | (self.prefetch_data >> 16)) & 0xffffffff
         } else {
             prev_prefetch_data
@@ -329,61 +402,75 @@ impl Core {
             let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
             return Err(Exception::AddressError{address: self.pc, access_type: AccessType::Read, address_space: address_space, processing_state: self.processing_state})
         }
-        self.prefetch_if_needed();
+        self.prefetch_if_needed()?;
         Ok(((self.prefetch_data >> ((2 - ((self.pc.wrapping_sub(2)) & 2))<<3)) & 0xffff) as u16)
     }
-    pub fn push_sp(&mut self) -> u32 {
+    pub fn push_sp(&mut self) -> Result<u32> {
          let new_sp = (Wrapping(sp!(self)) - Wrapping(4)).0;
          sp!(self) = new_sp;
-         self.write_data_long(new_sp, new_sp).unwrap();
-         new_sp
+         self.write_data_long(new_sp, new_sp)?;
+         Ok(new_sp)
     }
-    pub fn push_32(&mut self, value: u32) -> u32 {
+    pub fn push_32(&mut self, value: u32) -> Result<u32> {
          let new_sp = (Wrapping(sp!(self)) - Wrapping(4)).0;
          sp!(self) = new_sp;
-         self.write_data_long(new_sp, value).unwrap();
-         new_sp
+         self.write_data_long(new_sp, value)?;
+         Ok(new_sp)
     }
-    pub fn pop_32(&mut self) -> u32 {
+    pub fn pop_32(&mut self) -> Result<u32> {
         let sp = sp!(self);
-        let data = self.read_data_long(sp).unwrap();
+        let data = self.read_data_long(sp)?;
         sp!(self) = sp.wrapping_add(4);
-        data
+        Ok(data)
     }
-    pub fn push_16(&mut self, value: u16) -> u32 {
+    pub fn push_16(&mut self, value: u16) -> Result<u32> {
          let new_sp = (Wrapping(sp!(self)) - Wrapping(2)).0;
          sp!(self) = new_sp;
-         self.write_data_word(new_sp, value as u32).unwrap();
-         new_sp
+         self.write_data_word(new_sp, value as u32)?;
+         Ok(new_sp)
     }
-    pub fn pop_16(&mut self) -> u16 {
+    pub fn pop_16(&mut self) -> Result<u16> {
         let sp = sp!(self);
-        let data = self.read_data_word(sp).unwrap() as u16;
+        let data = self.read_data_word(sp)? as u16;
         sp!(self) = sp.wrapping_add(2);
-        data
+        Ok(data)
+    }
+    // The bus error raised by the access just made, if any, so that
+    // instructions stop at the faulting access, as on the 68000
+    fn bus_fault(&mut self, access_type: AccessType) -> Result<()> {
+        match self.mem.take_bus_error() {
+            Some(fault) => Err(Exception::BusError { address: fault.address, access_type,
+                processing_state: self.processing_state, address_space: fault.address_space }),
+            None => Ok(()),
+        }
     }
     pub fn read_data_byte(&mut self, address: u32) -> Result<u32> {
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(self.mem.read_byte(address_space, address))
+        let value = self.mem.read_byte(address_space, address);
+        self.bus_fault(AccessType::Read).map(|_| value)
     }
     pub fn read_program_byte(&mut self, address: u32) -> Result<u32> {
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        Ok(self.mem.read_byte(address_space, address))
+        let value = self.mem.read_byte(address_space, address);
+        self.bus_fault(AccessType::Read).map(|_| value)
     }
     pub fn write_data_byte(&mut self, address: u32, value: u32) -> Result<()> {
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
-        Ok(self.mem.write_byte(address_space, address, value))
+        self.mem.write_byte(address_space, address, value);
+        self.bus_fault(AccessType::Write)
     }
     pub fn write_program_byte(&mut self, address: u32, value: u32) -> Result<()> {
         let address_space = if self.s_flag != 0 {SUPERVISOR_PROGRAM} else {USER_PROGRAM};
-        Ok(self.mem.write_byte(address_space, address, value))
+        self.mem.write_byte(address_space, address, value);
+        self.bus_fault(AccessType::Write)
     }
     pub fn read_data_word(&mut self, address: u32) -> Result<u32> {
         let address_space = if self.s_flag != 0 {SUPERVISOR_DATA} else {USER_DATA};
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Read, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_word(address_space, address))
+            let value = self.mem.read_word(address_space, address);
+            self.bus_fault(AccessType::Read).map(|_| value)
         }
     }
     pub fn read_program_word(&mut self, address: u32) -> Result<u32> {
@@ -391,7 +478,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError {address: address, access_type: AccessType::Read, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_word(address_space, address))
+            let value = self.mem.read_word(address_space, address);
+            self.bus_fault(AccessType::Read).map(|_| value)
         }
     }
     pub fn write_data_word(&mut self, address: u32, value: u32) -> Result<()> {
@@ -399,7 +487,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Write, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.write_word(address_space, address, value))
+            self.mem.write_word(address_space, address, value);
+            self.bus_fault(AccessType::Write)
         }
     }
     pub fn write_program_word(&mut self, address: u32, value: u32) -> Result<()> {
@@ -407,7 +496,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Write, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.write_word(address_space, address, value))
+            self.mem.write_word(address_space, address, value);
+            self.bus_fault(AccessType::Write)
         }
     }
     pub fn read_data_long(&mut self, address: u32) -> Result<u32> {
@@ -415,7 +505,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Read, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_long(address_space, address))
+            let value = self.mem.read_long(address_space, address);
+            self.bus_fault(AccessType::Read).map(|_| value)
         }
     }
     pub fn read_program_long(&mut self, address: u32) -> Result<u32> {
@@ -423,7 +514,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Read, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.read_long(address_space, address))
+            let value = self.mem.read_long(address_space, address);
+            self.bus_fault(AccessType::Read).map(|_| value)
         }
     }
     pub fn write_data_long(&mut self, address: u32, value: u32) -> Result<()> {
@@ -431,7 +523,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Write, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.write_long(address_space, address, value))
+            self.mem.write_long(address_space, address, value);
+            self.bus_fault(AccessType::Write)
         }
     }
     pub fn write_program_long(&mut self, address: u32, value: u32) -> Result<()> {
@@ -439,7 +532,8 @@ impl Core {
         if address & 1 > 0 {
             Err(Exception::AddressError{address: address, access_type: AccessType::Write, address_space: address_space, processing_state: self.processing_state})
         } else {
-            Ok(self.mem.write_long(address_space, address, value))
+            self.mem.write_long(address_space, address, value);
+            self.bus_fault(AccessType::Write)
         }
     }
     pub fn jump(&mut self, pc: u32) {
@@ -514,9 +608,10 @@ impl Core {
         (self.not_z_flag == ZFLAG_SET) || (self.n_flag & NFLAG_SET!=0) && (self.v_flag & VFLAG_SET==0) || (self.n_flag & NFLAG_SET==0) && (self.v_flag & VFLAG_SET!=0)
     }
 
-    pub fn jump_vector(&mut self, vector: u8) {
+    pub fn jump_vector(&mut self, vector: u8) -> Result<()> {
         let vector_address = (vector as u32) << 2;
-        self.pc = self.read_data_long(vector_address).unwrap();
+        self.pc = self.read_data_long(vector_address)?;
+        Ok(())
     }
     pub fn ensure_supervisor_mode(&mut self) -> u16 {
         let backup_sr = self.status_register();
@@ -530,6 +625,14 @@ impl Core {
         backup_sr
     }
     pub fn handle_address_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        self.handle_group0_exception(EXCEPTION_ADDRESS_ERROR, bad_address, access_type, processing_state, address_space)
+    }
+    pub fn handle_bus_error(&mut self, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
+    {
+        self.handle_group0_exception(EXCEPTION_BUS_ERROR, bad_address, access_type, processing_state, address_space)
+    }
+    fn handle_group0_exception(&mut self, vector: u8, bad_address: u32, access_type: AccessType, processing_state: ProcessingState, address_space: AddressSpace) -> Cycles
     {
         if processing_state == ProcessingState::Group0Exception {
             self.processing_state = ProcessingState::Halted;
@@ -538,12 +641,6 @@ impl Core {
         self.processing_state = ProcessingState::Group0Exception;
         let backup_sr = self.ensure_supervisor_mode();
 
-        // Bus error stack frame (68000 only).
-        let (pc, ir) = (self.pc, self.ir);
-        self.push_32(pc);
-        self.push_16(backup_sr);
-        self.push_16(ir);
-        self.push_32(bad_address);    /* access address */
         /* 0 0 0 0 0 0 0 0 0 0 0 R/W I/N FC
          * R/W  0 = write, 1 = read
          * I/N  0 = instruction, 1 = not
@@ -552,9 +649,33 @@ impl Core {
         let access_info = match access_type {AccessType::Read => 0b10000, _ => 0 } |
             if processing_state.instruction_processing() { 0 } else { 0b01000 } |
             (address_space.fc() as u16);
-        self.push_16(access_info);
-        self.jump_vector(EXCEPTION_ADDRESS_ERROR);
-        Cycles(50)
+        match self.stack_group0_frame(backup_sr, bad_address, access_info, vector) {
+            Ok(()) => Cycles(50),
+            // a double fault halts the processor
+            Err(fault) => self.exception_fault(fault),
+        }
+    }
+    fn stack_group0_frame(&mut self, backup_sr: u16, bad_address: u32, access_info: u16, vector: u8) -> Result<()> {
+        // Bus error stack frame (68000 only).
+        let (pc, ir) = (self.pc, self.ir);
+        self.push_32(pc)?;
+        self.push_16(backup_sr)?;
+        self.push_16(ir)?;
+        self.push_32(bad_address)?;    /* access address */
+        self.push_16(access_info)?;
+        self.jump_vector(vector)
+    }
+    // Bus and address errors while stacking or fetching the vector of an
+    // exception begin processing of the fault instead, unless processing
+    // one already, when the core halts
+    fn exception_fault(&mut self, fault: Exception) -> Cycles {
+        match fault {
+            Exception::AddressError { address, access_type, processing_state, address_space } =>
+                self.handle_address_error(address, access_type, processing_state, address_space),
+            Exception::BusError { address, access_type, processing_state, address_space } =>
+                self.handle_bus_error(address, access_type, processing_state, address_space),
+            _ => unreachable!("only accesses fault during exception processing"),
+        }
     }
     pub fn handle_unimplemented_instruction(&mut self, pc: u32, vector: u8) -> Cycles {
         // somewhat unclear if the unimplemented instruction exceptions
@@ -584,11 +705,13 @@ impl Core {
         let backup_sr = self.ensure_supervisor_mode();
 
         // Group 1 and 2 stack frame (68000 only).
-        self.push_32(pc);
-        self.push_16(backup_sr);
-
-        self.jump_vector(vector);
-        Cycles(cycles)
+        let stacked = self.push_32(pc)
+            .and_then(|_| self.push_16(backup_sr))
+            .and_then(|_| self.jump_vector(vector));
+        match stacked {
+            Ok(()) => Cycles(cycles),
+            Err(fault) => Cycles(cycles) + self.exception_fault(fault),
+        }
     }
 
     pub fn handle_interrupt(&mut self, irq_level: u8, vector: u8) -> Cycles {
@@ -601,15 +724,17 @@ impl Core {
 
         // Musashi jumps first, and stacks later for interrupts,
         // but the other way around for exceptions
-        self.jump_vector(vector);
-
-        // Group 1 and 2 stack frame (68000 only).
-        self.push_32(pc);
-        self.push_16(backup_sr);
+        let stacked = self.jump_vector(vector)
+            // Group 1 and 2 stack frame (68000 only).
+            .and_then(|_| self.push_32(pc))
+            .and_then(|_| self.push_16(backup_sr));
 
         // 44 cycles for an interrupt according to MC68000UM, Table 8-14
         // The interrupt acknowledge cycle is assumed to take four clock periods
-        Cycles(44)
+        match stacked {
+            Ok(_) => Cycles(44),
+            Err(fault) => Cycles(44) + self.exception_fault(fault),
+        }
     }
 
     fn pending_interrupt(&self) -> Option<u8> {
@@ -622,10 +747,24 @@ impl Core {
             None
         }
     }
+    // A halted core, or a stopped one without a pending interrupt, cannot
+    // make progress no matter how many cycles it is given
+    pub fn is_idle(&self) -> bool {
+        match self.processing_state {
+            ProcessingState::Halted => true,
+            ProcessingState::Stopped => self.pending_interrupt().is_none(),
+            _ => false
+        }
+    }
     pub fn read_instruction(&mut self) -> Result<u16> {
         // first check for interrupts
         if let Some(irq) = self.pending_interrupt() {
-            let vector = self.int_ctrl.acknowledge_interrupt(irq).unwrap_or(SPURIOUS_INTERRUPT);
+            // the controller stops requesting it either way, and gives the
+            // vector used if nothing on the bus answers
+            let fallback = self.int_ctrl.acknowledge_interrupt(irq).unwrap_or(SPURIOUS_INTERRUPT);
+            let vector = self.mem.acknowledge_interrupt(irq, fallback).unwrap_or(fallback);
+            // a bus error terminating the acknowledge cycle makes it spurious
+            let vector = if self.mem.take_bus_error().is_some() { SPURIOUS_INTERRUPT } else { vector };
             Err(Exception::Interrupt(irq, vector))
         } else {
             // not interrupted, read instruction from PC
@@ -638,58 +777,115 @@ impl Core {
     pub fn execute(&mut self, cycles: i32) -> Cycles {
         self.execute_with_state(cycles, &mut EmulateAllExceptions)
     }
-    pub fn execute_with_state<T: Callbacks>(&mut self, cycles: i32, state: &mut T) -> Cycles {
+    pub fn execute_with_state<T: Callbacks<A>>(&mut self, cycles: i32, state: &mut T) -> Cycles {
         let cycles = Cycles(cycles);
         let mut remaining_cycles = cycles;
-        while remaining_cycles.any() && self.processing_state != ProcessingState::Halted && (self.processing_state != ProcessingState::Stopped || self.pending_interrupt().is_some()) {
-            // Read an instruction from PC (increments PC by 2)
-            let result = self.read_instruction().and_then(|opcode| {
-                    self.ir = opcode;
-                    // Call instruction handler to mutate Core accordingly
-                    self.ophandlers[opcode as usize](self)
-                });
-            remaining_cycles = remaining_cycles - match result {
-                Ok(cycles_used) => cycles_used,
-                Err(ex) => {
-                    match state.exception_callback(self, ex) {
-                        Ok(cycles_used) => cycles_used,
-                        Err(Exception::AddressError { address, access_type, processing_state, address_space }) =>
-                            self.handle_address_error(address, access_type, processing_state, address_space),
-                        Err(Exception::IllegalInstruction(_, pc)) =>
-                            self.handle_illegal_instruction(pc),
-                        Err(Exception::UnimplementedInstruction(_, pc, vector)) =>
-                            self.handle_unimplemented_instruction(pc, vector),
-                        Err(Exception::Trap(num, ea_calculation_cycles)) =>
-                            self.handle_trap(num, ea_calculation_cycles),
-                        Err(Exception::PrivilegeViolation(_, pc)) =>
-                            self.handle_privilege_violation(pc),
-                        Err(Exception::Interrupt(irq, vec)) =>
-                            self.handle_interrupt(irq, vec),
-                    }
+        self.watch_hit = None;
+        self.mem.take_watch_hit();
+        // as are faults of accesses made from outside the core
+        self.mem.take_bus_error();
+        while remaining_cycles.any() && !self.is_idle() {
+            if self.breakpoint_hit().is_some() {
+                break;
+            }
+            let pc = self.pc;
+            remaining_cycles = remaining_cycles - self.step(state);
+            if let Some(hit) = self.mem.take_watch_hit() {
+                self.watch_hit = Some(WatchHit { pc, ..hit });
+                break;
+            }
+        }
+        self.cycles_consumed(cycles, remaining_cycles)
+    }
+    // Executes a single instruction, or begins processing an exception
+    // if one is raised by the instruction (or an interrupt is pending)
+    pub fn step<T: Callbacks<A>>(&mut self, state: &mut T) -> Cycles {
+        // Read an instruction from PC (increments PC by 2)
+        let result = self.read_instruction().and_then(|opcode| {
+                self.start_instruction(opcode);
+                // Call instruction handler to mutate Core accordingly
+                self.ophandlers[opcode as usize](self)
+            });
+        self.complete_step(result, state)
+    }
+    fn start_instruction(&mut self, opcode: u16) {
+        self.ir = opcode;
+        self.elapsed_instructions += 1;
+        if self.call_stack.is_enabled() {
+            self.track_instruction_pc();
+        }
+    }
+    // Dispatches any exception raised by an instruction handler
+    fn complete_step<T: Callbacks<A>>(&mut self, result: Result<Cycles>, state: &mut T) -> Cycles {
+        let cycles = if self.call_stack.is_enabled() {
+            self.dispatch_tracking_calls(result, state)
+        } else {
+            self.dispatch_exception(result, state)
+        };
+        self.elapsed_cycles += cycles.0 as u64;
+        cycles
+    }
+    fn dispatch_exception<T: Callbacks<A>>(&mut self, result: Result<Cycles>, state: &mut T) -> Cycles {
+        match result {
+            Ok(cycles_used) => cycles_used,
+            Err(ex) => {
+                match state.exception_callback(self, ex) {
+                    Ok(cycles_used) => cycles_used,
+                    Err(Exception::AddressError { address, access_type, processing_state, address_space }) =>
+                        self.handle_address_error(address, access_type, processing_state, address_space),
+                    Err(Exception::BusError { address, access_type, processing_state, address_space }) =>
+                        self.handle_bus_error(address, access_type, processing_state, address_space),
+                    Err(Exception::IllegalInstruction(_, pc)) =>
+                        self.handle_illegal_instruction(pc),
+                    Err(Exception::UnimplementedInstruction(_, pc, vector)) =>
+                        self.handle_unimplemented_instruction(pc, vector),
+                    Err(Exception::Trap(num, ea_calculation_cycles)) =>
+                        self.handle_trap(num, ea_calculation_cycles),
+                    Err(Exception::PrivilegeViolation(_, pc)) =>
+                        self.handle_privilege_violation(pc),
+                    Err(Exception::Interrupt(irq, vec)) =>
+                        self.handle_interrupt(irq, vec),
                 }
-            };
+            }
         }
+    }
+    fn cycles_consumed(&mut self, cycles: Cycles, remaining_cycles: Cycles) -> Cycles {
         if self.processing_state.running() {
             cycles - remaining_cycles
         } else {
             // if not running, consume all available cycles
             // including overconsumed cycles
             let adjust = if remaining_cycles.0 < 0 { remaining_cycles } else { Cycles(0) };
+            self.elapsed_cycles += (remaining_cycles - adjust).0 as u64;
             cycles - adjust
         }
     }
 }
 
+impl<A: AddressBus + Fork> Core<A> {
+    // A copy of the core that then runs on its own, with all of its state,
+    // sharing memory with this one for as long as the bus can
+    pub fn fork(&self) -> Core<A> {
+        Core {
+            pc: self.pc, prefetch_addr: self.prefetch_addr, prefetch_data: self.prefetch_data, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
+            dar: self.dar, mem: self.mem.fork(), ophandlers: self.ophandlers.clone(),
+            irq_level: self.irq_level, int_ctrl: self.int_ctrl.clone(),
+            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag,
+            elapsed_cycles: self.elapsed_cycles, elapsed_instructions: self.elapsed_instructions, breakpoints: self.breakpoints.clone(), watch_hit: self.watch_hit, call_stack: self.call_stack.clone()
+        }
+    }
+}
+
+// A core in the same state running the full instruction set, on a fork of
+// the memory with an empty log, as needed to compare against Musashi
 impl Clone for Core {
     fn clone(&self) -> Self {
-        let mut lm = LoggingMem::new(self.mem.initializer, OpsLogger::new());
-        lm.copy_from(&self.mem);
-        assert_eq!(0, lm.logger.len());
         Core {
             pc: self.pc, prefetch_addr: 0, prefetch_data: 0, inactive_ssp: self.inactive_ssp, inactive_usp: self.inactive_usp, ir: self.ir, processing_state: self.processing_state,
-            dar: self.dar, mem: lm, ophandlers: ops::instruction_set(),
+            dar: self.dar, mem: self.mem.fork_with(OpsLogger::new()), ophandlers: ops::instruction_set(),
             irq_level: 0, int_ctrl: AutoInterruptController::new(),
-            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag
+            s_flag: self.s_flag, int_mask: self.int_mask, x_flag: self.x_flag, v_flag: self.v_flag, c_flag: self.c_flag, n_flag: self.n_flag, not_z_flag: self.not_z_flag,
+            elapsed_cycles: self.elapsed_cycles, elapsed_instructions: self.elapsed_instructions, breakpoints: self.breakpoints.clone(), watch_hit: self.watch_hit, call_stack: self.call_stack.clone()
         }
     }
 }
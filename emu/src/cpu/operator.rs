@@ -1,252 +1,253 @@
 
 use super::effective_address;
 use super::{Core, Result};
+use ram::AddressBus;
 
-pub fn ea_ay_pd_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_pd_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::predecrement_ay_8(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_ax_pd_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ax_pd_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::predecrement_ax_8(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_pi_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_pi_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::postincrement_ay_8(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_ai_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_ai_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::address_indirect_ay(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_di_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_di_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::displacement_ay(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_ix_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_ix_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::index_ay(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_aw_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_aw_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::absolute_word(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
-pub fn ea_al_8(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_al_8<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::absolute_long(core)
     .and_then(|ea| core.read_data_byte(ea).map(|val| (val, ea)))
 }
 
-pub fn ay_pd_8(core: &mut Core) -> Result<u32> {
+pub fn ay_pd_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::predecrement_ay_8(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn ay_pi_8(core: &mut Core) -> Result<u32> {
+pub fn ay_pi_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::postincrement_ay_8(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn ax_pi_8(core: &mut Core) -> Result<u32> {
+pub fn ax_pi_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::postincrement_ax_8(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn ay_ai_8(core: &mut Core) -> Result<u32> {
+pub fn ay_ai_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::address_indirect_ay(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn ay_di_8(core: &mut Core) -> Result<u32> {
+pub fn ay_di_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::displacement_ay(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn ay_ix_8(core: &mut Core) -> Result<u32> {
+pub fn ay_ix_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::index_ay(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn aw_8(core: &mut Core) -> Result<u32> {
+pub fn aw_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::absolute_word(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn al_8(core: &mut Core) -> Result<u32> {
+pub fn al_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::absolute_long(core)
     .and_then(|ea| core.read_data_byte(ea))
 }
-pub fn pcdi_8(core: &mut Core) -> Result<u32> {
+pub fn pcdi_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::displacement_pc(core)
     .and_then(|ea| core.read_program_byte(ea))
 }
-pub fn pcix_8(core: &mut Core) -> Result<u32> {
+pub fn pcix_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::index_pc(core)
     .and_then(|ea| core.read_program_byte(ea))
 }
-pub fn imm_8(core: &mut Core) -> Result<u32> {
+pub fn imm_8<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     core.read_imm_u16()
     .map(|extension| mask_out_above_8!(extension) as u32)
 }
 
-pub fn ea_ay_pd_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_pd_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::predecrement_ay_16(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_ax_pd_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ax_pd_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::predecrement_ax_16(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_pi_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_pi_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::postincrement_ay_16(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_ai_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_ai_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::address_indirect_ay(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_di_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_di_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::displacement_ay(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_ix_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_ix_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::index_ay(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_aw_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_aw_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::absolute_word(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
-pub fn ea_al_16(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_al_16<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::absolute_long(core)
     .and_then(|ea| core.read_data_word(ea).map(|val| (val, ea)))
 }
 
-pub fn ay_pd_16(core: &mut Core) -> Result<u32> {
+pub fn ay_pd_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::predecrement_ay_16(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn ay_pi_16(core: &mut Core) -> Result<u32> {
+pub fn ay_pi_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::postincrement_ay_16(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn ax_pi_16(core: &mut Core) -> Result<u32> {
+pub fn ax_pi_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::postincrement_ax_16(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn ay_ai_16(core: &mut Core) -> Result<u32> {
+pub fn ay_ai_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::address_indirect_ay(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn ay_di_16(core: &mut Core) -> Result<u32> {
+pub fn ay_di_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::displacement_ay(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn ay_ix_16(core: &mut Core) -> Result<u32> {
+pub fn ay_ix_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::index_ay(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn aw_16(core: &mut Core) -> Result<u32> {
+pub fn aw_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::absolute_word(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn al_16(core: &mut Core) -> Result<u32> {
+pub fn al_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::absolute_long(core)
     .and_then(|ea| core.read_data_word(ea))
 }
-pub fn pcdi_16(core: &mut Core) -> Result<u32> {
+pub fn pcdi_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::displacement_pc(core)
     .and_then(|ea| core.read_program_word(ea))
 }
-pub fn pcix_16(core: &mut Core) -> Result<u32> {
+pub fn pcix_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::index_pc(core)
     .and_then(|ea| core.read_program_word(ea))
 }
-pub fn imm_16(core: &mut Core) -> Result<u32> {
+pub fn imm_16<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     core.read_imm_i16()
     .map(|extension| extension as u32)
 }
 
-pub fn ea_ay_pd_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_pd_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::predecrement_ay_32(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_ax_pd_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ax_pd_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::predecrement_ax_32(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_pi_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_pi_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::postincrement_ay_32(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_ai_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_ai_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::address_indirect_ay(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_di_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_di_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::displacement_ay(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_ay_ix_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_ay_ix_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::index_ay(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_aw_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_aw_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::absolute_word(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
-pub fn ea_al_32(core: &mut Core) -> Result<(u32, u32)> {
+pub fn ea_al_32<A: AddressBus>(core: &mut Core<A>) -> Result<(u32, u32)> {
     effective_address::absolute_long(core)
     .and_then(|ea| core.read_data_long(ea).map(|val| (val, ea)))
 }
 
-pub fn ay_pd_32(core: &mut Core) -> Result<u32> {
+pub fn ay_pd_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::predecrement_ay_32(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn ay_pi_32(core: &mut Core) -> Result<u32> {
+pub fn ay_pi_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::postincrement_ay_32(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn ax_pi_32(core: &mut Core) -> Result<u32> {
+pub fn ax_pi_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::postincrement_ax_32(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn ay_ai_32(core: &mut Core) -> Result<u32> {
+pub fn ay_ai_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::address_indirect_ay(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn ay_di_32(core: &mut Core) -> Result<u32> {
+pub fn ay_di_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::displacement_ay(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn ay_ix_32(core: &mut Core) -> Result<u32> {
+pub fn ay_ix_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::index_ay(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn aw_32(core: &mut Core) -> Result<u32> {
+pub fn aw_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::absolute_word(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn al_32(core: &mut Core) -> Result<u32> {
+pub fn al_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::absolute_long(core)
     .and_then(|ea| core.read_data_long(ea))
 }
-pub fn pcdi_32(core: &mut Core) -> Result<u32> {
+pub fn pcdi_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::displacement_pc(core)
     .and_then(|ea| core.read_program_long(ea))
 }
-pub fn pcix_32(core: &mut Core) -> Result<u32> {
+pub fn pcix_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     effective_address::index_pc(core)
     .and_then(|ea| core.read_program_long(ea))
 }
-pub fn imm_32(core: &mut Core) -> Result<u32> {
+pub fn imm_32<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     core.read_imm_u32()
 }
-pub fn dx(core: &mut Core) -> Result<u32> {
+pub fn dx<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok(dx!(core))
 }
-pub fn dy(core: &mut Core) -> Result<u32> {
+pub fn dy<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok(dy!(core))
 }
-pub fn ay(core: &mut Core) -> Result<u32> {
+pub fn ay<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok(ay!(core))
 }
-pub fn ax(core: &mut Core) -> Result<u32> {
+pub fn ax<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok(ax!(core))
 }
-pub fn quick(core: &mut Core) -> Result<u32> {
+pub fn quick<A: AddressBus>(core: &mut Core<A>) -> Result<u32> {
     Ok((((core.ir as u32 >> 9) - 1) & 7) + 1)
 }
 
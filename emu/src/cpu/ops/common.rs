@@ -1,5 +1,6 @@
 #![macro_use]
 use super::super::Core;
+use ram::AddressBus;
 use cpu::{CFLAG_SET, ZFLAG_SET, XFLAG_SET, NFLAG_SET, ZFLAG_CLEAR, VFLAG_CLEAR, CFLAG_CLEAR, XFLAG_CLEAR, NFLAG_CLEAR};
 use std::num::Wrapping;
 
@@ -70,7 +71,7 @@ macro_rules! msb_32_set {
     ($e:expr) => (($e & 0x80000000) > 0)
 }
 // All instructions are ported from https://github.com/kstenerud/Musashi
-pub fn abcd_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn abcd_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     // unsigned int res = ((src) & 0x0f) + ((dst) & 0x0f) + ((m68ki_cpu.x_flag>>8)&1);
     let mut res = low_nibble!(src) + low_nibble!(dst) + core.x_flag_as_1();
 
@@ -104,7 +105,7 @@ pub fn abcd_8(core: &mut Core, dst: u32, src: u32) -> u32 {
     res
 }
 
-pub fn add_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn add_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
 
@@ -121,7 +122,7 @@ pub fn add_8(core: &mut Core, dst: u32, src: u32) -> u32 {
     core.not_z_flag = res8;
     res8
 }
-pub fn add_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn add_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst + src;
@@ -140,7 +141,7 @@ pub fn add_16(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res16
 }
-pub fn add_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn add_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res: u64 = (dst as u64) + (src as u64);
 
     let res_hi = (res >> 24) as u32;
@@ -158,7 +159,7 @@ pub fn add_32(core: &mut Core, dst: u32, src: u32) -> u32 {
     res32
 }
 
-pub fn addx_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn addx_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
 
@@ -173,7 +174,7 @@ pub fn addx_8(core: &mut Core, dst: u32, src: u32) -> u32 {
     core.not_z_flag |= res8;
     res8
 }
-pub fn addx_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn addx_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst + src + core.x_flag_as_1();
@@ -188,7 +189,7 @@ pub fn addx_16(core: &mut Core, dst: u32, src: u32) -> u32 {
     core.not_z_flag |= res16;
     res16
 }
-pub fn addx_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn addx_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res: u64 = (dst as u64) + (src as u64) + core.x_flag_as_1() as u64;
 
     let res_hi = (res >> 24) as u32;
@@ -202,7 +203,7 @@ pub fn addx_32(core: &mut Core, dst: u32, src: u32) -> u32 {
     res32
 }
 
-pub fn and_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn and_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
     let res = dst & src;
@@ -214,7 +215,7 @@ pub fn and_8(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res
 }
-pub fn and_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn and_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst & src;
@@ -227,7 +228,7 @@ pub fn and_16(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res
 }
-pub fn and_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn and_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res = dst & src;
 
     let res_hi = res >> 24;
@@ -239,7 +240,7 @@ pub fn and_32(core: &mut Core, dst: u32, src: u32) -> u32 {
     res
 }
 
-pub fn asr_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn asr_8<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_8!(dst);
     let res = src.wrapping_shr(shift);
 
@@ -282,7 +283,7 @@ pub fn asr_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn asr_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn asr_16<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_16!(dst);
     let res = src.wrapping_shr(shift);
     if shift != 0 {
@@ -324,7 +325,7 @@ pub fn asr_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn asr_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn asr_32<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = dst;
     let res = src.wrapping_shr(shift);
     if shift != 0 {
@@ -366,7 +367,7 @@ pub fn asr_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn asl_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn asl_8<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_8!(dst);
     let res = mask_out_above_8!(src.wrapping_shl(shift));
 
@@ -396,7 +397,7 @@ pub fn asl_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn asl_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn asl_16<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_16!(dst);
     let res = mask_out_above_16!(src.wrapping_shl(shift));
     if shift != 0 {
@@ -425,7 +426,7 @@ pub fn asl_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn asl_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn asl_32<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = dst;
     let res = src.wrapping_shl(shift);
     if shift != 0 {
@@ -488,7 +489,7 @@ static SHIFT_32_TABLE: [u32; 65] = [
  0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff, 0xffffffff
 ];
 
-pub fn cmp_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn cmp_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
 
@@ -502,7 +503,7 @@ pub fn cmp_8(core: &mut Core, dst: u32, src: u32) -> u32 {
     core.not_z_flag = res8;
     res8
 }
-pub fn cmp_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn cmp_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = (Wrapping(dst) - Wrapping(src)).0;
@@ -516,7 +517,7 @@ pub fn cmp_16(core: &mut Core, dst: u32, src: u32) -> u32 {
     core.not_z_flag = res16;
     res16
 }
-pub fn cmp_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn cmp_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res = (Wrapping(dst as u64) - Wrapping(src as u64)).0;
 
     let res_hi = (res >> 24) as u32;
@@ -531,7 +532,7 @@ pub fn cmp_32(core: &mut Core, dst: u32, src: u32) -> u32 {
 
 // Put common implementation of DBcc here
 // Put common implementation of DIVS here
-pub fn divs_16(core: &mut Core, dst: u32, src: i16) {
+pub fn divs_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: i16) {
     if dst == 0x80000000 && src == -1 {
         core.n_flag = 0;
         core.v_flag = 0;
@@ -554,7 +555,7 @@ pub fn divs_16(core: &mut Core, dst: u32, src: i16) {
 }
 
 // Put common implementation of DIVU here
-pub fn divu_16(core: &mut Core, dst: u32, src: u16) {
+pub fn divu_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u16) {
     let quotient: u32 = dst / (src as u32);
     let remainder: u32 = dst % (src as u32);
     if quotient < 0x10000 {
@@ -569,7 +570,7 @@ pub fn divu_16(core: &mut Core, dst: u32, src: u16) {
 }
 
 // Put common implementation of EOR here
-pub fn eor_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn eor_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
     let res = dst ^ src;
@@ -581,7 +582,7 @@ pub fn eor_8(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res
 }
-pub fn eor_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn eor_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst ^ src;
@@ -594,7 +595,7 @@ pub fn eor_16(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res
 }
-pub fn eor_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn eor_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res = dst ^ src;
 
     let res_hi = res >> 24;
@@ -615,7 +616,7 @@ pub fn eor_32(core: &mut Core, dst: u32, src: u32) -> u32 {
 // No common implementation of LINK needed
 
 // Put common implementation of LSL, LSR here
-pub fn lsr_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn lsr_8<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_8!(dst);
     let res = src.wrapping_shr(shift);
 
@@ -644,7 +645,7 @@ pub fn lsr_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn lsr_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn lsr_16<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_16!(dst);
     let res = src.wrapping_shr(shift);
     if shift != 0 {
@@ -672,7 +673,7 @@ pub fn lsr_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn lsr_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn lsr_32<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = dst;
     let res = src.wrapping_shr(shift);
     if shift != 0 {
@@ -700,7 +701,7 @@ pub fn lsr_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn lsl_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn lsl_8<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_8!(dst);
     let res = mask_out_above_8!(src.wrapping_shl(shift));
 
@@ -729,7 +730,7 @@ pub fn lsl_8(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn lsl_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn lsl_16<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = mask_out_above_16!(dst);
     let res = mask_out_above_16!(src.wrapping_shl(shift));
     if shift != 0 {
@@ -757,7 +758,7 @@ pub fn lsl_16(core: &mut Core, dst: u32, shift: u32) -> u32 {
     }
 }
 
-pub fn lsl_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
+pub fn lsl_32<A: AddressBus>(core: &mut Core<A>, dst: u32, shift: u32) -> u32 {
     let src = dst;
     let res = src.wrapping_shl(shift);
     if shift != 0 {
@@ -786,7 +787,7 @@ pub fn lsl_32(core: &mut Core, dst: u32, shift: u32) -> u32 {
 }
 
 // Put common implementation of MOVE here
-pub fn move_flags(core: &mut Core, src: u32, shift: u32) -> u32 {
+pub fn move_flags<A: AddressBus>(core: &mut Core<A>, src: u32, shift: u32) -> u32 {
     core.n_flag = src >> shift;
     core.not_z_flag = src;
     core.v_flag = 0;
@@ -803,7 +804,7 @@ pub fn move_flags(core: &mut Core, src: u32, shift: u32) -> u32 {
 // Put common implementation of MOVEP here
 // Put common implementation of MOVEQ here
 // Put common implementation of MULS here
-pub fn muls_16(core: &mut Core, dst: i16, src: i16) -> u32 {
+pub fn muls_16<A: AddressBus>(core: &mut Core<A>, dst: i16, src: i16) -> u32 {
     let res = (dst as i32).wrapping_mul(src as i32) as u32;
     core.not_z_flag = res;
     core.n_flag = res >> 24;
@@ -812,7 +813,7 @@ pub fn muls_16(core: &mut Core, dst: i16, src: i16) -> u32 {
     res
 }
 // Put common implementation of MULU here
-pub fn mulu_16(core: &mut Core, dst: u16, src: u16) -> u32 {
+pub fn mulu_16<A: AddressBus>(core: &mut Core<A>, dst: u16, src: u16) -> u32 {
     let res = (dst as u32).wrapping_mul(src as u32) as u32;
     core.not_z_flag = res;
     core.n_flag = res >> 24;
@@ -821,7 +822,7 @@ pub fn mulu_16(core: &mut Core, dst: u16, src: u16) -> u32 {
     res
 }
 // Put common implementation of NBCD here
-pub fn nbcd(core: &mut Core, dst: u32) -> Option<u32> {
+pub fn nbcd<A: AddressBus>(core: &mut Core<A>, dst: u32) -> Option<u32> {
     let mut res = mask_out_above_8!((0x9a as u32).wrapping_sub(dst).wrapping_sub(core.x_flag_as_1()));
     let answer = if res != 0x9a {
         core.v_flag = !res;
@@ -851,7 +852,7 @@ pub fn nbcd(core: &mut Core, dst: u32) -> Option<u32> {
 // Put common implementation of NEGX here
 // Put common implementation of NOP here
 // Put common implementation of NOT here
-pub fn not_8(core: &mut Core, dst: u32) -> u32 {
+pub fn not_8<A: AddressBus>(core: &mut Core<A>, dst: u32) -> u32 {
     let res = mask_out_above_8!(!dst);
 
     core.not_z_flag = res;
@@ -861,7 +862,7 @@ pub fn not_8(core: &mut Core, dst: u32) -> u32 {
 
     res
 }
-pub fn not_16(core: &mut Core, dst: u32) -> u32 {
+pub fn not_16<A: AddressBus>(core: &mut Core<A>, dst: u32) -> u32 {
     let res = mask_out_above_16!(!dst);
 
     let res_hi = res >> 8;
@@ -872,7 +873,7 @@ pub fn not_16(core: &mut Core, dst: u32) -> u32 {
 
     res
 }
-pub fn not_32(core: &mut Core, dst: u32) -> u32 {
+pub fn not_32<A: AddressBus>(core: &mut Core<A>, dst: u32) -> u32 {
     let res = !dst;
 
     let res_hi = res >> 24;
@@ -885,7 +886,7 @@ pub fn not_32(core: &mut Core, dst: u32) -> u32 {
 }
 
 // Put common implementation of OR here
-pub fn or_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn or_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
     let res = dst | src;
@@ -897,7 +898,7 @@ pub fn or_8(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res
 }
-pub fn or_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn or_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst | src;
@@ -910,7 +911,7 @@ pub fn or_16(core: &mut Core, dst: u32, src: u32) -> u32 {
 
     res
 }
-pub fn or_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn or_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res = dst | src;
 
     let res_hi = res >> 24;
@@ -928,7 +929,7 @@ pub fn or_32(core: &mut Core, dst: u32, src: u32) -> u32 {
 // Put common implementation of PEA here
 // Put common implementation of RESET here
 // Put common implementation of ROL, ROR here
-pub fn ror_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn ror_8<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = mask_out_above_8!(dst);
 
     if orig_shift != 0 {
@@ -948,7 +949,7 @@ pub fn ror_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn ror_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn ror_16<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = mask_out_above_16!(dst);
 
     if orig_shift != 0 {
@@ -968,7 +969,7 @@ pub fn ror_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn ror_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn ror_32<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = dst;
     if orig_shift != 0 {
         let shift = orig_shift & 31;
@@ -987,7 +988,7 @@ pub fn ror_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn rol_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn rol_8<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = mask_out_above_8!(dst);
 
     if orig_shift != 0 {
@@ -1015,7 +1016,7 @@ pub fn rol_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn rol_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn rol_16<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = mask_out_above_16!(dst);
     if orig_shift != 0 {
         let shift = orig_shift & 15;
@@ -1042,7 +1043,7 @@ pub fn rol_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn rol_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn rol_32<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = dst;
     if orig_shift != 0 {
         let shift = orig_shift & 31;
@@ -1062,7 +1063,7 @@ pub fn rol_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
 }
 
 // Put common implementation of ROXL, ROXR here
-pub fn roxr_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn roxr_8<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     if orig_shift != 0 {
         let shift = orig_shift % 9;
         let src = mask_out_above_8!(dst);
@@ -1085,7 +1086,7 @@ pub fn roxr_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn roxr_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn roxr_16<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     if orig_shift != 0 {
         let shift = orig_shift % 17;
         let src = mask_out_above_16!(dst);
@@ -1109,7 +1110,7 @@ pub fn roxr_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn roxr_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn roxr_32<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = dst;
     let shift = orig_shift % 33;
     let res = if shift != 0 {
@@ -1128,7 +1129,7 @@ pub fn roxr_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     res
 }
 
-pub fn roxl_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn roxl_8<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     if orig_shift != 0 {
         let shift = orig_shift % 9;
         let src = mask_out_above_8!(dst);
@@ -1151,7 +1152,7 @@ pub fn roxl_8(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn roxl_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn roxl_16<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     if orig_shift != 0 {
         let shift = orig_shift % 17;
         let src = mask_out_above_16!(dst);
@@ -1175,7 +1176,7 @@ pub fn roxl_16(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
     }
 }
 
-pub fn roxl_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
+pub fn roxl_32<A: AddressBus>(core: &mut Core<A>, dst: u32, orig_shift: u32) -> u32 {
     let src = dst;
     let shift = orig_shift % 33;
     let res = if shift != 0 {
@@ -1198,7 +1199,7 @@ pub fn roxl_32(core: &mut Core, dst: u32, orig_shift: u32) -> u32 {
 // Put common implementation of RTR here
 // Put common implementation of RTS here
 
-pub fn sbcd_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn sbcd_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let ln_src = low_nibble!(src);
     let hn_src = high_nibble!(src);
     let ln_dst = low_nibble!(dst);
@@ -1232,7 +1233,7 @@ pub fn sbcd_8(core: &mut Core, dst: u32, src: u32) -> u32 {
 // Put common implementation of STOP here
 // Put common implementation of SUB here
 
-pub fn sub_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn sub_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
 
@@ -1250,7 +1251,7 @@ pub fn sub_8(core: &mut Core, dst: u32, src: u32) -> u32 {
     res8
 }
 
-pub fn sub_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn sub_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst.wrapping_sub(src);
@@ -1270,7 +1271,7 @@ pub fn sub_16(core: &mut Core, dst: u32, src: u32) -> u32 {
     res16
 }
 
-pub fn sub_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn sub_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res: u64 = (dst as u64).wrapping_sub(src as u64);
 
     let res_hi = (res >> 24) as u32;
@@ -1288,7 +1289,7 @@ pub fn sub_32(core: &mut Core, dst: u32, src: u32) -> u32 {
     res32
 }
 
-pub fn subx_8(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn subx_8<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_8!(dst);
     let src = mask_out_above_8!(src);
     let res = dst.wrapping_sub(src).wrapping_sub(core.x_flag_as_1());
@@ -1303,7 +1304,7 @@ pub fn subx_8(core: &mut Core, dst: u32, src: u32) -> u32 {
     res8
 }
 
-pub fn subx_16(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn subx_16<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let dst = mask_out_above_16!(dst);
     let src = mask_out_above_16!(src);
     let res = dst.wrapping_sub(src).wrapping_sub(core.x_flag_as_1());
@@ -1319,7 +1320,7 @@ pub fn subx_16(core: &mut Core, dst: u32, src: u32) -> u32 {
     res16
 }
 
-pub fn subx_32(core: &mut Core, dst: u32, src: u32) -> u32 {
+pub fn subx_32<A: AddressBus>(core: &mut Core<A>, dst: u32, src: u32) -> u32 {
     let res = (dst as u64).wrapping_sub(src as u64).wrapping_sub(core.x_flag_as_1() as u64);
 
     let res_hi = (res >> 24) as u32;
@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 use super::super::Handler;
 use r68k_common::constants::*;
+use ram::AddressBus;
+use ram::pagedmem::PagedMem;
 
-#[allow(dead_code)]
-struct OpcodeHandler {
+struct OpcodeHandler<A: AddressBus> {
     mask: u32,
     matching: u32,
     name: &'static str,
-    handler: Handler
+    handler: Handler<A>
 }
 
 use super::super::InstructionSet;
@@ -1871,7 +1872,7 @@ pub const OP_TST_32_IMM  : u32 = OP_TST | LONG_SIZED | OPER_IMM;
 // Put constants for UNLK here
 pub const OP_UNLK_32     : u32 = 0b0100_1110_0101_1000;
 
-fn generate_optable() -> Vec<OpcodeHandler> {
+fn generate_optable<A: AddressBus>() -> Vec<OpcodeHandler<A>> {
     // the optable contains opcode mask, matching mask and the corresponding handler + name
     let optable = vec![
         op_entry!(MASK_LO3NIB, OP_UNIMPLEMENTED_1010, unimplemented_1010),
@@ -3668,11 +3669,27 @@ fn generate_optable() -> Vec<OpcodeHandler> {
     optable
 }
 
-pub fn generate() -> InstructionSet {
+pub fn generate<A: AddressBus>() -> InstructionSet<A> {
     // Covers all possible IR values (64k entries)
-    let mut handler: InstructionSet = Vec::with_capacity(0x10000);
+    let mut handler: InstructionSet<A> = Vec::with_capacity(0x10000);
     for _ in 0..0x10000 { handler.push(illegal); }
+    fill(&mut handler, generate_optable(), |op| op.handler);
+    handler
+}
 
+lazy_static! {
+    // The name of the handler of each opcode, like add_8_er_dn
+    pub static ref HANDLER_NAMES: Vec<&'static str> = {
+        let mut names = vec!["illegal"; 0x10000];
+        let optable: Vec<OpcodeHandler<PagedMem>> = generate_optable();
+        fill(&mut names, optable, |op| op.name);
+        names
+    };
+}
+
+// Sets the table entry of every opcode matched by an op, to the value
+// derived from that op
+fn fill<A: AddressBus, T, F: Fn(&OpcodeHandler<A>) -> T>(table: &mut [T], optable: Vec<OpcodeHandler<A>>, value: F) {
     // two of the commonly used op-masks (MASK_OUT_X (280+ uses) and
     // MASK_OUT_X_Y (500+)) are non-contiguous, so optimize for that.
     // This saves millions of iterations of the innermost loop below.
@@ -3696,7 +3713,6 @@ pub fn generate() -> InstructionSet {
     offset_cache.insert(MASK_OUT_X, x_offset(1));
     offset_cache.insert(MASK_OUT_X_Y, x_offset(8));
     offset_cache.insert(MASK_LOBYTX, x_offset(256));
-    let optable = generate_optable();
     let _ops = optable.len();
     let mut _implemented = 0;
 
@@ -3704,7 +3720,7 @@ pub fn generate() -> InstructionSet {
         match offset_cache.get(&op.mask) {
             Some(offsets) => {
                 for opcode in offsets.iter().flat_map(|&(start, len)| (start..(start+len)).map(|o| o + op.matching)) {
-                    handler[opcode as usize] = op.handler;
+                    table[opcode as usize] = value(&op);
                     _implemented += 1;
                 }
             },
@@ -3714,7 +3730,7 @@ pub fn generate() -> InstructionSet {
                 let mut matching = 0;
                 for opcode in op.matching..0x10000 {
                     if (opcode & op.mask) == op.matching {
-                        handler[opcode as usize] = op.handler;
+                        table[opcode as usize] = value(&op);
                         _implemented += 1;
                         matching += 1;
                         if matching >= max_count {
@@ -3730,15 +3746,15 @@ pub fn generate() -> InstructionSet {
     // M68010 implements 54194 opcodes (11342 illegal)
     // M68020 implements 55611 opcodes (9925 illegal)
     // println!("{:?} opcodes implemented ({:.2}% done) in {:?} instruction variants", _implemented, _implemented as f32 / 540.07f32, _ops);
-    handler
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ram::loggingmem::{LoggingMem, OpsLogger};
 
     #[test]
     fn optable_mask_and_matching_makes_sense() {
-        let optable = super::generate_optable();
+        let optable: Vec<OpcodeHandler<LoggingMem<OpsLogger>>> = super::generate_optable();
 
         for op in optable {
             if op.mask & op.matching != op.matching {
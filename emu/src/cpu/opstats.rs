@@ -0,0 +1,204 @@
+// Which instructions the guest runs: decoding opcodes to the handler
+// running them, and counting executions and cycles per opcode, to see the
+// instruction mix by handler or addressing mode, and which handlers never
+// ran at all.
+//
+// Handler names are like add_8_er_dn, the mnemonic, the size in bits and
+// the operands, including the addressing modes of effective addresses.
+use std::collections::{BTreeSet, HashMap};
+use std::convert::Infallible;
+use cpu::{Core, StopConditions, StopReason};
+use cpu::ops::handlers::HANDLER_NAMES;
+use ram::AddressBus;
+
+// as abbreviated in handler names
+pub const ADDRESSING_MODES: [&str; 12] = ["dn", "an", "ai", "pi", "pd", "di", "ix", "aw", "al", "pcdi", "pcix", "imm"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decoded {
+    pub handler: &'static str,
+    pub mnemonic: String,
+    pub size: Option<u32>, // in bits
+    pub modes: Vec<&'static str>,
+}
+
+// The handler of an opcode, and what its name tells, or None for illegal
+// opcodes
+pub fn decode(opcode: u16) -> Option<Decoded> {
+    let handler = HANDLER_NAMES[opcode as usize];
+    let mut parts = handler.split('_');
+    let mnemonic = match parts.next().unwrap() {
+        "illegal" => return None,
+        "real" => "ILLEGAL".to_string(),
+        "unimplemented" if handler.ends_with("1010") => "LINEA".to_string(),
+        "unimplemented" => "LINEF".to_string(),
+        mnemonic => mnemonic.to_uppercase(),
+    };
+    let parts: Vec<&'static str> = parts.collect();
+    let size = parts.first().and_then(|size| size.parse().ok());
+    let modes = parts.into_iter().filter(|part| ADDRESSING_MODES.contains(part)).collect();
+    Some(Decoded { handler, mnemonic, size, modes })
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counts {
+    pub executions: u64,
+    pub cycles: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.executions += other.executions;
+        self.cycles += other.cycles;
+    }
+}
+
+#[derive(Clone)]
+pub struct OpcodeStats {
+    opcodes: Vec<Counts>,
+}
+
+impl Default for OpcodeStats {
+    fn default() -> OpcodeStats {
+        OpcodeStats { opcodes: vec![Counts::default(); 0x10000] }
+    }
+}
+
+impl OpcodeStats {
+    pub fn new() -> OpcodeStats {
+        OpcodeStats::default()
+    }
+    pub fn opcode(&self, opcode: u16) -> Counts {
+        self.opcodes[opcode as usize]
+    }
+    pub fn total(&self) -> Counts {
+        let mut total = Counts::default();
+        for &counts in &self.opcodes {
+            total.add(counts);
+        }
+        total
+    }
+    fn executed(&self) -> impl Iterator<Item = (u16, Counts)> + '_ {
+        self.opcodes.iter().enumerate().filter(|&(_, counts)| counts.executions > 0).map(|(opcode, &counts)| (opcode as u16, counts))
+    }
+    fn sorted(counts: HashMap<&'static str, Counts>) -> Vec<(&'static str, Counts)> {
+        let mut counts: Vec<(&'static str, Counts)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.executions.cmp(&a.1.executions).then_with(|| a.0.cmp(b.0)));
+        counts
+    }
+    // most executed first
+    pub fn by_handler(&self) -> Vec<(&'static str, Counts)> {
+        let mut handlers = HashMap::new();
+        for (opcode, counts) in self.executed() {
+            handlers.entry(HANDLER_NAMES[opcode as usize]).or_insert_with(Counts::default).add(counts);
+        }
+        OpcodeStats::sorted(handlers)
+    }
+    // Instructions count once for each mode they use, so those moving
+    // between memory locations count twice
+    pub fn by_mode(&self) -> Vec<(&'static str, Counts)> {
+        let mut modes = HashMap::new();
+        for (opcode, counts) in self.executed() {
+            for mode in decode(opcode).map(|decoded| decoded.modes).unwrap_or_default() {
+                modes.entry(mode).or_insert_with(Counts::default).add(counts);
+            }
+        }
+        OpcodeStats::sorted(modes)
+    }
+    // The handlers of legal opcodes that never ran, by name
+    pub fn never_executed(&self) -> BTreeSet<&'static str> {
+        let mut handlers: BTreeSet<&'static str> = HANDLER_NAMES.iter().cloned().filter(|&name| name != "illegal").collect();
+        for (opcode, _) in self.executed() {
+            handlers.remove(HANDLER_NAMES[opcode as usize]);
+        }
+        handlers
+    }
+    // adds the counts of another run
+    pub fn merge(&mut self, other: &OpcodeStats) {
+        for (ours, &theirs) in self.opcodes.iter_mut().zip(other.opcodes.iter()) {
+            ours.add(theirs);
+        }
+    }
+    pub fn clear(&mut self) {
+        *self = OpcodeStats::new();
+    }
+    // The handlers by executions, with their share of them and of cycles
+    pub fn report(&self) -> String {
+        let total = self.total();
+        let share = |part: u64, all: u64| 100.0 * part as f64 / all.max(1) as f64;
+        let mut report = format!("{:>12} {:>7} {:>12} {:>7}  handler\n", "executions", "%", "cycles", "%");
+        for (handler, counts) in self.by_handler() {
+            report.push_str(&format!("{:>12} {:>7.2} {:>12} {:>7.2}  {}\n", counts.executions, share(counts.executions, total.executions),
+                counts.cycles, share(counts.cycles, total.cycles), handler));
+        }
+        report
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    // As run_until, but counting each instruction run, and the cycles
+    // it took
+    pub fn run_counting_opcodes(&mut self, conditions: &StopConditions, stats: &mut OpcodeStats) -> StopReason {
+        let result = self.run_stepwise::<Infallible, _>(conditions, |core, single| {
+            let (executed, cycle) = (core.elapsed_instructions, core.elapsed_cycles);
+            let reason = core.run_until(single);
+            if core.elapsed_instructions > executed {
+                let counts = &mut stats.opcodes[core.ir as usize];
+                counts.executions += 1;
+                counts.cycles += core.elapsed_cycles - cycle;
+            }
+            Ok(reason)
+        });
+        match result {
+            Ok(reason) => reason,
+            Err(never) => match never {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, Counts, Decoded, OpcodeStats};
+    use cpu::StopConditions;
+    use testing::core_running;
+
+    #[test]
+    fn opcodes_decode_to_their_handler() {
+        assert_eq!(Some(Decoded { handler: "add_8_er_ai", mnemonic: "ADD".to_string(), size: Some(8), modes: vec!["ai"] }), decode(0xd411));
+        assert_eq!(Some(Decoded { handler: "move_16_pi_di", mnemonic: "MOVE".to_string(), size: Some(16), modes: vec!["pi", "di"] }), decode(0x30e8));
+        assert_eq!(Some(Decoded { handler: "nop", mnemonic: "NOP".to_string(), size: None, modes: vec![] }), decode(0x4e71));
+        assert_eq!("ILLEGAL", decode(0x4afc).unwrap().mnemonic);
+        assert_eq!("LINEA", decode(0xa000).unwrap().mnemonic);
+        assert_eq!(None, decode(0x4afb));
+    }
+
+    fn counted() -> OpcodeStats {
+        // MOVEQ #1, D0; ADD.B (A1), D2; NOP; NOP
+        let mut core = core_running(&[0x70, 0x01, 0xd4, 0x11, 0x4e, 0x71, 0x4e, 0x71]);
+        core.dar[9] = 0x2000;
+        let mut stats = OpcodeStats::new();
+        core.run_counting_opcodes(&StopConditions { instructions: Some(4), ..Default::default() }, &mut stats);
+        stats
+    }
+
+    #[test]
+    fn executions_and_cycles_are_counted_by_handler_and_mode() {
+        let stats = counted();
+        assert_eq!(Counts { executions: 2, cycles: 8 }, stats.opcode(0x4e71));
+        assert_eq!(vec![("nop", Counts { executions: 2, cycles: 8 }), ("add_8_er_ai", Counts { executions: 1, cycles: 8 }),
+            ("moveq_32", Counts { executions: 1, cycles: 4 })], stats.by_handler());
+        assert_eq!(vec![("ai", Counts { executions: 1, cycles: 8 })], stats.by_mode());
+        assert_eq!(Counts { executions: 4, cycles: 20 }, stats.total());
+    }
+
+    #[test]
+    fn handlers_never_run_are_listed() {
+        let mut stats = counted();
+        let never = stats.never_executed();
+        assert!(never.contains("add_8_er_dn") && !never.contains("add_8_er_ai") && !never.contains("illegal"));
+        stats.merge(&counted());
+        assert_eq!(4, stats.opcode(0x4e71).executions);
+        let report = stats.report();
+        assert_eq!("           4   50.00           16   40.00  nop", report.lines().nth(1).unwrap());
+    }
+}
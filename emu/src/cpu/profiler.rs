@@ -0,0 +1,259 @@
+// Profiling guest code against the timing model of the core.
+//
+// run_profiled attributes cycles to the PC of each instruction, and to the
+// call path leading to it, from the shadow call stack (which it enables).
+// Exact profiles count every instruction with the cycles it took, while
+// sampling profiles run freely between samples of the PC and call path,
+// taken every so many cycles, which costs little more than just running.
+//
+// Reports are by symbol: a flat report of the self and total cycles (or
+// samples) of each function, and folded stacks, lines like
+// main;draw;plot 1234, which flamegraph.pl and friends take.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::Infallible;
+use std::io::{self, Write};
+use cpu::{Core, Frame, StopConditions, StopReason, Symbols};
+use ram::AddressBus;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProfileMode {
+    Exact,
+    Sampling(u64), // cycles between samples
+}
+
+// Cycles spent at a PC and the instructions run there, or when sampling,
+// the samples taken there in both
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PcProfile {
+    pub weight: u64,
+    pub count: u64,
+}
+
+pub struct Profiler {
+    mode: ProfileMode,
+    pcs: HashMap<u32, PcProfile>,
+    // weights by the call sites on the call stack, outermost first,
+    // followed by the PC
+    stacks: HashMap<Vec<u32>, u64>,
+    path: Vec<u32>,
+    next_sample: Option<u64>,
+}
+
+impl Profiler {
+    pub fn new(mode: ProfileMode) -> Profiler {
+        Profiler { mode, pcs: HashMap::new(), stacks: HashMap::new(), path: Vec::new(), next_sample: None }
+    }
+    pub fn mode(&self) -> ProfileMode {
+        self.mode
+    }
+    pub fn pcs(&self) -> &HashMap<u32, PcProfile> {
+        &self.pcs
+    }
+    // all cycles, or samples, recorded
+    pub fn total(&self) -> u64 {
+        self.pcs.values().map(|profile| profile.weight).sum()
+    }
+    pub fn clear(&mut self) {
+        self.pcs.clear();
+        self.stacks.clear();
+        self.next_sample = None;
+    }
+    fn unit(&self) -> &'static str {
+        match self.mode {
+            ProfileMode::Exact => "cycles",
+            ProfileMode::Sampling(_) => "samples",
+        }
+    }
+    fn trace_path(&mut self, pc: u32, frames: &[Frame]) {
+        self.path.clear();
+        self.path.extend(frames.iter().map(|frame| frame.call_site));
+        self.path.push(pc);
+    }
+    // for the path traced last
+    fn record(&mut self, weight: u64) {
+        let pc = *self.path.last().unwrap();
+        let profile = self.pcs.entry(pc).or_default();
+        profile.weight += weight;
+        profile.count += 1;
+        match self.stacks.get_mut(&self.path[..]) {
+            Some(stack) => *stack += weight,
+            None => {
+                self.stacks.insert(self.path.clone(), weight);
+            }
+        }
+    }
+    fn folded(&self, symbols: &Symbols) -> BTreeMap<Vec<String>, u64> {
+        let name = |address: u32| symbols.lookup(address).map(|(name, _)| name.to_string()).unwrap_or_else(|| format!("{:08x}", address));
+        let mut folded = BTreeMap::new();
+        for (path, &weight) in &self.stacks {
+            *folded.entry(path.iter().map(|&address| name(address)).collect()).or_insert(0) += weight;
+        }
+        folded
+    }
+    // Self and total weight per function, heaviest first
+    pub fn flat_report(&self, symbols: &Symbols) -> String {
+        let mut functions: HashMap<String, (u64, u64)> = HashMap::new();
+        for (path, weight) in self.folded(symbols) {
+            functions.entry(path.last().unwrap().clone()).or_default().0 += weight;
+            // recursive functions only count once per path
+            let unique: HashSet<&String> = path.iter().collect();
+            for function in unique {
+                functions.entry(function.clone()).or_default().1 += weight;
+            }
+        }
+        let mut functions: Vec<(String, (u64, u64))> = functions.into_iter().collect();
+        functions.sort_by(|a, b| (b.1).0.cmp(&(a.1).0).then_with(|| a.0.cmp(&b.0)));
+        let total = self.total().max(1) as f64;
+        let mut report = format!("{:>12} {:>7} {:>12} {:>7}  function\n", self.unit(), "self%", "total", "total%");
+        for (function, (own, all)) in functions {
+            report.push_str(&format!("{:>12} {:>7.2} {:>12} {:>7.2}  {}\n",
+                own, 100.0 * own as f64 / total, all, 100.0 * all as f64 / total, function));
+        }
+        report
+    }
+    // The folded stacks, as flamegraph.pl reads them
+    pub fn write_folded<W: Write>(&self, symbols: &Symbols, writer: &mut W) -> io::Result<()> {
+        for (path, weight) in self.folded(symbols) {
+            writeln!(writer, "{} {}", path.join(";"), weight)?;
+        }
+        Ok(())
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    // As run_until, but profiling. Calls are tracked for the run, unless
+    // they already were; enable the call stack first to profile a run in
+    // parts without losing its frames in between.
+    pub fn run_profiled(&mut self, conditions: &StopConditions, profiler: &mut Profiler) -> StopReason {
+        let tracking = self.call_stack.is_enabled();
+        self.call_stack.enable();
+        let reason = match profiler.mode {
+            ProfileMode::Exact => {
+                let result = self.run_stepwise::<Infallible, _>(conditions, |core, single| {
+                    let cycle = core.elapsed_cycles;
+                    profiler.trace_path(core.pc, core.call_stack.frames());
+                    let reason = core.run_until(single);
+                    if core.elapsed_cycles > cycle {
+                        profiler.record(core.elapsed_cycles - cycle);
+                    }
+                    Ok(reason)
+                });
+                match result {
+                    Ok(reason) => reason,
+                    Err(never) => match never {},
+                }
+            },
+            ProfileMode::Sampling(interval) => self.run_sampled(conditions, profiler, interval.max(1)),
+        };
+        if !tracking {
+            self.call_stack.disable();
+        }
+        reason
+    }
+    fn run_sampled(&mut self, conditions: &StopConditions, profiler: &mut Profiler, interval: u64) -> StopReason {
+        let started = self.elapsed_instructions;
+        let mut first = true;
+        loop {
+            if !first && conditions.pc == Some(self.pc) {
+                return StopReason::Pc(self.pc);
+            }
+            let next = *profiler.next_sample.get_or_insert(self.elapsed_cycles + interval);
+            let run = self.elapsed_instructions - started;
+            let chunk = StopConditions {
+                instructions: conditions.instructions.map(|count| count.saturating_sub(run)),
+                deadline: Some(conditions.deadline.map_or(next, |deadline| deadline.min(next))),
+                ..conditions.clone()
+            };
+            let reason = self.run_until(&chunk);
+            if self.elapsed_cycles >= next {
+                profiler.trace_path(self.pc, self.call_stack.frames());
+                profiler.record(1);
+                // one sample, however far it overshot
+                profiler.next_sample = Some(next + interval * ((self.elapsed_cycles - next) / interval + 1));
+            }
+            match reason {
+                StopReason::Deadline(_) if conditions.deadline.is_none_or(|deadline| self.elapsed_cycles < deadline) => first = false,
+                StopReason::Instructions(_) => return StopReason::Instructions(conditions.instructions.unwrap_or(0)),
+                reason => return reason,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Profiler, ProfileMode};
+    use cpu::{Core, StopConditions, StopReason, Symbols};
+    use ram::pagedmem::PagedMem;
+    use testing::core_running;
+
+    // main: BSR.S sub; NOP; BRA.S main; sub: NOP; RTS
+    fn core() -> Core<PagedMem> {
+        let mut core = core_running(&[0x61, 0x04, 0x4e, 0x71, 0x60, 0xfa, 0x4e, 0x71, 0x4e, 0x75]);
+        core.dar[15] = 0x8000;
+        core
+    }
+
+    fn symbols() -> Symbols {
+        let mut symbols = Symbols::new();
+        symbols.insert(0x1000, "main");
+        symbols.insert(0x1006, "sub");
+        symbols
+    }
+
+    fn folded(profiler: &Profiler) -> String {
+        let mut folded = Vec::new();
+        profiler.write_folded(&symbols(), &mut folded).unwrap();
+        String::from_utf8(folded).unwrap()
+    }
+
+    #[test]
+    fn exact_profiles_count_every_cycle() {
+        let mut core = core();
+        let mut profiler = Profiler::new(ProfileMode::Exact);
+        let reason = core.run_profiled(&StopConditions { instructions: Some(10), ..Default::default() }, &mut profiler);
+        assert_eq!(StopReason::Instructions(10), reason);
+        assert_eq!(core.elapsed_cycles, profiler.total());
+        // BSR 18, NOP 4 and BRA 10 in main, NOP 4 and RTS 16 in sub
+        assert_eq!("main 64\nmain;sub 40\n", folded(&profiler));
+        let bsr = profiler.pcs()[&0x1000];
+        assert_eq!((36, 2), (bsr.weight, bsr.count));
+        assert!(!core.call_stack.is_enabled());
+    }
+
+    #[test]
+    fn flat_reports_have_self_and_total_weights() {
+        let mut core = core();
+        let mut profiler = Profiler::new(ProfileMode::Exact);
+        core.run_profiled(&StopConditions { instructions: Some(10), ..Default::default() }, &mut profiler);
+        let report = profiler.flat_report(&symbols());
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!("      cycles   self%        total  total%  function", lines[0]);
+        assert_eq!("          64   61.54          104  100.00  main", lines[1]);
+        assert_eq!("          40   38.46           40   38.46  sub", lines[2]);
+    }
+
+    #[test]
+    fn sampling_profiles_take_a_sample_per_interval() {
+        let mut core = core();
+        let mut profiler = Profiler::new(ProfileMode::Sampling(100));
+        let reason = core.run_profiled(&StopConditions { deadline: Some(10_000), ..Default::default() }, &mut profiler);
+        assert_eq!(StopReason::Deadline(core.elapsed_cycles), reason);
+        assert_eq!(100, profiler.total());
+        let folded = folded(&profiler);
+        assert!(folded.starts_with("main ") && folded.contains("\nmain;sub "));
+        assert!(!core.call_stack.is_enabled());
+    }
+
+    #[test]
+    fn calls_tracked_before_stay_tracked() {
+        let mut core = core();
+        core.call_stack.enable();
+        let mut profiler = Profiler::new(ProfileMode::Exact);
+        // stopping within sub, and resuming there
+        core.run_profiled(&StopConditions { instructions: Some(2), ..Default::default() }, &mut profiler);
+        assert_eq!(1, core.call_stack.frames().len());
+        core.run_profiled(&StopConditions { instructions: Some(8), ..Default::default() }, &mut profiler);
+        assert_eq!("main 64\nmain;sub 40\n", folded(&profiler));
+    }
+}
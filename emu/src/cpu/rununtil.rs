@@ -0,0 +1,225 @@
+// Running until something of interest happens.
+//
+// Where execute runs for a number of cycles and leaves it to the caller
+// to work out why it returned, run_until keeps stepping until one of the
+// given conditions is met, or the core can make no further progress, and
+// says which. The elapsed_cycles and elapsed_instructions totals on the
+// core are kept by every way of executing, so the caller can tell how
+// far it got.
+use cpu::{Core, Cycles, Callbacks, Exception, EmulateAllExceptions, ProcessingState, Result, EXCEPTION_TRAP_BASE};
+use ram::AddressBus;
+use ram::watching::WatchHit;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    Pc(u32),              // about to run the instruction at the PC
+    Instructions(u64),    // ran the given number of instructions
+    Deadline(u64),        // elapsed_cycles reached the deadline, now this
+    Stopped,              // STOP executed, no interrupt pending
+    Halted,               // ExternalReset needed to resume
+    Trap(u8),             // just ran a TRAP #n left to the host
+    Breakpoint(usize),    // about to run the instruction at the breakpoint
+    Watchpoint(WatchHit), // just ran an instruction triggering it
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct StopConditions {
+    // stop before running the instruction at this PC, unless it is the
+    // first one, so a stopped core can be resumed with the same conditions
+    pub pc: Option<u32>,
+    // stop after running this many instructions
+    pub instructions: Option<u64>,
+    // stop once elapsed_cycles reaches this
+    pub deadline: Option<u64>,
+    // TRAP #n numbers the host emulates itself; the core stops right
+    // after the TRAP instruction, without taking the exception
+    pub traps: Vec<u8>,
+}
+
+// Intercepts the TRAP exceptions the host wants, and passes everything
+// else on to the callbacks of the caller
+struct HostTraps<'a, T> {
+    traps: &'a [u8],
+    state: &'a mut T,
+    taken: Option<u8>,
+}
+
+impl<'a, A: AddressBus, T: Callbacks<A>> Callbacks<A> for HostTraps<'a, T> {
+    fn exception_callback(&mut self, core: &mut Core<A>, ex: Exception) -> Result<Cycles> {
+        match ex {
+            Exception::Trap(vector, _) if vector >= EXCEPTION_TRAP_BASE && self.traps.contains(&(vector - EXCEPTION_TRAP_BASE)) => {
+                self.taken = Some(vector - EXCEPTION_TRAP_BASE);
+                // just the opcode fetch, exception processing is skipped
+                Ok(Cycles(4))
+            },
+            ex => self.state.exception_callback(core, ex)
+        }
+    }
+}
+
+impl<A: AddressBus> Core<A> {
+    pub fn run_until_pc(&mut self, pc: u32) -> StopReason {
+        self.run_until(&StopConditions { pc: Some(pc), ..Default::default() })
+    }
+    pub fn run_instructions(&mut self, count: u64) -> StopReason {
+        self.run_until(&StopConditions { instructions: Some(count), ..Default::default() })
+    }
+    pub fn run_until_cycle(&mut self, deadline: u64) -> StopReason {
+        self.run_until(&StopConditions { deadline: Some(deadline), ..Default::default() })
+    }
+    // Note that without conditions, this only returns once the core
+    // stops or halts
+    pub fn run_until(&mut self, conditions: &StopConditions) -> StopReason {
+        self.run_until_with_state(conditions, &mut EmulateAllExceptions)
+    }
+    pub fn run_until_with_state<T: Callbacks<A>>(&mut self, conditions: &StopConditions, state: &mut T) -> StopReason {
+        let started = self.elapsed_instructions;
+        self.watch_hit = None;
+        self.mem.take_watch_hit();
+        let mut state = HostTraps { traps: &conditions.traps, state, taken: None };
+        let mut first = true;
+        loop {
+            if self.is_idle() {
+                return if self.processing_state == ProcessingState::Halted { StopReason::Halted } else { StopReason::Stopped };
+            }
+            if !first && conditions.pc == Some(self.pc) {
+                return StopReason::Pc(self.pc);
+            }
+            if let Some(count) = conditions.instructions {
+                if self.elapsed_instructions - started >= count {
+                    return StopReason::Instructions(count);
+                }
+            }
+            if let Some(deadline) = conditions.deadline {
+                if self.elapsed_cycles >= deadline {
+                    return StopReason::Deadline(self.elapsed_cycles);
+                }
+            }
+            if let Some(id) = self.breakpoint_hit() {
+                return StopReason::Breakpoint(id);
+            }
+            let pc = self.pc;
+            self.step(&mut state);
+            if let Some(trap) = state.taken {
+                return StopReason::Trap(trap);
+            }
+            if let Some(hit) = self.mem.take_watch_hit() {
+                self.watch_hit = Some(WatchHit { pc, ..hit });
+                return StopReason::Watchpoint(WatchHit { pc, ..hit });
+            }
+            first = false;
+        }
+    }
+    // Runs an instruction at a time, for hosts looking at each of them:
+    // step is called with conditions to run_until for one instruction,
+    // until it returns any other reason, or the conditions are met
+    pub(crate) fn run_stepwise<E, F>(&mut self, conditions: &StopConditions, mut step: F) -> ::std::result::Result<StopReason, E>
+        where F: FnMut(&mut Self, &StopConditions) -> ::std::result::Result<StopReason, E> {
+        let started = self.elapsed_instructions;
+        let single = StopConditions { instructions: Some(1), traps: conditions.traps.clone(), ..Default::default() };
+        let mut first = true;
+        loop {
+            if !first && conditions.pc == Some(self.pc) {
+                return Ok(StopReason::Pc(self.pc));
+            }
+            if let Some(count) = conditions.instructions {
+                if self.elapsed_instructions - started >= count {
+                    return Ok(StopReason::Instructions(count));
+                }
+            }
+            if let Some(deadline) = conditions.deadline {
+                if self.elapsed_cycles >= deadline {
+                    return Ok(StopReason::Deadline(self.elapsed_cycles));
+                }
+            }
+            match step(self, &single)? {
+                StopReason::Instructions(_) => first = false,
+                reason => return Ok(reason),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StopConditions, StopReason};
+    use cpu::{Cycles, ProcessingState};
+    use testing::core_running;
+
+    // ADDQ.L #1, D0 followed by BRA.S back to it
+    const COUNTING_LOOP: [u8; 4] = [0x52, 0x80, 0x60, 0xfc];
+
+    #[test]
+    fn stops_before_running_the_instruction_at_a_pc() {
+        // three NOPs followed by ADDQ.L #1, D0
+        let mut core = core_running(&[0x4e, 0x71, 0x4e, 0x71, 0x4e, 0x71, 0x52, 0x80]);
+        assert_eq!(StopReason::Pc(0x1006), core.run_until_pc(0x1006));
+        assert_eq!(0x1006, core.pc);
+        assert_eq!(0, core.dar[0]);
+        assert_eq!(3, core.elapsed_instructions);
+    }
+
+    #[test]
+    fn resumes_from_the_pc_it_stopped_at() {
+        let mut core = core_running(&COUNTING_LOOP);
+        assert_eq!(StopReason::Pc(0x1000), core.run_until_pc(0x1000));
+        assert_eq!(StopReason::Pc(0x1000), core.run_until_pc(0x1000));
+        assert_eq!(2, core.dar[0]);
+    }
+
+    #[test]
+    fn stops_after_a_number_of_instructions() {
+        let mut core = core_running(&COUNTING_LOOP);
+        assert_eq!(StopReason::Instructions(5), core.run_instructions(5));
+        assert_eq!(3, core.dar[0]);
+        assert_eq!(StopReason::Instructions(5), core.run_instructions(5));
+        assert_eq!(5, core.dar[0]);
+        assert_eq!(10, core.elapsed_instructions);
+    }
+
+    #[test]
+    fn stops_at_a_cycle_deadline() {
+        let mut core = core_running(&COUNTING_LOOP);
+        let reason = core.run_until_cycle(100);
+        assert_eq!(StopReason::Deadline(core.elapsed_cycles), reason);
+        // ADDQ.L takes 8 cycles and BRA 10, so it can be overshot by 9
+        assert!(core.elapsed_cycles >= 100 && core.elapsed_cycles < 110);
+    }
+
+    #[test]
+    fn stops_when_the_core_stops_or_halts() {
+        // NOP followed by STOP #$2700
+        let mut core = core_running(&[0x4e, 0x71, 0x4e, 0x72, 0x27, 0x00]);
+        assert_eq!(StopReason::Stopped, core.run_until(&StopConditions::default()));
+        assert_eq!(ProcessingState::Stopped, core.processing_state);
+        assert_eq!(2, core.elapsed_instructions);
+        core.processing_state = ProcessingState::Halted;
+        assert_eq!(StopReason::Halted, core.run_until_pc(0));
+    }
+
+    #[test]
+    fn stops_on_traps_left_to_the_host() {
+        // MOVEQ #1, D0; TRAP #15; ADDQ.L #1, D0
+        let mut core = core_running(&[0x70, 0x01, 0x4e, 0x4f, 0x52, 0x80]);
+        let conditions = StopConditions { traps: vec![15], instructions: Some(3), ..Default::default() };
+        assert_eq!(StopReason::Trap(15), core.run_until(&conditions));
+        assert_eq!(0x1004, core.pc);
+        assert_eq!(ProcessingState::Normal, core.processing_state);
+        core.dar[0] = 41;
+        assert_eq!(StopReason::Instructions(1), core.run_instructions(1));
+        assert_eq!(42, core.dar[0]);
+    }
+
+    #[test]
+    fn totals_are_kept_by_execute_as_well() {
+        let mut core = core_running(&COUNTING_LOOP);
+        let Cycles(consumed) = core.execute(100);
+        assert_eq!(consumed as u64, core.elapsed_cycles);
+        assert_eq!(12, core.elapsed_instructions);
+        // a stopped core consumes all cycles it is given
+        core.processing_state = ProcessingState::Stopped;
+        core.int_mask = 0x700;
+        core.execute(1000);
+        assert_eq!(consumed as u64 + 1000, core.elapsed_cycles);
+    }
+}
@@ -0,0 +1,329 @@
+// Instruction traces.
+//
+// run_traced runs a core on a Recorder bus like run_until, and hands an
+// entry per instruction to a TraceSink: the cycle it started at, its PC,
+// opcode words and disassembly, the registers it changed, and the memory
+// accesses it made (including its own fetches, so a cached prefetch
+// means fewer). An interrupt or exception taken before an instruction
+// is part of the entry of the first instruction of its handler.
+//
+// TextTrace writes a line per entry, which diffs well against another
+// run, or against lines generated by a Musashi instruction hook in the
+// same format. BinaryTrace is the compact equivalent, and can be read
+// back with BinaryTraceReader, to diff or print later.
+use std::fmt;
+use std::io::{self, Read, Write, ErrorKind};
+use cpu::{Core, StopConditions, StopReason};
+use ram::{AddressBus, SUPERVISOR_PROGRAM, USER_PROGRAM};
+use ram::loggingmem::Operation;
+use ram::opslog::{read_access, write_access};
+use ram::recording::Recorder;
+use ram::watching::AccessSize;
+use r68k_tools::disassembler::disassemble;
+use r68k_tools::memory::{Memory, MemoryVec};
+
+const MAGIC: &[u8] = b"r68ktrc2";
+
+// the registers a trace keeps track of: D0-D7, A0-A7 and SR
+const REGISTERS: usize = 17;
+const SR: u8 = 16;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    // elapsed_cycles of the core before the instruction
+    pub cycle: u64,
+    pub pc: u32,
+    pub words: Vec<u16>,
+    pub text: String,
+    // new values of changed registers; 0-7 are D0-D7, 8-15 A0-A7, 16 SR
+    pub registers: Vec<(u8, u32)>,
+    pub accesses: Vec<Operation>,
+}
+
+fn register_name(register: u8) -> String {
+    match register {
+        0..=7 => format!("D{}", register),
+        8..=15 => format!("A{}", register - 8),
+        _ => "SR".to_string(),
+    }
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let words: Vec<String> = self.words.iter().map(|word| format!("{:04x}", word)).collect();
+        write!(f, "{:>10} {:06x}  {:<19} {:<24}", self.cycle, self.pc, words.join(" "), self.text)?;
+        for &(register, value) in &self.registers {
+            if register == SR {
+                write!(f, " SR={:04x}", value)?;
+            } else {
+                write!(f, " {}={:08x}", register_name(register), value)?;
+            }
+        }
+        for access in &self.accesses {
+            match *access {
+                Operation::None => (),
+                Operation::ReadByte(space, address, value) => write!(f, " r.b {}:{:06x}={:02x}", space.fc(), address, value)?,
+                Operation::ReadWord(space, address, value) => write!(f, " r.w {}:{:06x}={:04x}", space.fc(), address, value)?,
+                Operation::ReadLong(space, address, value) => write!(f, " r.l {}:{:06x}={:08x}", space.fc(), address, value)?,
+                Operation::WriteByte(space, address, value) => write!(f, " w.b {}:{:06x}={:02x}", space.fc(), address, value)?,
+                Operation::WriteWord(space, address, value) => write!(f, " w.w {}:{:06x}={:04x}", space.fc(), address, value)?,
+                Operation::WriteLong(space, address, value) => write!(f, " w.l {}:{:06x}={:08x}", space.fc(), address, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait TraceSink {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()>;
+}
+
+// keeps the trace in memory
+impl TraceSink for Vec<TraceEntry> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        self.push(entry.clone());
+        Ok(())
+    }
+}
+
+pub struct TextTrace<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TextTrace<W> {
+    pub fn new(writer: W) -> TextTrace<W> {
+        TextTrace { writer }
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TraceSink for TextTrace<W> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        writeln!(self.writer, "{}", entry)
+    }
+}
+
+// After a magic header, each entry is, big-endian:
+//
+//     cycle: u64, pc: u32,
+//     word count: u8, words: u16 each,
+//     text length: u16, text: UTF-8,
+//     register count: u8, registers: register u8, value u32 each,
+//     access count: u16, accesses: as ram::opslog::write_access
+//         encodes them
+pub struct BinaryTrace<W: Write> {
+    writer: W,
+    started: bool,
+}
+
+impl<W: Write> BinaryTrace<W> {
+    pub fn new(writer: W) -> BinaryTrace<W> {
+        BinaryTrace { writer, started: false }
+    }
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> TraceSink for BinaryTrace<W> {
+    fn record(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        let mut out = Vec::with_capacity(64);
+        if !self.started {
+            out.extend_from_slice(MAGIC);
+            self.started = true;
+        }
+        out.extend_from_slice(&entry.cycle.to_be_bytes());
+        out.extend_from_slice(&entry.pc.to_be_bytes());
+        out.push(entry.words.len() as u8);
+        for word in &entry.words {
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        out.extend_from_slice(&(entry.text.len() as u16).to_be_bytes());
+        out.extend_from_slice(entry.text.as_bytes());
+        out.push(entry.registers.len() as u8);
+        for &(register, value) in &entry.registers {
+            out.push(register);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+        let mut accesses = Vec::with_capacity(8 * entry.accesses.len());
+        let access_count = entry.accesses.iter().filter(|&&access| write_access(&mut accesses, access)).count();
+        out.extend_from_slice(&(access_count as u16).to_be_bytes());
+        out.extend_from_slice(&accesses);
+        self.writer.write_all(&out)
+    }
+}
+
+// Reads back what a BinaryTrace wrote, one entry at a time
+pub struct BinaryTraceReader<R: Read> {
+    reader: R,
+    started: bool,
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("not a trace: {}", what))
+}
+
+impl<R: Read> BinaryTraceReader<R> {
+    pub fn new(reader: R) -> BinaryTraceReader<R> {
+        BinaryTraceReader { reader, started: false }
+    }
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0u8; N];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+    // None at the end of the trace
+    pub fn read_entry(&mut self) -> io::Result<Option<TraceEntry>> {
+        if !self.started {
+            let mut magic = [0u8; 8];
+            match self.reader.read_exact(&mut magic) {
+                // an empty trace, as nothing was recorded
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                result => result?,
+            }
+            if magic != MAGIC {
+                return Err(invalid("bad header"));
+            }
+            self.started = true;
+        }
+        let cycle = match self.bytes::<8>() {
+            Ok(bytes) => u64::from_be_bytes(bytes),
+            Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let pc = u32::from_be_bytes(self.bytes()?);
+        let [word_count] = self.bytes::<1>()?;
+        let mut words = Vec::with_capacity(word_count as usize);
+        for _ in 0..word_count {
+            words.push(u16::from_be_bytes(self.bytes()?));
+        }
+        let mut text = vec![0u8; u16::from_be_bytes(self.bytes()?) as usize];
+        self.reader.read_exact(&mut text)?;
+        let text = String::from_utf8(text).map_err(|_| invalid("text is not UTF-8"))?;
+        let [register_count] = self.bytes::<1>()?;
+        let mut registers = Vec::with_capacity(register_count as usize);
+        for _ in 0..register_count {
+            let [register] = self.bytes::<1>()?;
+            registers.push((register, u32::from_be_bytes(self.bytes()?)));
+        }
+        let access_count = u16::from_be_bytes(self.bytes()?);
+        let mut accesses = Vec::with_capacity(access_count as usize);
+        for _ in 0..access_count {
+            // the entry can't end before its accesses
+            accesses.push(read_access(&mut self.reader)?.ok_or_else(|| io::Error::from(ErrorKind::UnexpectedEof))?);
+        }
+        Ok(Some(TraceEntry { cycle, pc, words, text, registers, accesses }))
+    }
+}
+
+impl<R: Read> Iterator for BinaryTraceReader<R> {
+    type Item = io::Result<TraceEntry>;
+    fn next(&mut self) -> Option<io::Result<TraceEntry>> {
+        self.read_entry().transpose()
+    }
+}
+
+impl<A: AddressBus> Core<Recorder<A>> {
+    // As run_until, but recording every instruction run to the sink
+    pub fn run_traced<S: TraceSink>(&mut self, conditions: &StopConditions, sink: &mut S) -> io::Result<StopReason> {
+        self.run_stepwise(conditions, |core, single| {
+            let (pc, cycle, before) = (core.pc, core.elapsed_cycles, core.traced_registers());
+            let (words, text) = core.instruction_at(pc);
+            core.mem.take_accesses();
+            let reason = core.run_until(single);
+            let accesses = core.mem.take_accesses();
+            if core.elapsed_cycles > cycle {
+                let after = core.traced_registers();
+                let registers = (0..REGISTERS).filter(|&n| before[n] != after[n]).map(|n| (n as u8, after[n])).collect();
+                sink.record(&TraceEntry { cycle, pc, words, text, registers, accesses })?;
+            }
+            Ok(reason)
+        })
+    }
+    fn traced_registers(&self) -> [u32; REGISTERS] {
+        let mut registers = [0u32; REGISTERS];
+        registers[..16].copy_from_slice(&self.dar);
+        registers[SR as usize] = self.status_register() as u32;
+        registers
+    }
+    // The opcode words and disassembly of the instruction at pc, peeked
+    // so the recorder doesn't see it. What the disassembler doesn't know yet is
+    // just the opcode, as DC.W.
+    fn instruction_at(&self, pc: u32) -> (Vec<u16>, String) {
+        let space = if self.s_flag != 0 { SUPERVISOR_PROGRAM } else { USER_PROGRAM };
+        let bytes = (0..10).map(|offset| self.mem.peek(space, pc.wrapping_add(offset), AccessSize::Byte) as u8).collect();
+        let mem = MemoryVec::new8(pc, bytes);
+        let word = |n: u32| mem.read_word(pc.wrapping_add(2 * n));
+        match disassemble(pc, &mem) {
+            Ok(instruction) => ((0..instruction.length()).map(word).collect(), format!("{}", instruction).replace('\t', " ")),
+            Err(_) => (vec![word(0)], format!("DC.W ${:04x}", word(0))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TraceEntry, TextTrace, BinaryTrace, BinaryTraceReader};
+    use cpu::{Core, StopConditions, StopReason};
+    use ram::SUPERVISOR_DATA;
+    use ram::loggingmem::Operation;
+    use ram::pagedmem::PagedMem;
+    use ram::recording::Recorder;
+    use testing::core_on;
+
+    // MOVEQ #1, D0; ADD.B D0, D1; MOVE.W D1, (A0); NOP
+    fn core() -> Core<Recorder<PagedMem>> {
+        let mut core = core_on(Recorder::new(PagedMem::new(0)), &[0x70, 0x01, 0xd2, 0x00, 0x30, 0x81, 0x4e, 0x71]);
+        core.dar[8] = 0x2000;
+        core
+    }
+
+    fn trace(core: &mut Core<Recorder<PagedMem>>) -> Vec<TraceEntry> {
+        let mut entries = Vec::new();
+        let reason = core.run_traced(&StopConditions { pc: Some(0x1006), ..Default::default() }, &mut entries).unwrap();
+        assert_eq!(StopReason::Pc(0x1006), reason);
+        entries
+    }
+
+    #[test]
+    fn entries_hold_what_each_instruction_did() {
+        let mut core = core();
+        let entries = trace(&mut core);
+        assert_eq!(3, entries.len());
+        assert_eq!((0, 0x1000, vec![0x7001]), (entries[0].cycle, entries[0].pc, entries[0].words.clone()));
+        assert_eq!("DC.W $7001", entries[0].text);
+        assert_eq!(vec![(0, 1)], entries[0].registers);
+        assert_eq!((4, 0x1002, "ADD.B D0,D1"), (entries[1].cycle, entries[1].pc, entries[1].text.as_str()));
+        assert_eq!(vec![(1, 1)], entries[1].registers);
+        assert!(entries[2].registers.is_empty());
+        assert_eq!(Some(&Operation::WriteWord(SUPERVISOR_DATA, 0x2000, 1)), entries[2].accesses.last());
+    }
+
+    #[test]
+    fn text_traces_have_a_line_per_entry() {
+        let mut core = core();
+        let mut sink = TextTrace::new(Vec::new());
+        core.run_traced(&StopConditions { instructions: Some(3), ..Default::default() }, &mut sink).unwrap();
+        let text = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!("         4 001002  d200                ADD.B D0,D1              D1=00000001", lines[1]);
+        assert!(lines[2].ends_with(" w.w 5:002000=0001"));
+    }
+
+    #[test]
+    fn binary_traces_read_back_the_same() {
+        let mut core = core();
+        let entries = trace(&mut core);
+        let mut core = self::core();
+        let mut sink = BinaryTrace::new(Vec::new());
+        core.run_traced(&StopConditions { pc: Some(0x1006), ..Default::default() }, &mut sink).unwrap();
+        let bytes = sink.into_inner();
+        let read: Vec<TraceEntry> = BinaryTraceReader::new(&bytes[..]).map(|entry| entry.unwrap()).collect();
+        assert_eq!(entries, read);
+        assert!(BinaryTraceReader::new(&b"nonsense"[..]).next().unwrap().is_err());
+        assert!(BinaryTraceReader::new(&b""[..]).next().is_none());
+    }
+}
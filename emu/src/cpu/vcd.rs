@@ -0,0 +1,198 @@
+// Bus activity as a value change dump, which GTKWave and HDL simulators
+// read, to compare against the bus of a hardware implementation.
+//
+// The signals are those of the 68000 bus: A23-A1, D15-D0, FC2-FC0, R/W,
+// UDS, LDS and AS (active low, as on the pins), and IACK, high during
+// interrupt acknowledge cycles. Times are in CPU clocks of the given
+// period, in ns.
+//
+// Each access is a bus cycle of four clocks, longs taking two, with the
+// address out from the first clock, AS and the strobes asserted from the
+// second (the strobes of writes from the third, with the data), read data
+// on the bus from the fourth, and everything released after. Accesses
+// come from an Operation log, like those of LoggingMem or a Recorder,
+// stamped with the cycle the instruction making them started at; they
+// run back to back from there, without the wait states or internal
+// cycles of a real 68000 in between, so while their order and contents
+// are exact, their timing within an instruction is not.
+use std::io::{self, Write};
+use cpu::{Core, StopConditions, StopReason};
+use ram::{AddressBus, AddressSpace, CPU_SPACE};
+use ram::loggingmem::Operation;
+use ram::recording::Recorder;
+use ram::watching::{AccessSize, WatchKind};
+
+const HEADER: &str = "$version r68k $end
+$timescale 1 ns $end
+$scope module m68k $end
+$var wire 23 ! A [23:1] $end
+$var wire 16 \" D [15:0] $end
+$var wire 3 # FC [2:0] $end
+$var wire 1 $ RW $end
+$var wire 1 % UDS_n $end
+$var wire 1 & LDS_n $end
+$var wire 1 ' AS_n $end
+$var wire 1 ( IACK $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+b00000000000000000000000 !
+bzzzzzzzzzzzzzzzz \"
+b000 #
+1$
+1%
+1&
+1'
+0(
+$end
+";
+
+pub struct VcdWriter<W: Write> {
+    writer: W,
+    period: u64,
+    started: bool,
+    // the time last written, in ns
+    time: u64,
+    // the clock the bus is free again at
+    free: u64,
+}
+
+impl<W: Write> VcdWriter<W> {
+    pub fn new(writer: W, period: u64) -> VcdWriter<W> {
+        VcdWriter { writer, period, started: false, time: 0, free: 0 }
+    }
+    // ends the dump when the bus was last released, and flushes it
+    pub fn finish(mut self) -> io::Result<W> {
+        let free = self.free;
+        self.at(free)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+    fn at(&mut self, clock: u64) -> io::Result<()> {
+        if !self.started {
+            self.writer.write_all(HEADER.as_bytes())?;
+            self.started = true;
+        }
+        let time = clock * self.period;
+        if time != self.time {
+            writeln!(self.writer, "#{}", time)?;
+            self.time = time;
+        }
+        Ok(())
+    }
+    // The accesses of an instruction, or exception processing, starting
+    // at the cycle given
+    pub fn instruction(&mut self, cycle: u64, accesses: &[Operation]) -> io::Result<()> {
+        let mut clock = cycle.max(self.free);
+        for (kind, size, space, address, value) in accesses.iter().filter_map(|access| access.parts()) {
+            if size == AccessSize::Long {
+                self.bus_cycle(clock, kind, space, address, AccessSize::Word, value >> 16)?;
+                self.bus_cycle(clock + 4, kind, space, address.wrapping_add(2), AccessSize::Word, value & 0xffff)?;
+                clock += 8;
+            } else {
+                self.bus_cycle(clock, kind, space, address, size, value)?;
+                clock += 4;
+            }
+        }
+        self.free = clock;
+        Ok(())
+    }
+    fn bus_cycle(&mut self, clock: u64, kind: WatchKind, space: AddressSpace, address: u32, size: AccessSize, value: u32) -> io::Result<()> {
+        // bytes at even addresses are on the upper half of the data bus
+        let (upper, lower) = match size {
+            AccessSize::Byte => (address & 1 == 0, address & 1 == 1),
+            _ => (true, true),
+        };
+        let high = if size == AccessSize::Byte { value } else { value >> 8 };
+        let lane = |driven: bool, byte: u32| if driven { format!("{:08b}", byte & 0xff) } else { "zzzzzzzz".to_string() };
+        let data = format!("b{}{} \"", lane(upper, high), lane(lower, value));
+        let strobes = format!("{}{}", if upper { "0%\n" } else { "" }, if lower { "0&\n" } else { "" });
+        let write = kind == WatchKind::Write;
+        self.at(clock)?;
+        write!(self.writer, "b{:023b} !\nb{:03b} #\n{}$\n{}(\n", (address >> 1) & 0x7fffff, space.fc(), if write { 0 } else { 1 }, if space == CPU_SPACE { 1 } else { 0 })?;
+        self.at(clock + 1)?;
+        if write {
+            writeln!(self.writer, "0'\n{}", data)?;
+            self.at(clock + 2)?;
+            self.writer.write_all(strobes.as_bytes())?;
+        } else {
+            write!(self.writer, "0'\n{}", strobes)?;
+            self.at(clock + 3)?;
+            writeln!(self.writer, "{}", data)?;
+        }
+        self.at(clock + 4)?;
+        write!(self.writer, "1'\n1%\n1&\nbzzzzzzzzzzzzzzzz \"\n1$\n0(\n")
+    }
+}
+
+impl<A: AddressBus> Core<Recorder<A>> {
+    // As run_until, but dumping the bus cycles of every instruction run
+    pub fn run_dumping_bus<W: Write>(&mut self, conditions: &StopConditions, vcd: &mut VcdWriter<W>) -> io::Result<StopReason> {
+        self.run_stepwise(conditions, |core, single| {
+            let cycle = core.elapsed_cycles;
+            core.mem.take_accesses();
+            let reason = core.run_until(single);
+            vcd.instruction(cycle, &core.mem.take_accesses())?;
+            Ok(reason)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{VcdWriter, HEADER};
+    use cpu::StopConditions;
+    use ram::{CPU_SPACE, SUPERVISOR_DATA};
+    use ram::loggingmem::Operation;
+    use ram::pagedmem::PagedMem;
+    use ram::recording::Recorder;
+    use testing::core_on;
+
+    fn dump(cycle: u64, accesses: &[Operation]) -> String {
+        let mut vcd = VcdWriter::new(Vec::new(), 125);
+        vcd.instruction(cycle, accesses).unwrap();
+        String::from_utf8(vcd.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn word_writes_drive_both_halves_of_the_data_bus() {
+        let dump = dump(0, &[Operation::WriteWord(SUPERVISOR_DATA, 0x1000, 0xabcd)]);
+        assert!(dump.starts_with(HEADER));
+        assert_eq!("b00000000000100000000000 !\nb101 #\n0$\n0(\n\
+            #125\n0'\nb1010101111001101 \"\n\
+            #250\n0%\n0&\n\
+            #500\n1'\n1%\n1&\nbzzzzzzzzzzzzzzzz \"\n1$\n0(\n", &dump[HEADER.len()..]);
+    }
+
+    #[test]
+    fn byte_reads_use_one_strobe_and_iack_is_flagged() {
+        let dump = dump(10, &[Operation::ReadByte(CPU_SPACE, 0xfffff9, 0x1c)]);
+        assert_eq!("#1250\nb11111111111111111111100 !\nb111 #\n1$\n1(\n\
+            #1375\n0'\n0&\n\
+            #1625\nbzzzzzzzz00011100 \"\n\
+            #1750\n1'\n1%\n1&\nbzzzzzzzzzzzzzzzz \"\n1$\n0(\n", &dump[HEADER.len()..]);
+    }
+
+    #[test]
+    fn runs_dump_bus_cycles_back_to_back_from_each_instruction() {
+        // NOP; MOVE.W D0, $2000.W
+        let mut core = core_on(Recorder::new(PagedMem::new(0)), &[0x4e, 0x71, 0x31, 0xc0, 0x20, 0x00]);
+        let mut vcd = VcdWriter::new(Vec::new(), 1);
+        core.run_dumping_bus(&StopConditions { instructions: Some(2), ..Default::default() }, &mut vcd).unwrap();
+        let dump = String::from_utf8(vcd.finish().unwrap()).unwrap();
+        let mut time = 0;
+        let mut starts = vec![];
+        for line in dump[HEADER.len()..].lines() {
+            if let Some(stamp) = line.strip_prefix('#') {
+                time = stamp.parse().unwrap();
+            } else if line.ends_with(" !") {
+                starts.push(time);
+            }
+        }
+        // the fetches of both, then the write, the MOVE waiting for the bus
+        // rather than overlapping the cycles of the NOP
+        assert_eq!(vec![0, 4, 8, 12, 16], starts);
+        assert!(dump.contains("b00000000001000000000000 !\nb101 #\n0$\n"));
+    }
+}
@@ -0,0 +1,622 @@
+// A stub for the GDB remote serial protocol, so a core can be debugged
+// with m68k-elf-gdb over a TCP or Unix socket, like
+//
+//     (gdb) target remote localhost:1234
+//
+// The core has to run on a Watcher bus, which the stub sets watchpoints
+// on. Registers are those of the org.gnu.gdb.m68k.core feature, in its
+// order: D0-D7, A0-A7, SR and PC, all 32 bits. Memory is accessed in the
+// data space of the current mode, without raising address errors.
+//
+// One connection is served at a time, in all-stop mode; serving returns
+// when GDB detaches, kills the target or disconnects.
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use cpu::{Core, Breakpoint, StopReason};
+use ram::{AddressBus, SUPERVISOR_DATA, USER_DATA};
+use ram::watching::{AccessSize, Watcher, Watchpoint, WatchKind};
+
+const REGISTERS: usize = 18;
+const SR: usize = 16;
+const PC: usize = 17;
+
+// cycles run between checks for an interrupt (Ctrl-C) from GDB
+const SLICE: u64 = 100_000;
+
+const TARGET_XML: &str = "<?xml version=\"1.0\"?>
+<!DOCTYPE target SYSTEM \"gdb-target.dtd\">
+<target version=\"1.0\">
+  <architecture>m68k:68000</architecture>
+  <feature name=\"org.gnu.gdb.m68k.core\">
+    <reg name=\"d0\" bitsize=\"32\"/>
+    <reg name=\"d1\" bitsize=\"32\"/>
+    <reg name=\"d2\" bitsize=\"32\"/>
+    <reg name=\"d3\" bitsize=\"32\"/>
+    <reg name=\"d4\" bitsize=\"32\"/>
+    <reg name=\"d5\" bitsize=\"32\"/>
+    <reg name=\"d6\" bitsize=\"32\"/>
+    <reg name=\"d7\" bitsize=\"32\"/>
+    <reg name=\"a0\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"a1\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"a2\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"a3\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"a4\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"a5\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"fp\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"sp\" bitsize=\"32\" type=\"data_ptr\"/>
+    <reg name=\"ps\" bitsize=\"32\"/>
+    <reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\"/>
+  </feature>
+</target>
+";
+
+// A stream GDB is connected over. Continuing polls it for interrupts,
+// so it needs to be able to not block.
+pub trait Connection: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+#[cfg(unix)]
+impl Connection for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+// Waits for GDB to connect, and serves it
+pub fn serve_tcp<A: AddressBus, T: ToSocketAddrs>(core: &mut Core<Watcher<A>>, address: T) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    GdbStub::new(stream).serve(core)
+}
+
+#[cfg(unix)]
+pub fn serve_unix<A: AddressBus, P: AsRef<Path>>(core: &mut Core<Watcher<A>>, path: P) -> io::Result<()> {
+    let listener = UnixListener::bind(path)?;
+    let (stream, _) = listener.accept()?;
+    GdbStub::new(stream).serve(core)
+}
+
+enum Packet {
+    Command(Vec<u8>),
+    Interrupt,
+}
+
+pub struct GdbStub<S: Connection> {
+    stream: S,
+    // breakpoint ids by address, and watchpoint ids by type, address
+    // and length, as GDB removes them by what it inserted
+    breakpoints: HashMap<u32, usize>,
+    watchpoints: HashMap<(u8, u32, u32), usize>,
+}
+
+impl<S: Connection> GdbStub<S> {
+    pub fn new(stream: S) -> GdbStub<S> {
+        GdbStub { stream, breakpoints: HashMap::new(), watchpoints: HashMap::new() }
+    }
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+    pub fn serve<A: AddressBus>(&mut self, core: &mut Core<Watcher<A>>) -> io::Result<()> {
+        while let Some(packet) = self.read_packet()? {
+            let reply = match packet {
+                Packet::Command(command) => match self.handle(core, &command)? {
+                    Some(reply) => reply,
+                    None => return Ok(()),
+                },
+                // the core isn't running
+                Packet::Interrupt => b"S02".to_vec(),
+            };
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8];
+        loop {
+            return match self.stream.read(&mut byte) {
+                Ok(0) => Ok(None),
+                Ok(_) => Ok(Some(byte[0])),
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+        }
+    }
+    // None once GDB disconnects
+    fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            // acks from GDB are skipped along with anything else
+            // outside of packets
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(0x03) => return Ok(Some(Packet::Interrupt)),
+                Some(b'$') => (),
+                Some(_) => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let mut checksum = [0u8; 2];
+            for digit in checksum.iter_mut() {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(byte) => *digit = byte,
+                }
+            }
+            let sum = data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+            if parse_hex(&checksum) == Some(sum as u32) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(Packet::Command(data)));
+            }
+            self.stream.write_all(b"-")?;
+        }
+    }
+    // Replies aren't retransmitted, as GDB only asks for that when a
+    // packet was corrupted, which the sockets used won't do
+    fn send(&mut self, reply: &[u8]) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(reply.len() + 4);
+        packet.push(b'$');
+        for &byte in reply {
+            match byte {
+                b'$' | b'#' | b'}' | b'*' => packet.extend_from_slice(&[b'}', byte ^ 0x20]),
+                _ => packet.push(byte),
+            }
+        }
+        let sum = packet[1..].iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        packet.extend_from_slice(format!("#{:02x}", sum).as_bytes());
+        self.stream.write_all(&packet)?;
+        self.stream.flush()
+    }
+    // true if GDB asked to stop the running core, or went away
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut byte = [0u8];
+        match self.stream.read(&mut byte) {
+            Ok(0) => Ok(true),
+            Ok(_) => Ok(byte[0] == 0x03),
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::Interrupted => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    // The reply to a command, or None to stop serving
+    fn handle<A: AddressBus>(&mut self, core: &mut Core<Watcher<A>>, command: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let args = &command[1.min(command.len())..];
+        let reply = match command.first() {
+            Some(b'?') => b"S05".to_vec(),
+            Some(b'g') => (0..REGISTERS).map(|n| format!("{:08x}", register(core, n))).collect::<String>().into_bytes(),
+            Some(b'G') => {
+                match parse_hex_words(args) {
+                    Some(ref values) if values.len() == REGISTERS => {
+                        // SR first, as it decides which stack pointer A7 is
+                        set_register(core, SR, values[SR]);
+                        for (n, &value) in values.iter().enumerate().filter(|&(n, _)| n != SR) {
+                            set_register(core, n, value);
+                        }
+                        b"OK".to_vec()
+                    },
+                    _ => b"E01".to_vec(),
+                }
+            },
+            Some(b'p') => match parse_hex(args) {
+                Some(n) if (n as usize) < REGISTERS => format!("{:08x}", register(core, n as usize)).into_bytes(),
+                _ => b"E01".to_vec(),
+            },
+            Some(b'P') => {
+                let mut parts = args.splitn(2, |&byte| byte == b'=');
+                match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex)) {
+                    (Some(n), Some(value)) if (n as usize) < REGISTERS => {
+                        set_register(core, n as usize, value);
+                        b"OK".to_vec()
+                    },
+                    _ => b"E01".to_vec(),
+                }
+            },
+            Some(b'm') => match parse_pair(args) {
+                Some((address, length)) => {
+                    let space = data_space(core);
+                    let bytes = (0..length).map(|offset| format!("{:02x}", core.mem.peek(space, address.wrapping_add(offset), AccessSize::Byte)));
+                    bytes.collect::<String>().into_bytes()
+                },
+                None => b"E01".to_vec(),
+            },
+            Some(b'M') => {
+                let (header, data) = split_at_colon(args);
+                match (header.and_then(parse_pair), parse_hex_bytes(data)) {
+                    (Some((address, length)), Some(ref bytes)) if bytes.len() == length as usize => {
+                        write_memory(core, address, bytes);
+                        b"OK".to_vec()
+                    },
+                    _ => b"E01".to_vec(),
+                }
+            },
+            Some(b'X') => {
+                let (header, data) = split_at_colon(args);
+                let bytes = unescape(data);
+                match header.and_then(parse_pair) {
+                    Some((address, length)) if bytes.len() == length as usize => {
+                        write_memory(core, address, &bytes);
+                        b"OK".to_vec()
+                    },
+                    _ => b"E01".to_vec(),
+                }
+            },
+            Some(&insert @ b'Z') | Some(&insert @ b'z') => self.breakpoint(core, insert == b'Z', args),
+            Some(b's') => {
+                resume_at(core, args);
+                let mut reason = core.run_instructions(1);
+                // stopped before running anything, at a breakpoint at the
+                // PC; running again steps past it
+                if let StopReason::Breakpoint(_) = reason {
+                    reason = core.run_instructions(1);
+                }
+                stop_reply(core, reason)
+            },
+            Some(b'c') => {
+                resume_at(core, args);
+                self.continue_running(core)?
+            },
+            Some(b'D') => {
+                self.send(b"OK")?;
+                return Ok(None);
+            },
+            Some(b'k') => return Ok(None),
+            Some(b'H') | Some(b'T') => b"OK".to_vec(),
+            Some(b'q') => query(args),
+            // including vCont?, which makes GDB use s and c
+            _ => Vec::new(),
+        };
+        Ok(Some(reply))
+    }
+    fn breakpoint<A: AddressBus>(&mut self, core: &mut Core<Watcher<A>>, insert: bool, args: &[u8]) -> Vec<u8> {
+        let mut parts = args.split(|&byte| byte == b',');
+        let kind = parts.next().and_then(parse_hex);
+        let address = parts.next().and_then(parse_hex);
+        let length = parts.next().and_then(parse_hex);
+        let (kind, address, length) = match (kind, address, length) {
+            (Some(kind), Some(address), Some(length)) => (kind as u8, address, length),
+            _ => return b"E01".to_vec(),
+        };
+        let watch = match kind {
+            0 | 1 => None,
+            2 => Some(WatchKind::Write),
+            3 => Some(WatchKind::Read),
+            4 => Some(WatchKind::Access),
+            _ => return Vec::new(),
+        };
+        match (watch, insert) {
+            (None, true) => {
+                self.breakpoints.entry(address).or_insert_with(|| core.breakpoints.add(Breakpoint::new(address)));
+            },
+            (None, false) => {
+                if let Some(id) = self.breakpoints.remove(&address) {
+                    core.breakpoints.remove(id);
+                }
+            },
+            (Some(watch), true) => {
+                if length == 0 {
+                    return b"E01".to_vec();
+                }
+                let end = address.wrapping_add(length - 1);
+                let id = core.mem.add(Watchpoint::new(watch, address, end));
+                if let Some(replaced) = self.watchpoints.insert((kind, address, length), id) {
+                    core.mem.remove(replaced);
+                }
+            },
+            (Some(_), false) => {
+                if let Some(id) = self.watchpoints.remove(&(kind, address, length)) {
+                    core.mem.remove(id);
+                }
+            },
+        }
+        b"OK".to_vec()
+    }
+    fn continue_running<A: AddressBus>(&mut self, core: &mut Core<Watcher<A>>) -> io::Result<Vec<u8>> {
+        self.stream.set_nonblocking(true)?;
+        let reply = loop {
+            let deadline = core.elapsed_cycles + SLICE;
+            match core.run_until_cycle(deadline) {
+                StopReason::Deadline(_) => if self.interrupted()? {
+                    break b"S02".to_vec();
+                },
+                reason => break stop_reply(core, reason),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        Ok(reply)
+    }
+}
+
+fn register<A: AddressBus>(core: &Core<A>, n: usize) -> u32 {
+    match n {
+        SR => core.status_register() as u32,
+        PC => core.pc,
+        _ => core.dar[n],
+    }
+}
+
+fn set_register<A: AddressBus>(core: &mut Core<A>, n: usize, value: u32) {
+    match n {
+        SR => core.sr_to_flags(value as u16),
+        PC => core.jump(value),
+        _ => core.dar[n] = value,
+    }
+}
+
+fn data_space<A: AddressBus>(core: &Core<A>) -> ::ram::AddressSpace {
+    if core.s_flag != 0 { SUPERVISOR_DATA } else { USER_DATA }
+}
+
+fn write_memory<A: AddressBus>(core: &mut Core<Watcher<A>>, address: u32, bytes: &[u8]) {
+    let space = data_space(core);
+    for (offset, &byte) in bytes.iter().enumerate() {
+        core.mem.poke(space, address.wrapping_add(offset as u32), AccessSize::Byte, byte as u32);
+    }
+    // what was prefetched may have been overwritten
+    core.prefetch_addr = 1;
+}
+
+// s and c may give an address to resume at
+fn resume_at<A: AddressBus>(core: &mut Core<A>, args: &[u8]) {
+    if let Some(address) = parse_hex(args) {
+        core.jump(address);
+    }
+}
+
+fn stop_reply<A: AddressBus>(core: &Core<Watcher<A>>, reason: StopReason) -> Vec<u8> {
+    match reason {
+        StopReason::Watchpoint(hit) => {
+            // GDB wants to know the kind of watchpoint, not of access
+            // and an address it watches, which the access may start before
+            let (name, address) = match core.mem.get(hit.id) {
+                Some(watchpoint) => (match watchpoint.kind {
+                    WatchKind::Read => "rwatch",
+                    WatchKind::Write => "watch",
+                    WatchKind::Access => "awatch",
+                }, hit.address.max(watchpoint.start)),
+                None => ("watch", hit.address),
+            };
+            format!("T05{}:{:x};", name, address).into_bytes()
+        },
+        _ => b"S05".to_vec(),
+    }
+}
+
+fn query(args: &[u8]) -> Vec<u8> {
+    const FEATURES: &[u8] = b"Xfer:features:read:target.xml:";
+    if args.starts_with(b"Supported") {
+        b"PacketSize=1000;qXfer:features:read+".to_vec()
+    } else if args.starts_with(FEATURES) {
+        match parse_pair(&args[FEATURES.len()..]) {
+            Some((offset, length)) => {
+                let xml = TARGET_XML.as_bytes();
+                let start = (offset as usize).min(xml.len());
+                let end = start.saturating_add(length as usize).min(xml.len());
+                let mut reply = vec![if end == xml.len() { b'l' } else { b'm' }];
+                reply.extend_from_slice(&xml[start..end]);
+                reply
+            },
+            None => b"E01".to_vec(),
+        }
+    } else if args.starts_with(b"Xfer:features:read:") {
+        b"E00".to_vec()
+    } else if args == b"Attached" {
+        b"1".to_vec()
+    } else if args == b"fThreadInfo" {
+        b"m1".to_vec()
+    } else if args == b"sThreadInfo" {
+        b"l".to_vec()
+    } else if args == b"C" {
+        b"QC1".to_vec()
+    } else {
+        Vec::new()
+    }
+}
+
+fn parse_hex(digits: &[u8]) -> Option<u32> {
+    ::std::str::from_utf8(digits).ok().and_then(|digits| u32::from_str_radix(digits, 16).ok())
+}
+
+// address,length
+fn parse_pair(args: &[u8]) -> Option<(u32, u32)> {
+    let mut parts = args.splitn(2, |&byte| byte == b',');
+    match (parts.next().and_then(parse_hex), parts.next().and_then(parse_hex)) {
+        (Some(first), Some(second)) => Some((first, second)),
+        _ => None,
+    }
+}
+
+fn parse_hex_bytes(digits: &[u8]) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits.chunks(2).map(|pair| parse_hex(pair).map(|byte| byte as u8)).collect()
+}
+
+fn parse_hex_words(digits: &[u8]) -> Option<Vec<u32>> {
+    if !digits.len().is_multiple_of(8) {
+        return None;
+    }
+    digits.chunks(8).map(parse_hex).collect()
+}
+
+fn split_at_colon(args: &[u8]) -> (Option<&[u8]>, &[u8]) {
+    match args.iter().position(|&byte| byte == b':') {
+        Some(colon) => (Some(&args[..colon]), &args[colon + 1..]),
+        None => (None, &[]),
+    }
+}
+
+// binary data in X packets escapes $, #, } and * with } and xor 0x20
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len());
+    let mut escaped = false;
+    for &byte in data {
+        if escaped {
+            bytes.push(byte ^ 0x20);
+            escaped = false;
+        } else if byte == b'}' {
+            escaped = true;
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connection, GdbStub};
+    use std::cell::Cell;
+    use std::io::{self, ErrorKind, Read, Write};
+    use cpu::Core;
+    use ram::pagedmem::PagedMem;
+    use ram::watching::Watcher;
+    use testing::core_on;
+
+    // GDB, as a script of everything it sends. The script is only read
+    // from while blocking, so continuing is never interrupted.
+    struct Script {
+        input: Vec<u8>,
+        position: usize,
+        output: Vec<u8>,
+        nonblocking: Cell<bool>,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.nonblocking.get() {
+                return Err(io::Error::new(ErrorKind::WouldBlock, "scripted"));
+            }
+            let count = buf.len().min(self.input.len() - self.position);
+            buf[..count].copy_from_slice(&self.input[self.position..self.position + count]);
+            self.position += count;
+            Ok(count)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Script {
+        fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+            self.nonblocking.set(nonblocking);
+            Ok(())
+        }
+    }
+
+    fn packet(command: &str) -> String {
+        let sum = command.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        format!("${}#{:02x}", command, sum)
+    }
+
+    // the acks and replies to the commands
+    fn session(core: &mut Core<Watcher<PagedMem>>, input: &[u8]) -> (String, Vec<String>) {
+        let script = Script { input: input.to_vec(), position: 0, output: Vec::new(), nonblocking: Cell::new(false) };
+        let mut stub = GdbStub::new(script);
+        stub.serve(core).unwrap();
+        let output = String::from_utf8(stub.into_inner().output).unwrap();
+        let acks = output.chars().filter(|&c| c == '+' || c == '-').collect();
+        let replies = output.split('$').skip(1).map(|reply| reply.split('#').next().unwrap().to_string()).collect();
+        (acks, replies)
+    }
+
+    fn run(core: &mut Core<Watcher<PagedMem>>, commands: &[&str]) -> Vec<String> {
+        let input: String = commands.iter().map(|command| packet(command)).collect();
+        session(core, input.as_bytes()).1
+    }
+
+    // ADDQ.L #1, D0; MOVE.W D0, (A0); BRA.S back to the ADDQ
+    fn core() -> Core<Watcher<PagedMem>> {
+        let mut core = core_on(Watcher::new(PagedMem::new(0)), &[0x52, 0x80, 0x30, 0x80, 0x60, 0xfa]);
+        core.dar[8] = 0x2000;
+        core
+    }
+
+    #[test]
+    fn packets_with_bad_checksums_are_rejected() {
+        let mut core = core();
+        let (acks, replies) = session(&mut core, b"+$?#00$?#3f");
+        assert_eq!("-+", acks);
+        assert_eq!(vec!["S05"], replies);
+    }
+
+    #[test]
+    fn registers_are_in_the_m68k_core_layout() {
+        let mut core = core();
+        core.dar[1] = 0x12345678;
+        let replies = run(&mut core, &["g", "P11=2000", "p11", "p10", "p12"]);
+        assert_eq!(18 * 8, replies[0].len());
+        assert_eq!("12345678", &replies[0][8..16]);
+        assert_eq!("00001000", &replies[0][17 * 8..]);
+        assert_eq!(vec!["OK", "00002000", "00002700", "E01"], replies[1..].to_vec());
+        assert_eq!(0x2000, core.pc);
+        let mut registers: String = (0..16).map(|n| format!("{:08x}", n)).collect();
+        registers.push_str("0000001f00003000");
+        assert_eq!(vec!["OK"], run(&mut core, &[&format!("G{}", registers)]));
+        assert_eq!((15, 0x1f, 0x3000), (core.dar[15], core.status_register(), core.pc));
+        assert_eq!(0, core.s_flag);
+    }
+
+    #[test]
+    fn memory_can_be_read_and_written() {
+        let mut core = core();
+        let replies = run(&mut core, &["m1000,6", "M3000,2:abcd", "m3000,3"]);
+        assert_eq!(vec!["5280308060fa", "OK", "abcd00"], replies);
+        // binary data, with an escaped #
+        let (_, replies) = session(&mut core, format!("$X3000,2:}}\x03a#{:02x}", b"X3000,2:}\x03a".iter().fold(0u8, |sum, &b| sum.wrapping_add(b))).as_bytes());
+        assert_eq!(vec!["OK"], replies);
+        assert_eq!(0x2361, core.mem.inner.read_u8(0x3000) << 8 | core.mem.inner.read_u8(0x3001));
+    }
+
+    #[test]
+    fn continuing_stops_at_breakpoints_and_stepping_steps_past_them() {
+        let mut core = core();
+        let replies = run(&mut core, &["Z0,1002,2", "c", "s", "z0,1002,2", "s"]);
+        assert_eq!(vec!["OK", "S05", "S05", "OK", "S05"], replies);
+        assert_eq!(0x1000, core.pc);
+        assert_eq!(1, core.dar[0]);
+        assert!(core.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn watchpoints_report_their_kind_and_address() {
+        let mut core = core();
+        let replies = run(&mut core, &["Z2,2000,2", "c", "z2,2000,2", "Z4,2001,1", "c"]);
+        assert_eq!(vec!["OK", "T05watch:2000;", "OK", "OK", "T05awatch:2001;"], replies);
+        assert_eq!(2, core.dar[0]);
+    }
+
+    #[test]
+    fn the_target_description_is_served_in_chunks() {
+        let mut core = core();
+        let replies = run(&mut core, &["qSupported:xmlRegisters=m68k", "qXfer:features:read:target.xml:0,20", "qXfer:features:read:target.xml:20,10000"]);
+        assert!(replies[0].contains("qXfer:features:read+"));
+        assert_eq!("m<?xml version=\"1.0\"?>\n<!DOCTYPE ", replies[1]);
+        assert!(replies[2].starts_with('l') && replies[2].contains("org.gnu.gdb.m68k.core"));
+    }
+}
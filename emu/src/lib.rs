@@ -3,10 +3,17 @@ extern crate lazy_static;
 #[cfg(test)]
 extern crate itertools;
 extern crate r68k_common;
+extern crate r68k_tools;
+#[cfg(feature = "jit")]
+extern crate libc;
 
 pub mod cpu;
 pub mod ram;
+pub mod runner;
+pub mod gdb;
 pub mod musashi;
+#[cfg(test)]
+mod testing;
 
 #[cfg(test)]
 mod tests {
@@ -350,7 +350,7 @@ mod tests {
     use ram::SUPERVISOR_PROGRAM;
     use super::MUSASHI_LOCK;
     use super::QUICKCHECK_LOCK;
-    use ram::{AddressBus};
+    use ram::{AddressBus, CPU_SPACE};
     use ram::loggingmem::Operation;
     use cpu::{Core, EXCEPTION_ZERO_DIVIDE, EXCEPTION_CHK, Cycles};
     use std::cmp;
@@ -532,11 +532,11 @@ mod tests {
                 },
             }
         }
-        let mut r68k = musashi.clone(); // so very self-aware!
+        let r68k = musashi.clone(); // so very self-aware!
         let _mutex = MUSASHI_LOCK.lock().unwrap();
 
         let musashi_cycles = reset_and_execute1(&mut musashi, memory_initializer & mem_mask);
-        let r68k_cycles = r68k.execute(super::EXEC_CYCLES);
+        let (r68k, r68k_cycles) = execute_r68k(r68k, super::EXEC_CYCLES);
         // panics if differences are found. Returns false if an
         // exception occurred, and then we cannot compare state further
         // unless PC is the same (as then the cores have progressed to
@@ -562,6 +562,27 @@ mod tests {
         }
     }
 
+    #[cfg(not(all(feature = "jit", target_arch = "x86_64", unix)))]
+    fn execute_r68k(mut r68k: Core, cycles: i32) -> (Core, Cycles) {
+        let cycles = r68k.execute(cycles);
+        (r68k, cycles)
+    }
+
+    // Translates on a copy of the core first, so that the core under
+    // test runs the translated code rather than the recording interpreter
+    #[cfg(all(feature = "jit", target_arch = "x86_64", unix))]
+    fn execute_r68k(r68k: Core, cycles: i32) -> (Core, Cycles) {
+        use cpu::jit::Jit;
+        use ram::tracking::WriteTracker;
+        let mut jit = Jit::new();
+        let mut warmup = r68k.clone().map_bus(WriteTracker::new);
+        jit.execute(&mut warmup, cycles);
+        let mut jitted = r68k.map_bus(WriteTracker::new);
+        jit.attach(&mut jitted);
+        let cycles = jit.execute(&mut jitted, cycles);
+        (jitted.map_bus(WriteTracker::into_inner), cycles)
+    }
+
     macro_rules! qc8 {
         ($opmask:ident, $opcode:ident, $fn_name:ident) => (qc!($opmask, $opcode, $fn_name, hammer_cores););
     }
@@ -2556,7 +2577,9 @@ mod tests {
         })
     }
     fn assert_all_memory_accesses_equal(r68k: &Core) {
-        assert_equal(get_ops(), r68k.mem.logger.ops());
+        // Musashi autovectors interrupts without an acknowledge cycle
+        let ops = r68k.mem.logger.ops().into_iter().filter(|op| !matches!(*op, Operation::ReadByte(CPU_SPACE, _, _)));
+        assert_equal(get_ops(), ops);
     }
     fn memory_accesses_equal_unless_exception(r68k: &Core) -> Option<u8> {
         let is_reading_vector = |&op| match op {
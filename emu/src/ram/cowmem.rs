@@ -0,0 +1,229 @@
+use std::sync::Arc;
+use super::{AddressSpace, AddressBus, Fork, ADDRBUS_MASK};
+
+const PAGE_SIZE: usize = 0x1000;
+const PAGE_BITS: u32 = 12;
+const PAGES: usize = (ADDRBUS_MASK as usize + 1) / PAGE_SIZE;
+
+type Page = [u8; PAGE_SIZE];
+
+// RAM in copy-on-write pages. Forks share all pages with the memory they
+// were forked from, until either writes to one, which then gets a copy of
+// its own, so save states, speculative runs and trying many inputs from
+// one point cost little more than the pages they touch. Pages never
+// written read as the initializer, as with PagedMem.
+//
+// Pages are reference counted atomically, so forks can run on other
+// threads, like those of the runner.
+pub struct CowMem {
+    pages: Vec<Option<Arc<Page>>>,
+    initializer: u32,
+}
+
+impl CowMem {
+    pub fn new(initializer: u32) -> CowMem {
+        CowMem { pages: vec![None; PAGES], initializer }
+    }
+    pub fn initializer(&self) -> u32 {
+        self.initializer
+    }
+    // pages written to, whether shared or not
+    pub fn written_pages(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+    // pages shared with forks, or what this was forked from
+    pub fn shared_pages(&self) -> usize {
+        self.pages.iter().filter(|page| page.as_ref().is_some_and(|page| Arc::strong_count(page) > 1)).count()
+    }
+    fn initializer_byte(&self, address: u32) -> u8 {
+        self.initializer.to_be_bytes()[(address & 3) as usize]
+    }
+    fn page_mut(&mut self, address: u32) -> &mut Page {
+        let initializer = self.initializer.to_be_bytes();
+        let page = self.pages[((address & ADDRBUS_MASK) >> PAGE_BITS) as usize].get_or_insert_with(|| {
+            let mut page = [0; PAGE_SIZE];
+            for (index, byte) in page.iter_mut().enumerate() {
+                *byte = initializer[index & 3];
+            }
+            Arc::new(page)
+        });
+        Arc::make_mut(page)
+    }
+    pub fn read_u8(&self, address: u32) -> u32 {
+        match self.pages[((address & ADDRBUS_MASK) >> PAGE_BITS) as usize] {
+            Some(ref page) => page[address as usize & (PAGE_SIZE - 1)] as u32,
+            None => self.initializer_byte(address) as u32,
+        }
+    }
+    pub fn write_u8(&mut self, address: u32, value: u32) {
+        let unwritten = self.pages[((address & ADDRBUS_MASK) >> PAGE_BITS) as usize].is_none();
+        // as PagedMem, only allocating for bytes that differ
+        if unwritten && value as u8 == self.initializer_byte(address) {
+            return;
+        }
+        self.page_mut(address)[address as usize & (PAGE_SIZE - 1)] = value as u8;
+    }
+    // copies bytes in a page at a time, wrapping around at the end
+    pub fn load(&mut self, address: u32, bytes: &[u8]) {
+        let mut done = 0;
+        while done < bytes.len() {
+            let at = address.wrapping_add(done as u32);
+            let index = at as usize & (PAGE_SIZE - 1);
+            let run = (PAGE_SIZE - index).min(bytes.len() - done);
+            self.page_mut(at)[index..index + run].copy_from_slice(&bytes[done..done + run]);
+            done += run;
+        }
+    }
+    // and out
+    pub fn save(&self, address: u32, buffer: &mut [u8]) {
+        let mut done = 0;
+        while done < buffer.len() {
+            let at = address.wrapping_add(done as u32);
+            let index = at as usize & (PAGE_SIZE - 1);
+            let run = (PAGE_SIZE - index).min(buffer.len() - done);
+            match self.pages[((at & ADDRBUS_MASK) >> PAGE_BITS) as usize] {
+                Some(ref page) => buffer[done..done + run].copy_from_slice(&page[index..index + run]),
+                None => for (offset, byte) in buffer[done..done + run].iter_mut().enumerate() {
+                    *byte = self.initializer_byte(at.wrapping_add(offset as u32));
+                },
+            }
+            done += run;
+        }
+    }
+    // the page an access lies within, unless it crosses into the next
+    fn within_page(&self, address: u32, size: usize) -> Option<(&Page, usize)> {
+        let index = address as usize & (PAGE_SIZE - 1);
+        match self.pages[((address & ADDRBUS_MASK) >> PAGE_BITS) as usize] {
+            Some(ref page) if index + size <= PAGE_SIZE => Some((page, index)),
+            _ => None,
+        }
+    }
+}
+
+impl Fork for CowMem {
+    // shares every page, copying only the page table
+    fn fork(&self) -> CowMem {
+        CowMem { pages: self.pages.clone(), initializer: self.initializer }
+    }
+}
+
+impl AddressBus for CowMem {
+    // shares the pages of the other, as forking does
+    fn copy_from(&mut self, other: &Self) {
+        self.pages.clone_from(&other.pages);
+        self.initializer = other.initializer;
+    }
+
+    // in the 16 byte pages of PagedMem, for comparable quotas, counting
+    // shared pages too
+    fn allocated_pages(&self) -> usize {
+        self.written_pages() * PAGE_SIZE / 16
+    }
+
+    fn read_byte(&self, _: AddressSpace, address: u32) -> u32 {
+        self.read_u8(address)
+    }
+
+    fn read_word(&self, _: AddressSpace, address: u32) -> u32 {
+        match self.within_page(address, 2) {
+            Some((page, index)) => u16::from_be_bytes([page[index], page[index + 1]]) as u32,
+            None => self.read_u8(address) << 8 | self.read_u8(address.wrapping_add(1)),
+        }
+    }
+
+    fn read_long(&self, _: AddressSpace, address: u32) -> u32 {
+        match self.within_page(address, 4) {
+            Some((page, index)) => u32::from_be_bytes([page[index], page[index + 1], page[index + 2], page[index + 3]]),
+            None => (0..4).fold(0, |value, offset| value << 8 | self.read_u8(address.wrapping_add(offset))),
+        }
+    }
+
+    fn write_byte(&mut self, _: AddressSpace, address: u32, value: u32) {
+        self.write_u8(address, value);
+    }
+
+    fn write_word(&mut self, _: AddressSpace, address: u32, value: u32) {
+        self.write_u8(address, value >> 8);
+        self.write_u8(address.wrapping_add(1), value);
+    }
+
+    fn write_long(&mut self, _: AddressSpace, address: u32, value: u32) {
+        for offset in 0..4 {
+            self.write_u8(address.wrapping_add(offset), value >> (24 - 8 * offset));
+        }
+    }
+
+    fn read_bytes(&self, _: AddressSpace, address: u32, buffer: &mut [u8]) {
+        self.save(address, buffer);
+    }
+
+    fn write_bytes(&mut self, _: AddressSpace, address: u32, bytes: &[u8]) {
+        self.load(address, bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use super::CowMem;
+    use cpu::{Core, StopConditions};
+    use ram::{AddressBus, Fork, SUPERVISOR_DATA, USER_DATA};
+
+    #[test]
+    fn unwritten_memory_reads_as_the_initializer() {
+        let mut mem = CowMem::new(0x01020304);
+        assert_eq!(0x03040102, mem.read_long(SUPERVISOR_DATA, 0xffffe));
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x01020304);
+        assert_eq!(0, mem.written_pages());
+        mem.write_long(SUPERVISOR_DATA, 0xffffe, 0x12345678);
+        assert_eq!(2, mem.written_pages());
+        assert_eq!(0x12345678, mem.read_long(USER_DATA, 0xffffe));
+        assert_eq!(0x5678, mem.read_word(USER_DATA, 0x1100000));
+    }
+
+    #[test]
+    fn blocks_are_copied_a_page_at_a_time() {
+        let mut mem = CowMem::new(0xaaaaaaaa);
+        let image: Vec<u8> = (0..0x2000).map(|byte| byte as u8).collect();
+        mem.write_bytes(SUPERVISOR_DATA, 0xfff800, &image);
+        assert_eq!(3, mem.written_pages());
+        assert_eq!(0xfeff, mem.read_word(SUPERVISOR_DATA, 0xfffffe));
+        assert_eq!(0x0001, mem.read_word(SUPERVISOR_DATA, 0));
+        let mut buffer = [0; 4];
+        mem.read_bytes(USER_DATA, 0x17fe, &mut buffer);
+        assert_eq!([0xfe, 0xff, 0xaa, 0xaa], buffer);
+    }
+
+    #[test]
+    fn forks_share_pages_until_written() {
+        let mut parent = CowMem::new(0);
+        parent.write_long(SUPERVISOR_DATA, 0x1000, 0x11111111);
+        parent.write_long(SUPERVISOR_DATA, 0x2000, 0x22222222);
+        let mut child = parent.fork();
+        assert_eq!(2, child.shared_pages());
+        child.write_long(SUPERVISOR_DATA, 0x1000, 0x33333333);
+        assert_eq!((1, 1), (parent.shared_pages(), child.shared_pages()));
+        assert_eq!(0x11111111, parent.read_long(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(0x33333333, child.read_long(SUPERVISOR_DATA, 0x1000));
+        parent.write_long(SUPERVISOR_DATA, 0x3000, 0x44444444);
+        assert_eq!(0, child.read_long(SUPERVISOR_DATA, 0x3000));
+        drop(parent);
+        assert_eq!(0, child.shared_pages());
+    }
+
+    #[test]
+    fn forked_cores_run_on_their_own() {
+        // ADDQ.L #1, $4000.W; BRA.S back
+        let mut mem = CowMem::new(0);
+        mem.load(0x1000, &[0x52, 0xb8, 0x40, 0x00, 0x60, 0xfa]);
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[0] = 7;
+        let mut fork = core.fork();
+        fork.run_until(&StopConditions { instructions: Some(4), ..Default::default() });
+        assert_eq!(2, fork.mem.read_long(SUPERVISOR_DATA, 0x4000));
+        assert_eq!(0, core.mem.read_long(SUPERVISOR_DATA, 0x4000));
+        assert_eq!((7, 0x1000), (fork.dar[0], core.pc));
+        assert_eq!(4, fork.elapsed_instructions - core.elapsed_instructions);
+        assert!(Arc::ptr_eq(&core.ophandlers, &fork.ophandlers));
+    }
+}
@@ -0,0 +1,228 @@
+use super::{AddressSpace, AddressBus, ADDRBUS_MASK};
+
+// Plain RAM in one contiguous allocation, for running real workloads:
+// no logging, hashing or allocation per access. Smaller sizes (powers of
+// two) are mirrored across the address bus, as when not all address lines
+// are decoded.
+pub struct FlatMem {
+    bytes: Vec<u8>,
+    mask: u32,
+}
+
+impl FlatMem {
+    // all 16 MB, filled with the initializer repeated, as with PagedMem
+    pub fn new(initializer: u32) -> FlatMem {
+        FlatMem::with_size(ADDRBUS_MASK + 1, initializer)
+    }
+    pub fn with_size(size: u32, initializer: u32) -> FlatMem {
+        assert!(size.is_power_of_two() && size <= ADDRBUS_MASK + 1, "RAM size must be a power of two up to 16 MB, not {}", size);
+        // zeroed memory is left to the OS to hand out as it is touched
+        let bytes = if initializer == 0 {
+            vec![0; size as usize]
+        } else {
+            initializer.to_be_bytes().iter().cloned().cycle().take(size as usize).collect()
+        };
+        FlatMem { bytes, mask: size - 1 }
+    }
+    pub fn size(&self) -> u32 {
+        self.bytes.len() as u32
+    }
+    pub fn read_u8(&self, address: u32) -> u32 {
+        self.bytes[(address & self.mask) as usize] as u32
+    }
+    pub fn write_u8(&mut self, address: u32, value: u32) {
+        self.bytes[(address & self.mask) as usize] = value as u8;
+    }
+    // copies bytes in, wrapping around at the end
+    pub fn load(&mut self, address: u32, bytes: &[u8]) {
+        let mut done = 0;
+        while done < bytes.len() {
+            let index = (address.wrapping_add(done as u32) & self.mask) as usize;
+            let run = (self.bytes.len() - index).min(bytes.len() - done);
+            self.bytes[index..index + run].copy_from_slice(&bytes[done..done + run]);
+            done += run;
+        }
+    }
+    // and out
+    pub fn save(&self, address: u32, buffer: &mut [u8]) {
+        let mut done = 0;
+        while done < buffer.len() {
+            let index = (address.wrapping_add(done as u32) & self.mask) as usize;
+            let run = (self.bytes.len() - index).min(buffer.len() - done);
+            buffer[done..done + run].copy_from_slice(&self.bytes[index..index + run]);
+            done += run;
+        }
+    }
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+    // where a value of the given size starts, unless it wraps around
+    fn contiguous(&self, address: u32, size: usize) -> Option<usize> {
+        let index = (address & self.mask) as usize;
+        if index + size <= self.bytes.len() { Some(index) } else { None }
+    }
+}
+
+impl AddressBus for FlatMem {
+    fn copy_from(&mut self, other: &Self) {
+        self.bytes.clone_from(&other.bytes);
+        self.mask = other.mask;
+    }
+
+    // in the 16 byte pages of PagedMem, for comparable quotas
+    fn allocated_pages(&self) -> usize {
+        self.bytes.len() / 16
+    }
+
+    fn read_byte(&self, _: AddressSpace, address: u32) -> u32 {
+        self.read_u8(address)
+    }
+
+    fn read_word(&self, _: AddressSpace, address: u32) -> u32 {
+        match self.contiguous(address, 2) {
+            Some(index) => u16::from_be_bytes([self.bytes[index], self.bytes[index + 1]]) as u32,
+            None => self.read_u8(address) << 8 | self.read_u8(address.wrapping_add(1)),
+        }
+    }
+
+    fn read_long(&self, _: AddressSpace, address: u32) -> u32 {
+        match self.contiguous(address, 4) {
+            Some(index) => u32::from_be_bytes([self.bytes[index], self.bytes[index + 1], self.bytes[index + 2], self.bytes[index + 3]]),
+            None => (0..4).fold(0, |value, offset| value << 8 | self.read_u8(address.wrapping_add(offset))),
+        }
+    }
+
+    fn write_byte(&mut self, _: AddressSpace, address: u32, value: u32) {
+        self.write_u8(address, value);
+    }
+
+    fn write_word(&mut self, _: AddressSpace, address: u32, value: u32) {
+        match self.contiguous(address, 2) {
+            Some(index) => self.bytes[index..index + 2].copy_from_slice(&(value as u16).to_be_bytes()),
+            None => {
+                self.write_u8(address, value >> 8);
+                self.write_u8(address.wrapping_add(1), value);
+            }
+        }
+    }
+
+    fn write_long(&mut self, _: AddressSpace, address: u32, value: u32) {
+        match self.contiguous(address, 4) {
+            Some(index) => self.bytes[index..index + 4].copy_from_slice(&value.to_be_bytes()),
+            None => for offset in 0..4 {
+                self.write_u8(address.wrapping_add(offset), value >> (24 - 8 * offset));
+            },
+        }
+    }
+
+    fn read_bytes(&self, _: AddressSpace, address: u32, buffer: &mut [u8]) {
+        self.save(address, buffer);
+    }
+
+    fn write_bytes(&mut self, _: AddressSpace, address: u32, bytes: &[u8]) {
+        self.load(address, bytes);
+    }
+
+    // blocks wrapping around go element by element
+    fn read_words(&self, address_space: AddressSpace, address: u32, buffer: &mut [u16]) {
+        match self.contiguous(address, 2 * buffer.len()) {
+            Some(index) => for (word, bytes) in buffer.iter_mut().zip(self.bytes[index..].chunks_exact(2)) {
+                *word = u16::from_be_bytes([bytes[0], bytes[1]]);
+            },
+            None => for (offset, word) in buffer.iter_mut().enumerate() {
+                *word = self.read_word(address_space, address.wrapping_add(2 * offset as u32)) as u16;
+            },
+        }
+    }
+
+    fn write_words(&mut self, address_space: AddressSpace, address: u32, words: &[u16]) {
+        match self.contiguous(address, 2 * words.len()) {
+            Some(index) => for (bytes, word) in self.bytes[index..].chunks_exact_mut(2).zip(words) {
+                bytes.copy_from_slice(&word.to_be_bytes());
+            },
+            None => for (offset, &word) in words.iter().enumerate() {
+                self.write_word(address_space, address.wrapping_add(2 * offset as u32), word as u32);
+            },
+        }
+    }
+
+    fn read_longs(&self, address_space: AddressSpace, address: u32, buffer: &mut [u32]) {
+        match self.contiguous(address, 4 * buffer.len()) {
+            Some(index) => for (long, bytes) in buffer.iter_mut().zip(self.bytes[index..].chunks_exact(4)) {
+                *long = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            },
+            None => for (offset, long) in buffer.iter_mut().enumerate() {
+                *long = self.read_long(address_space, address.wrapping_add(4 * offset as u32));
+            },
+        }
+    }
+
+    fn write_longs(&mut self, address_space: AddressSpace, address: u32, longs: &[u32]) {
+        match self.contiguous(address, 4 * longs.len()) {
+            Some(index) => for (bytes, long) in self.bytes[index..].chunks_exact_mut(4).zip(longs) {
+                bytes.copy_from_slice(&long.to_be_bytes());
+            },
+            None => for (offset, &long) in longs.iter().enumerate() {
+                self.write_long(address_space, address.wrapping_add(4 * offset as u32), long);
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlatMem;
+    use ram::{AddressBus, SUPERVISOR_DATA, USER_PROGRAM};
+
+    #[test]
+    fn is_filled_with_the_initializer() {
+        let mem = FlatMem::with_size(0x1000, 0x01020304);
+        assert_eq!(0x01020304, mem.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!(0x0304, mem.read_word(USER_PROGRAM, 0x102));
+        assert_eq!(0x02, mem.read_byte(SUPERVISOR_DATA, 0x105));
+    }
+
+    #[test]
+    fn values_are_big_endian() {
+        let mut mem = FlatMem::new(0);
+        mem.write_long(SUPERVISOR_DATA, 0x1000, 0x12345678);
+        mem.write_word(SUPERVISOR_DATA, 0x1004, 0x9abcdef);
+        assert_eq!(&[0x12, 0x34, 0x56, 0x78, 0xcd, 0xef], &mem.as_slice()[0x1000..0x1006]);
+        assert_eq!(0x5678cdef, mem.read_long(USER_PROGRAM, 0x1002));
+    }
+
+    #[test]
+    fn addresses_wrap_around_the_size() {
+        let mut mem = FlatMem::with_size(0x100, 0);
+        mem.write_long(SUPERVISOR_DATA, 0x1fe, 0x12345678);
+        assert_eq!(0x5678, mem.read_word(SUPERVISOR_DATA, 0));
+        assert_eq!(0x12345678, mem.read_long(SUPERVISOR_DATA, 0xfe));
+        assert_eq!(0x12, mem.read_u8(0xffffffe));
+    }
+
+    #[test]
+    fn blocks_are_copied_whole_or_wrapping_around() {
+        let mut mem = FlatMem::with_size(0x100, 0);
+        mem.write_longs(SUPERVISOR_DATA, 0x10, &[0x01020304, 0x05060708]);
+        let mut words = [0; 4];
+        mem.read_words(SUPERVISOR_DATA, 0x10, &mut words);
+        assert_eq!([0x0102, 0x0304, 0x0506, 0x0708], words);
+        mem.write_bytes(SUPERVISOR_DATA, 0x1fe, &[0xaa, 0xbb, 0xcc]);
+        mem.write_words(SUPERVISOR_DATA, 0xfc, &[0x1122, 0x3344]);
+        let mut bytes = [0; 6];
+        mem.read_bytes(USER_PROGRAM, 0xfc, &mut bytes);
+        assert_eq!([0x11, 0x22, 0x33, 0x44, 0xcc, 0x00], bytes);
+        let mut longs = [0; 1];
+        mem.read_longs(USER_PROGRAM, 0x1fe, &mut longs);
+        assert_eq!([0x3344cc00], longs);
+    }
+
+    #[test]
+    #[should_panic]
+    fn sizes_must_be_powers_of_two() {
+        FlatMem::with_size(3000, 0);
+    }
+}
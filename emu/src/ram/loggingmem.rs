@@ -1,6 +1,7 @@
 use std::cell::RefCell;
-use super::{AddressSpace, AddressBus, ADDRBUS_MASK};
+use super::{AddressSpace, AddressBus, Fork, ADDRBUS_MASK, CPU_SPACE, SUPERVISOR_DATA, iack_address};
 use ram::pagedmem::{PagedMem, DiffIter};
+use ram::watching::{AccessSize, WatchKind};
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Operation {
@@ -12,6 +13,33 @@ pub enum Operation {
     WriteWord(AddressSpace, u32, u32),
     WriteLong(AddressSpace, u32, u32),
 }
+impl Operation {
+    // The kind (Read or Write), size, address space, address and value
+    // of an access, or None for Operation::None
+    pub fn parts(&self) -> Option<(WatchKind, AccessSize, AddressSpace, u32, u32)> {
+        match *self {
+            Operation::None => None,
+            Operation::ReadByte(space, address, value) => Some((WatchKind::Read, AccessSize::Byte, space, address, value as u32)),
+            Operation::ReadWord(space, address, value) => Some((WatchKind::Read, AccessSize::Word, space, address, value as u32)),
+            Operation::ReadLong(space, address, value) => Some((WatchKind::Read, AccessSize::Long, space, address, value)),
+            Operation::WriteByte(space, address, value) => Some((WatchKind::Write, AccessSize::Byte, space, address, value)),
+            Operation::WriteWord(space, address, value) => Some((WatchKind::Write, AccessSize::Word, space, address, value)),
+            Operation::WriteLong(space, address, value) => Some((WatchKind::Write, AccessSize::Long, space, address, value)),
+        }
+    }
+    // and back, where WatchKind::Access reads
+    pub fn from_parts(kind: WatchKind, size: AccessSize, space: AddressSpace, address: u32, value: u32) -> Operation {
+        match (kind, size) {
+            (WatchKind::Write, AccessSize::Byte) => Operation::WriteByte(space, address, value),
+            (WatchKind::Write, AccessSize::Word) => Operation::WriteWord(space, address, value),
+            (WatchKind::Write, AccessSize::Long) => Operation::WriteLong(space, address, value),
+            (_, AccessSize::Byte) => Operation::ReadByte(space, address, value as u8),
+            (_, AccessSize::Word) => Operation::ReadWord(space, address, value as u16),
+            (_, AccessSize::Long) => Operation::ReadLong(space, address, value),
+        }
+    }
+}
+
 use std::fmt;
 impl fmt::Debug for Operation {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -29,8 +57,13 @@ impl fmt::Debug for Operation {
 
 pub trait OpsLogging {
     fn log(&self, op: Operation);
+    // number of operations currently held by the logger
+    fn len(&self) -> usize {
+        0
+    }
 }
 
+#[derive(Clone)]
 pub struct OpsLogger {
     log: RefCell<Vec<Operation>>,
 }
@@ -51,6 +84,9 @@ impl OpsLogging for OpsLogger {
     fn log(&self, op: Operation) {
         self.log.borrow_mut().push(op);
     }
+    fn len(&self) -> usize {
+        self.log.borrow().len()
+    }
 }
 
 pub struct LoggingMem<T: OpsLogging> {
@@ -74,13 +110,39 @@ impl<T: OpsLogging> LoggingMem<T> {
     pub fn diffs<'a>(&'a self) -> DiffIter<'a> {
         self.mem.diffs()
     }
+    // copies bytes in without logging, as loaders do
+    pub fn load(&mut self, address: u32, bytes: &[u8]) {
+        self.mem.write_bytes(SUPERVISOR_DATA, address, bytes);
+    }
+    // A fork of the memory, logging to another logger
+    pub fn fork_with<U: OpsLogging>(&self, logger: U) -> LoggingMem<U> {
+        LoggingMem { logger, mem: self.mem.fork(), initializer: self.initializer }
+    }
+}
+
+impl<T: OpsLogging + Clone> Fork for LoggingMem<T> {
+    // with a copy of the log so far
+    fn fork(&self) -> LoggingMem<T> {
+        self.fork_with(self.logger.clone())
+    }
 }
 
 impl<T: OpsLogging> AddressBus for LoggingMem<T> {
     fn copy_from(&mut self, other: &Self) {
-        for (addr, byte) in other.diffs() {
-            self.write_u8(addr, byte as u32);
-        }
+        self.mem.copy_from(&other.mem);
+    }
+
+    fn allocated_pages(&self) -> usize {
+        self.mem.allocated_pages()
+    }
+
+    fn log_len(&self) -> usize {
+        self.logger.len()
+    }
+
+    fn acknowledge_interrupt(&mut self, level: u8, fallback: u8) -> Option<u8> {
+        self.logger.log(Operation::ReadByte(CPU_SPACE, iack_address(level), fallback));
+        None
     }
 
     fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
@@ -123,11 +185,20 @@ impl<T: OpsLogging> AddressBus for LoggingMem<T> {
         self.write_u8(address.wrapping_add(2), (value >>  8));
         self.write_u8(address.wrapping_add(3), (value >>  0));
     }
+
+    // not logged
+    fn peek(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        self.mem.peek(address_space, address, size)
+    }
+    fn poke(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        self.mem.poke(address_space, address, size, value)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{LoggingMem, AddressBus, OpsLogger, OpsLogging, Operation};
+    use ram::watching::AccessSize;
     use ram::{SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM, ADDRBUS_MASK};
 
     struct NopLogger;
@@ -137,6 +208,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn peeks_and_pokes_are_not_logged() {
+        let mut mem = LoggingMem::new(0x01020304, OpsLogger::new());
+        mem.poke(SUPERVISOR_DATA, 0x80, AccessSize::Word, 0xabcd);
+        assert_eq!(0xabcd0304, mem.peek(USER_DATA, 0x80, AccessSize::Long));
+        assert_eq!(0, mem.logger.len());
+    }
+
     #[test]
     fn read_byte_is_logged() {
         do_read_byte_is_logged(0x80);
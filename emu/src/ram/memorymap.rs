@@ -0,0 +1,683 @@
+use std::cell::{Cell, RefCell};
+use super::{AddressSpace, AddressBus, BusFault, Fork, ADDRBUS_MASK, CPU_SPACE, USER_DATA, USER_PROGRAM, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, iack_address};
+use super::watching::AccessSize;
+
+// A memory mapped device. Accesses come whole, at offsets from the start
+// of its region.
+//
+// Debuggers see devices through their debug view instead: peek is what
+// reading would give, without the side effects, like clearing flags, and
+// poke sets registers directly. Devices without one read as all ones and
+// ignore pokes.
+//
+// Maps are forked with copies of their devices, which only devices
+// returning one from fork can give.
+pub trait Device {
+    fn read(&mut self, address_space: AddressSpace, offset: u32, size: AccessSize) -> u32;
+    fn write(&mut self, address_space: AddressSpace, offset: u32, size: AccessSize, value: u32);
+    fn peek(&self, _address_space: AddressSpace, _offset: u32, size: AccessSize) -> u32 {
+        0xffffffff >> (32 - 8 * size.bytes())
+    }
+    fn poke(&mut self, _address_space: AddressSpace, _offset: u32, _size: AccessSize, _value: u32) {
+    }
+    // a copy going its own way from here on
+    fn fork(&self) -> Option<Box<dyn Device>> {
+        None
+    }
+}
+
+// What writes to ROM do
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RomWrites {
+    Ignore,
+    BusError,
+}
+
+// What accesses nothing answers do: read as the byte given, repeated, and
+// ignore writes, or raise a bus error
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Unmapped {
+    OpenBus(u8),
+    BusError,
+}
+
+// When a boot overlay stops answering, leaving its addresses to the
+// regions below it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverlayEnd {
+    // after this many bus cycles since reset, longs taking two, as with
+    // a counter; fetching the reset vectors takes four
+    BusCycles(u32),
+    // on the first access from first to last address, like where the
+    // ROM really is
+    AccessTo(u32, u32),
+    // on the first write from first to last address, as to a latch
+    WriteTo(u32, u32),
+}
+
+enum Contents {
+    Ram(Vec<u8>),
+    Rom(Vec<u8>, RomWrites),
+    Device(RefCell<Box<dyn Device>>),
+}
+
+struct Region {
+    // first and last address decoded
+    start: u32,
+    end: u32,
+    // of what is decoded, so anything smaller than the region repeats
+    size: u32,
+    contents: Contents,
+    // the function codes decoded, as bits
+    spaces: u8,
+    // bus errors for user accesses, and for writes
+    supervisor_only: bool,
+    write_protected: bool,
+    enabled: Cell<bool>,
+    overlay: Option<OverlayEnd>,
+}
+
+impl Region {
+    fn fork(&self) -> Region {
+        let contents = match self.contents {
+            Contents::Ram(ref memory) => Contents::Ram(memory.clone()),
+            Contents::Rom(ref image, writes) => Contents::Rom(image.clone(), writes),
+            Contents::Device(ref device) => match device.borrow().fork() {
+                Some(device) => Contents::Device(RefCell::new(device)),
+                None => panic!("the device at {:06x} can't be forked", self.start),
+            }
+        };
+        Region { contents, enabled: self.enabled.clone(), ..*self }
+    }
+    // big-endian, repeating through the region
+    fn get(memory: &[u8], size: u32, offset: u32, bytes: u32) -> u32 {
+        (0..bytes).fold(0, |value, byte| value << 8 | memory[((offset + byte) % size) as usize] as u32)
+    }
+    fn set(memory: &mut [u8], size: u32, offset: u32, bytes: u32, value: u32) {
+        for byte in 0..bytes {
+            memory[((offset + byte) % size) as usize] = (value >> (8 * (bytes - 1 - byte))) as u8;
+        }
+    }
+}
+
+// Routes address ranges to RAM, ROM and devices, as the address decoding
+// of a machine would. Regions may be larger than what they hold, which
+// then repeats through them, as with partial address decoding. Regions
+// may overlap, the one mapped last answering.
+//
+// Regions can also decode the function code, to have separate program and
+// data spaces, or protect supervisor memory and code from user programs
+// and stray writes. Only regions set to decode CPU space answer interrupt
+// acknowledge cycles, with the vector they read as.
+//
+// Boot overlays are regions answering only from reset until some access
+// ends them, as boards mapping ROM at 0 for the reset vectors, and RAM
+// there afterwards, do.
+pub struct MemoryMap {
+    regions: Vec<Region>,
+    unmapped: Unmapped,
+    fault: Cell<Option<BusFault>>,
+    // whether any overlay is still answering, and the bus cycles since reset
+    overlaid: Cell<bool>,
+    cycles: Cell<u32>,
+}
+
+impl MemoryMap {
+    pub fn new(unmapped: Unmapped) -> MemoryMap {
+        MemoryMap { regions: Vec::new(), unmapped, fault: Cell::new(None), overlaid: Cell::new(false), cycles: Cell::new(0) }
+    }
+    // RAM of the given size from start to end, both included, cleared
+    pub fn add_ram(&mut self, start: u32, end: u32, size: u32) -> usize {
+        self.add(start, end, size, Contents::Ram(vec![0; size as usize]))
+    }
+    pub fn add_rom(&mut self, start: u32, end: u32, image: Vec<u8>, writes: RomWrites) -> usize {
+        self.add(start, end, image.len() as u32, Contents::Rom(image, writes))
+    }
+    // a device answering size bytes worth of offsets
+    pub fn add_device(&mut self, start: u32, end: u32, size: u32, device: Box<dyn Device>) -> usize {
+        self.add(start, end, size, Contents::Device(RefCell::new(device)))
+    }
+    fn add(&mut self, start: u32, end: u32, size: u32, contents: Contents) -> usize {
+        let (start, end) = (start & ADDRBUS_MASK, end & ADDRBUS_MASK);
+        assert!(start <= end && size > 0, "empty region {:06x}-{:06x}", start, end);
+        let spaces = [USER_DATA, USER_PROGRAM, SUPERVISOR_DATA, SUPERVISOR_PROGRAM].iter().fold(0, |bits, space| bits | 1 << space.fc());
+        self.regions.push(Region { start, end, size, contents, spaces, supervisor_only: false, write_protected: false,
+            enabled: Cell::new(true), overlay: None });
+        self.regions.len() - 1
+    }
+    // Has a region answer only accesses in these address spaces, leaving
+    // the others to the regions below it
+    pub fn set_spaces(&mut self, id: usize, spaces: &[AddressSpace]) {
+        self.regions[id].spaces = spaces.iter().fold(0, |bits, space| bits | 1 << space.fc());
+    }
+    // user accesses to it raise bus errors
+    pub fn set_supervisor_only(&mut self, id: usize, supervisor_only: bool) {
+        self.regions[id].supervisor_only = supervisor_only;
+    }
+    // writes to it raise bus errors
+    pub fn set_write_protected(&mut self, id: usize, write_protected: bool) {
+        self.regions[id].write_protected = write_protected;
+    }
+    // disabled regions answer nothing, as when a bank is switched out
+    pub fn set_enabled(&mut self, id: usize, enabled: bool) {
+        self.regions[id].enabled.set(enabled);
+    }
+    pub fn is_enabled(&self, id: usize) -> bool {
+        self.regions[id].enabled.get()
+    }
+    // Makes a region a boot overlay, answering from now, as after reset,
+    // until the access given ends it
+    pub fn set_boot_overlay(&mut self, id: usize, end: OverlayEnd) {
+        let end = match end {
+            OverlayEnd::AccessTo(first, last) => OverlayEnd::AccessTo(first & ADDRBUS_MASK, last & ADDRBUS_MASK),
+            OverlayEnd::WriteTo(first, last) => OverlayEnd::WriteTo(first & ADDRBUS_MASK, last & ADDRBUS_MASK),
+            end => end,
+        };
+        self.regions[id].overlay = Some(end);
+        self.regions[id].enabled.set(true);
+        self.overlaid.set(true);
+    }
+    // the contents of RAM and ROM regions, by the id add_* gave
+    pub fn memory(&self, id: usize) -> Option<&[u8]> {
+        match self.regions.get(id).map(|region| &region.contents) {
+            Some(&Contents::Ram(ref bytes)) | Some(&Contents::Rom(ref bytes, _)) => Some(bytes),
+            _ => None,
+        }
+    }
+    pub fn memory_mut(&mut self, id: usize) -> Option<&mut [u8]> {
+        match self.regions.get_mut(id).map(|region| &mut region.contents) {
+            Some(&mut Contents::Ram(ref mut bytes)) | Some(&mut Contents::Rom(ref mut bytes, _)) => Some(bytes),
+            _ => None,
+        }
+    }
+    fn region(&self, address_space: AddressSpace, address: u32) -> Option<(usize, u32)> {
+        let space = 1 << address_space.fc();
+        self.regions.iter().enumerate().rev()
+            .find(|&(_, region)| region.start <= address && address <= region.end && region.spaces & space != 0 && region.enabled.get())
+            .map(|(index, region)| (index, (address - region.start) % region.size))
+    }
+    fn fault(&self, address_space: AddressSpace, address: u32, write: bool) {
+        if self.fault.get().is_none() {
+            self.fault.set(Some(BusFault { address_space, address, write }));
+        }
+    }
+    // ends the overlays the access given ends, once it is done
+    fn end_overlays(&self, address: u32, size: AccessSize, write: bool) {
+        if !self.overlaid.get() {
+            return;
+        }
+        let address = address & ADDRBUS_MASK;
+        let cycles = self.cycles.get().saturating_add(if size == AccessSize::Long { 2 } else { 1 });
+        self.cycles.set(cycles);
+        let mut overlaid = false;
+        for region in self.regions.iter().filter(|region| region.enabled.get()) {
+            let ended = match region.overlay {
+                None => continue,
+                Some(OverlayEnd::BusCycles(count)) => cycles >= count,
+                Some(OverlayEnd::AccessTo(first, last)) => first <= address && address <= last,
+                Some(OverlayEnd::WriteTo(first, last)) => write && first <= address && address <= last,
+            };
+            region.enabled.set(!ended);
+            overlaid |= !ended;
+        }
+        self.overlaid.set(overlaid);
+    }
+    fn read(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        let value = self.route_read(address_space, address, size);
+        self.end_overlays(address, size, false);
+        value
+    }
+    fn write(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        self.route_write(address_space, address, size, value);
+        self.end_overlays(address, size, true);
+    }
+    fn route_read(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        let address = address & ADDRBUS_MASK;
+        let bytes = size.bytes();
+        let (index, offset) = match self.region(address_space, address) {
+            Some((index, _)) if self.regions[index].supervisor_only && !address_space.is_supervisor() => {
+                self.fault(address_space, address, false);
+                return 0xffffffff >> (32 - 8 * bytes);
+            },
+            Some(found) => found,
+            None => {
+                let value = match self.unmapped {
+                    Unmapped::OpenBus(byte) => byte as u32 * 0x01010101,
+                    Unmapped::BusError => {
+                        self.fault(address_space, address, false);
+                        0xffffffff
+                    }
+                };
+                return value >> (32 - 8 * bytes);
+            }
+        };
+        let region = &self.regions[index];
+        match region.contents {
+            Contents::Ram(ref memory) | Contents::Rom(ref memory, _) => Region::get(memory, region.size, offset, bytes),
+            Contents::Device(ref device) => device.borrow_mut().read(address_space, offset, size),
+        }
+    }
+    fn route_write(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        let address = address & ADDRBUS_MASK;
+        let bytes = size.bytes();
+        let (index, offset) = match self.region(address_space, address) {
+            Some((index, _)) if self.regions[index].write_protected || self.regions[index].supervisor_only && !address_space.is_supervisor() => {
+                self.fault(address_space, address, true);
+                return;
+            },
+            Some(found) => found,
+            None => {
+                if self.unmapped == Unmapped::BusError {
+                    self.fault(address_space, address, true);
+                }
+                return;
+            }
+        };
+        let region = &mut self.regions[index];
+        match region.contents {
+            Contents::Ram(ref mut memory) => Region::set(memory, region.size, offset, bytes, value),
+            Contents::Rom(_, RomWrites::Ignore) => (),
+            Contents::Rom(_, RomWrites::BusError) => self.fault(address_space, address, true),
+            Contents::Device(ref mut device) => device.get_mut().write(address_space, offset, size, value),
+        }
+    }
+}
+
+impl Fork for MemoryMap {
+    // copies RAM and ROM, and forks the devices; panics if any can't be
+    fn fork(&self) -> MemoryMap {
+        MemoryMap {
+            regions: self.regions.iter().map(Region::fork).collect(), unmapped: self.unmapped, fault: self.fault.clone(),
+            overlaid: self.overlaid.clone(), cycles: self.cycles.clone()
+        }
+    }
+}
+
+impl AddressBus for MemoryMap {
+    // copies RAM and which regions are enabled, the only state a map
+    // can copy, region by region
+    fn copy_from(&mut self, other: &Self) {
+        for (ours, theirs) in self.regions.iter_mut().zip(other.regions.iter()) {
+            ours.enabled.set(theirs.enabled.get());
+            if let (Contents::Ram(ours), Contents::Ram(theirs)) = (&mut ours.contents, &theirs.contents) {
+                ours.clone_from(theirs);
+            }
+        }
+        self.overlaid.set(other.overlaid.get());
+        self.cycles.set(other.cycles.get());
+    }
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, AccessSize::Byte)
+    }
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, AccessSize::Word)
+    }
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, AccessSize::Long)
+    }
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.write(address_space, address, AccessSize::Byte, value)
+    }
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.write(address_space, address, AccessSize::Word, value)
+    }
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.write(address_space, address, AccessSize::Long, value)
+    }
+    // RAM, in the 16 byte pages of PagedMem
+    fn allocated_pages(&self) -> usize {
+        self.regions.iter().map(|region| match region.contents {
+            Contents::Ram(ref memory) => memory.len() / 16,
+            _ => 0,
+        }).sum()
+    }
+    fn acknowledge_interrupt(&mut self, level: u8, _fallback: u8) -> Option<u8> {
+        let address = iack_address(level);
+        self.region(CPU_SPACE, address).map(|_| self.read(CPU_SPACE, address, AccessSize::Byte) as u8)
+    }
+    fn take_bus_error(&mut self) -> Option<BusFault> {
+        self.fault.take()
+    }
+    // As the core sees it, except for ROM taking writes, without faults,
+    // protection or ending overlays
+    fn peek(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        let bytes = size.bytes();
+        let (index, offset) = match self.region(address_space, address & ADDRBUS_MASK) {
+            Some(found) => found,
+            None => {
+                let byte = match self.unmapped {
+                    Unmapped::OpenBus(byte) => byte as u32,
+                    Unmapped::BusError => 0xff,
+                };
+                return (byte * 0x01010101) >> (32 - 8 * bytes);
+            }
+        };
+        let region = &self.regions[index];
+        match region.contents {
+            Contents::Ram(ref memory) | Contents::Rom(ref memory, _) => Region::get(memory, region.size, offset, bytes),
+            Contents::Device(ref device) => device.borrow().peek(address_space, offset, size),
+        }
+    }
+    fn poke(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        if let Some((index, offset)) = self.region(address_space, address & ADDRBUS_MASK) {
+            let region = &mut self.regions[index];
+            match region.contents {
+                Contents::Ram(ref mut memory) | Contents::Rom(ref mut memory, _) => Region::set(memory, region.size, offset, size.bytes(), value),
+                Contents::Device(ref mut device) => device.get_mut().poke(address_space, offset, size, value),
+            }
+        }
+    }
+    // boot overlays answer again
+    fn reset(&mut self) {
+        let mut overlaid = false;
+        for region in self.regions.iter().filter(|region| region.overlay.is_some()) {
+            region.enabled.set(true);
+            overlaid = true;
+        }
+        self.overlaid.set(overlaid);
+        self.cycles.set(0);
+        self.fault.set(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::{Device, MemoryMap, OverlayEnd, RomWrites, Unmapped};
+    use ram::Fork;
+    use cpu::{Core, ProcessingState, StopConditions};
+    use ram::{AddressBus, AddressSpace, BusFault, CPU_SPACE, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA, USER_PROGRAM};
+    use ram::watching::AccessSize;
+
+    #[test]
+    fn partially_decoded_ram_repeats() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        let ram = mem.add_ram(0x0, 0xfffff, 0x10000);
+        mem.write_long(SUPERVISOR_DATA, 0x1fffe, 0x12345678);
+        assert_eq!(0x12345678, mem.read_long(SUPERVISOR_DATA, 0xffffe));
+        assert_eq!(0x5678, mem.read_word(SUPERVISOR_DATA, 0x30000));
+        assert_eq!(Some(&[0x12, 0x34][..]), mem.memory(ram).map(|bytes| &bytes[0xfffe..]));
+        assert_eq!(0x1000, mem.allocated_pages());
+    }
+
+    #[test]
+    fn rom_writes_are_ignored_or_faulted() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        mem.add_rom(0x0, 0x3, vec![1, 2, 3, 4], RomWrites::Ignore);
+        mem.add_rom(0x10, 0x13, vec![5, 6, 7, 8], RomWrites::BusError);
+        mem.write_word(SUPERVISOR_DATA, 0x0, 0);
+        assert_eq!(None, mem.take_bus_error());
+        mem.write_byte(USER_DATA, 0x11, 0);
+        assert_eq!(Some(BusFault { address_space: USER_DATA, address: 0x11, write: true }), mem.take_bus_error());
+        assert_eq!(0x01020304, mem.read_long(SUPERVISOR_DATA, 0x0));
+        assert_eq!(0x0506, mem.read_word(SUPERVISOR_DATA, 0x10));
+    }
+
+    struct Registers(Rc<RefCell<Vec<(u32, AccessSize, u32)>>>);
+    impl Device for Registers {
+        fn read(&mut self, _: AddressSpace, offset: u32, _: AccessSize) -> u32 {
+            offset
+        }
+        fn write(&mut self, _: AddressSpace, offset: u32, size: AccessSize, value: u32) {
+            self.0.borrow_mut().push((offset, size, value));
+        }
+    }
+
+    #[test]
+    fn devices_are_accessed_whole_and_mapped_last_answers() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        let writes = Rc::new(RefCell::new(Vec::new()));
+        mem.add_ram(0x0, 0xffffff, 0x100);
+        mem.add_device(0xff0000, 0xffffff, 0x10, Box::new(Registers(writes.clone())));
+        mem.write_word(SUPERVISOR_DATA, 0xff0012, 0xabcd);
+        assert_eq!(vec![(2, AccessSize::Word, 0xabcd)], *writes.borrow());
+        assert_eq!(0xe, mem.read_long(SUPERVISOR_DATA, 0xff003e));
+        mem.write_byte(SUPERVISOR_DATA, 0xfe0000, 0x42);
+        assert_eq!(0x42, mem.read_byte(SUPERVISOR_DATA, 0));
+    }
+
+    #[test]
+    fn unmapped_addresses_read_open_bus() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0x4e));
+        mem.write_long(SUPERVISOR_DATA, 0x1000, 0);
+        assert_eq!(0x4e4e, mem.read_word(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(None, mem.take_bus_error());
+    }
+
+    #[test]
+    fn unmapped_addresses_raise_bus_errors() {
+        // MOVE.W $100000, D0, with the bus error vector at $8
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        let ram = mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.memory_mut(ram).unwrap()[0x8..0xc].copy_from_slice(&[0x00, 0x00, 0x20, 0x00]);
+        mem.memory_mut(ram).unwrap()[0x1000..0x1006].copy_from_slice(&[0x30, 0x39, 0x00, 0x10, 0x00, 0x00]);
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x8000;
+        core.run_until(&StopConditions { instructions: Some(1), ..Default::default() });
+        assert_eq!(0x2000, core.pc);
+        // the access address of the bus error stack frame
+        assert_eq!(0x100000, core.mem.read_long(SUPERVISOR_DATA, core.dar[15] + 2));
+    }
+
+    #[test]
+    fn instructions_stop_at_the_faulting_access() {
+        // MOVE.L $100000, $3000.W
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        let ram = mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.memory_mut(ram).unwrap()[0x8..0xc].copy_from_slice(&[0x00, 0x00, 0x20, 0x00]);
+        mem.memory_mut(ram).unwrap()[0x1000..0x1008].copy_from_slice(&[0x21, 0xf9, 0x00, 0x10, 0x00, 0x00, 0x30, 0x00]);
+        mem.write_long(SUPERVISOR_DATA, 0x3000, 0xdeadbeef);
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x8000;
+        core.run_until(&StopConditions { instructions: Some(1), ..Default::default() });
+        assert_eq!(0x2000, core.pc);
+        assert_eq!(0xdeadbeef, core.mem.read_long(SUPERVISOR_DATA, 0x3000));
+    }
+
+    #[test]
+    fn faults_fetching_a_vector_raise_bus_errors() {
+        // TRAP #0, with its vector unmapped, but not that of bus errors
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        let vectors = mem.add_ram(0x0, 0xf, 0x10);
+        mem.memory_mut(vectors).unwrap()[0x8..0xc].copy_from_slice(&[0x00, 0x00, 0x20, 0x00]);
+        let ram = mem.add_ram(0x1000, 0xffff, 0x10000);
+        mem.memory_mut(ram).unwrap()[0x0..0x2].copy_from_slice(&[0x4e, 0x40]);
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x8000;
+        core.run_until(&StopConditions { instructions: Some(1), ..Default::default() });
+        assert_eq!(0x2000, core.pc);
+        assert_eq!(0x80, core.mem.read_long(SUPERVISOR_DATA, core.dar[15] + 2));
+        assert_ne!(ProcessingState::Halted, core.processing_state);
+    }
+
+    #[test]
+    fn double_bus_faults_halt() {
+        // MOVE.W $100000, D0, with the stack pointer unmapped too
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        let ram = mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.memory_mut(ram).unwrap()[0x8..0xc].copy_from_slice(&[0x00, 0x00, 0x20, 0x00]);
+        mem.memory_mut(ram).unwrap()[0x1000..0x1006].copy_from_slice(&[0x30, 0x39, 0x00, 0x10, 0x00, 0x00]);
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x200000;
+        core.run_until(&StopConditions { instructions: Some(1), ..Default::default() });
+        assert_eq!(ProcessingState::Halted, core.processing_state);
+        // as does a reset without vectors to fetch
+        let mut core = Core::new_with_bus(0x1000, MemoryMap::new(Unmapped::BusError));
+        core.reset();
+        assert_eq!(ProcessingState::Halted, core.processing_state);
+    }
+
+    #[test]
+    fn program_and_data_spaces_can_be_separate() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        let program = mem.add_rom(0x0, 0xffff, vec![0x4e, 0x71], RomWrites::Ignore);
+        mem.set_spaces(program, &[USER_PROGRAM, SUPERVISOR_PROGRAM]);
+        let data = mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.set_spaces(data, &[USER_DATA, SUPERVISOR_DATA]);
+        mem.write_word(SUPERVISOR_DATA, 0x0, 0x1234);
+        assert_eq!(0x4e71, mem.read_word(USER_PROGRAM, 0x0));
+        assert_eq!(0x1234, mem.read_word(USER_DATA, 0x0));
+    }
+
+    #[test]
+    fn protected_regions_raise_bus_errors() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        let system = mem.add_ram(0x0, 0xfff, 0x1000);
+        mem.set_supervisor_only(system, true);
+        let code = mem.add_ram(0x1000, 0x1fff, 0x1000);
+        mem.set_write_protected(code, true);
+        mem.write_word(SUPERVISOR_DATA, 0x100, 0x1234);
+        assert_eq!(0x1234, mem.read_word(SUPERVISOR_PROGRAM, 0x100));
+        assert_eq!(None, mem.take_bus_error());
+        mem.read_word(USER_DATA, 0x100);
+        assert_eq!(Some(BusFault { address_space: USER_DATA, address: 0x100, write: false }), mem.take_bus_error());
+        mem.write_long(SUPERVISOR_DATA, 0x1000, 0);
+        assert_eq!(Some(BusFault { address_space: SUPERVISOR_DATA, address: 0x1000, write: true }), mem.take_bus_error());
+        assert_eq!(0, mem.read_long(USER_PROGRAM, 0x1000));
+    }
+
+    // a status register that reading clears
+    struct Uart(u8);
+    impl Device for Uart {
+        fn read(&mut self, _: AddressSpace, _: u32, _: AccessSize) -> u32 {
+            let status = self.0;
+            self.0 = 0;
+            status as u32
+        }
+        fn write(&mut self, _: AddressSpace, _: u32, _: AccessSize, _: u32) {
+        }
+        fn peek(&self, _: AddressSpace, _: u32, _: AccessSize) -> u32 {
+            self.0 as u32
+        }
+        fn poke(&mut self, _: AddressSpace, _: u32, _: AccessSize, value: u32) {
+            self.0 = value as u8;
+        }
+        fn fork(&self) -> Option<Box<dyn Device>> {
+            Some(Box::new(Uart(self.0)))
+        }
+    }
+
+    #[test]
+    fn peeks_and_pokes_have_no_side_effects() {
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        let rom = mem.add_rom(0x0, 0xfff, vec![0; 0x1000], RomWrites::BusError);
+        mem.set_supervisor_only(rom, true);
+        mem.add_device(0x10000, 0x10000, 1, Box::new(Uart(0x80)));
+        let overlay = mem.add_rom(0x0, 0xf, vec![0xff; 0x10], RomWrites::Ignore);
+        mem.set_boot_overlay(overlay, OverlayEnd::BusCycles(1));
+        assert_eq!(0x80, mem.peek(SUPERVISOR_DATA, 0x10000, AccessSize::Byte));
+        mem.poke(USER_DATA, 0x100, AccessSize::Long, 0x4e714e75);
+        assert_eq!(0xffff, mem.peek(USER_DATA, 0x200000, AccessSize::Word));
+        assert_eq!(None, mem.take_bus_error());
+        assert!(mem.is_enabled(overlay));
+        assert_eq!(0x4e714e75, mem.peek(USER_PROGRAM, 0x100, AccessSize::Long));
+        assert_eq!(0x80, mem.read_byte(SUPERVISOR_DATA, 0x10000));
+        assert_eq!(0, mem.read_byte(SUPERVISOR_DATA, 0x10000));
+        mem.poke(SUPERVISOR_DATA, 0x10000, AccessSize::Byte, 0x40);
+        assert_eq!(0x40, mem.read_byte(SUPERVISOR_DATA, 0x10000));
+    }
+
+    #[test]
+    fn forks_copy_memory_and_devices() {
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.add_device(0x10000, 0x10000, 1, Box::new(Uart(0x80)));
+        mem.write_long(SUPERVISOR_DATA, 0x100, 0x12345678);
+        let mut fork = mem.fork();
+        fork.write_long(SUPERVISOR_DATA, 0x100, 0);
+        assert_eq!(0x80, fork.read_byte(SUPERVISOR_DATA, 0x10000));
+        assert_eq!(0, fork.read_byte(SUPERVISOR_DATA, 0x10000));
+        assert_eq!(0x12345678, mem.read_long(SUPERVISOR_DATA, 0x100));
+        assert_eq!(0x80, mem.read_byte(SUPERVISOR_DATA, 0x10000));
+    }
+
+    #[test]
+    #[should_panic(expected = "the device at 010000 can't be forked")]
+    fn maps_with_devices_that_cant_be_copied_cant_be_forked() {
+        let mut mem = MemoryMap::new(Unmapped::BusError);
+        mem.add_device(0x10000, 0x10000, 1, Box::new(Registers(Rc::new(RefCell::new(Vec::new())))));
+        mem.fork();
+    }
+
+    struct VectoringController;
+    impl Device for VectoringController {
+        // the vector is 0x40 plus the level, read at odd offsets 1 to 15
+        fn read(&mut self, address_space: AddressSpace, offset: u32, _: AccessSize) -> u32 {
+            assert_eq!(CPU_SPACE, address_space);
+            0x40 + (offset >> 1)
+        }
+        fn write(&mut self, _: AddressSpace, _: u32, _: AccessSize, _: u32) {
+        }
+    }
+
+    #[test]
+    fn devices_in_cpu_space_answer_interrupt_acknowledge_cycles() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        let ram = mem.add_ram(0x0, 0xffffff, 0x10000);
+        mem.memory_mut(ram).unwrap()[0x114..0x118].copy_from_slice(&[0x00, 0x00, 0x20, 0x00]);
+        let controller = mem.add_device(0xfffff0, 0xffffff, 0x10, Box::new(VectoringController));
+        mem.set_spaces(controller, &[CPU_SPACE]);
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[15] = 0x8000;
+        core.sr_to_flags(0x2000);
+        core.int_ctrl.request_interrupt(5);
+        core.execute1();
+        // vector 0x45, rather than autovector 0x1d
+        assert_eq!(0x2000, core.pc);
+    }
+
+    // SSP $8000, PC $f00008, then MOVE.L #$12345678, 0.W
+    fn boot_rom() -> Vec<u8> {
+        vec![0x00, 0x00, 0x80, 0x00, 0x00, 0xf0, 0x00, 0x08, 0x21, 0xfc, 0x12, 0x34, 0x56, 0x78, 0x00, 0x00]
+    }
+
+    fn booting(end: OverlayEnd) -> (MemoryMap, usize) {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.add_rom(0xf00000, 0xf0ffff, boot_rom(), RomWrites::Ignore);
+        let overlay = mem.add_rom(0x0, 0xffff, boot_rom(), RomWrites::Ignore);
+        mem.set_boot_overlay(overlay, end);
+        (mem, overlay)
+    }
+
+    #[test]
+    fn boot_overlays_end_after_the_reset_vectors_and_return_on_reset() {
+        let (mem, overlay) = booting(OverlayEnd::BusCycles(4));
+        let mut core = Core::power_on(mem);
+        assert_eq!((0x8000, 0xf00008), (core.dar[15], core.pc));
+        assert!(!core.mem.is_enabled(overlay));
+        core.execute1();
+        assert_eq!(0x12345678, core.mem.read_long(SUPERVISOR_DATA, 0x0));
+        core.reset();
+        assert_eq!((0x8000, 0xf00008), (core.dar[15], core.pc));
+    }
+
+    #[test]
+    fn boot_overlays_end_on_accesses_or_latch_writes() {
+        let (mem, overlay) = booting(OverlayEnd::AccessTo(0xf00000, 0xffffff));
+        let mut core = Core::power_on(mem);
+        assert!(core.mem.is_enabled(overlay));
+        core.execute1();
+        assert_eq!(0x12345678, core.mem.read_long(SUPERVISOR_DATA, 0x0));
+
+        let (mut mem, overlay) = booting(OverlayEnd::WriteTo(0xbfe001, 0xbfe001));
+        mem.read_byte(SUPERVISOR_DATA, 0xbfe001);
+        mem.write_long(SUPERVISOR_DATA, 0x0, 0);
+        assert_eq!(0x8000, mem.read_long(SUPERVISOR_DATA, 0x0));
+        mem.write_byte(SUPERVISOR_DATA, 0xbfe001, 0);
+        assert!(!mem.is_enabled(overlay));
+        assert_eq!(0, mem.read_long(SUPERVISOR_DATA, 0x0));
+    }
+
+    #[test]
+    fn disabled_regions_answer_nothing() {
+        let mut mem = MemoryMap::new(Unmapped::OpenBus(0xff));
+        let ram = mem.add_ram(0x0, 0xffff, 0x10000);
+        mem.set_enabled(ram, false);
+        mem.write_word(SUPERVISOR_DATA, 0x100, 0x1234);
+        assert_eq!(0xffff, mem.read_word(SUPERVISOR_DATA, 0x100));
+        mem.set_enabled(ram, true);
+        assert_eq!(0, mem.read_word(SUPERVISOR_DATA, 0x100));
+    }
+}
@@ -1,5 +1,13 @@
+pub mod cowmem;
 pub mod loggingmem;
-mod pagedmem;
+pub mod memorymap;
+pub mod opslog;
+pub mod flatmem;
+pub mod pagedmem;
+pub mod recording;
+pub mod tracking;
+pub mod watching;
+use self::watching::{AccessSize, WatchHit};
 
 // The m68k had a 24 bit external address bus with
 // (2^24 bytes = ) 16 MB addressable space
@@ -15,6 +23,20 @@ impl AddressSpace {
             USER_PROGRAM => 2,
             SUPERVISOR_DATA => 5,
             SUPERVISOR_PROGRAM => 6,
+            AddressSpace(_, Segment::Cpu) => 7,
+        }
+    }
+    pub fn is_supervisor(&self) -> bool {
+        self.0 == Mode::Supervisor
+    }
+    pub fn from_fc(fc: u32) -> Option<AddressSpace> {
+        match fc {
+            1 => Some(USER_DATA),
+            2 => Some(USER_PROGRAM),
+            5 => Some(SUPERVISOR_DATA),
+            6 => Some(SUPERVISOR_PROGRAM),
+            7 => Some(CPU_SPACE),
+            _ => None,
         }
     }
 }
@@ -29,7 +51,7 @@ impl fmt::Debug for AddressSpace {
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 enum Segment {
-    Program, Data
+    Program, Data, Cpu
 }
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 enum Mode {
@@ -40,6 +62,30 @@ pub const SUPERVISOR_PROGRAM: AddressSpace = AddressSpace(Mode::Supervisor, Segm
 pub const SUPERVISOR_DATA: AddressSpace = AddressSpace(Mode::Supervisor, Segment::Data);
 pub const USER_PROGRAM: AddressSpace = AddressSpace(Mode::User, Segment::Program);
 pub const USER_DATA: AddressSpace = AddressSpace(Mode::User, Segment::Data);
+// for interrupt acknowledge cycles, and on later models breakpoint
+// acknowledge and coprocessor cycles
+pub const CPU_SPACE: AddressSpace = AddressSpace(Mode::Supervisor, Segment::Cpu);
+
+// Where interrupt acknowledge cycles read the vector of an interrupt level
+// from, in CPU space
+pub fn iack_address(level: u8) -> u32 {
+    0xfffff1 | (level as u32 & 7) << 1
+}
+
+// Buses that can be copied cheaply, into copies that then go their own
+// way, see cowmem::CowMem
+pub trait Fork {
+    fn fork(&self) -> Self;
+}
+
+// A bus cycle nothing answered, or that was refused, for the core to
+// raise a bus error on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BusFault {
+    pub address_space: AddressSpace,
+    pub address: u32,
+    pub write: bool,
+}
 
 pub trait AddressBus {
     fn copy_from(&mut self, other: &Self);
@@ -49,5 +95,88 @@ pub trait AddressBus {
     fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32);
     fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32);
     fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32);
+
+    // memory footprint, used by hosts to enforce per-machine quotas
+    fn allocated_pages(&self) -> usize {
+        0
+    }
+    fn log_len(&self) -> usize {
+        0
+    }
+    // the watchpoint triggered since the last call, on buses that have
+    // them, see watching::Watcher
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        None
+    }
+    // An interrupt acknowledge cycle, reading the vector for the level
+    // from CPU space, at iack_address. Buses where nothing answers leave
+    // the vector to the interrupt controller of the core, as if VPA was
+    // asserted for autovectoring; fallback is the vector it gives, for
+    // buses logging the cycle.
+    fn acknowledge_interrupt(&mut self, _level: u8, _fallback: u8) -> Option<u8> {
+        None
+    }
+    // the first bus fault since the last call, on buses that have them,
+    // see memorymap::MemoryMap
+    fn take_bus_error(&mut self) -> Option<BusFault> {
+        None
+    }
+    // Block transfers, for loaders, DMA and save states: the elements
+    // read or written in turn, as byte, word or long accesses, from the
+    // address up. Buses over contiguous memory copy them whole.
+    fn read_bytes(&self, address_space: AddressSpace, address: u32, buffer: &mut [u8]) {
+        for (offset, byte) in buffer.iter_mut().enumerate() {
+            *byte = self.read_byte(address_space, address.wrapping_add(offset as u32)) as u8;
+        }
+    }
+    fn write_bytes(&mut self, address_space: AddressSpace, address: u32, bytes: &[u8]) {
+        for (offset, &byte) in bytes.iter().enumerate() {
+            self.write_byte(address_space, address.wrapping_add(offset as u32), byte as u32);
+        }
+    }
+    fn read_words(&self, address_space: AddressSpace, address: u32, buffer: &mut [u16]) {
+        for (offset, word) in buffer.iter_mut().enumerate() {
+            *word = self.read_word(address_space, address.wrapping_add(2 * offset as u32)) as u16;
+        }
+    }
+    fn write_words(&mut self, address_space: AddressSpace, address: u32, words: &[u16]) {
+        for (offset, &word) in words.iter().enumerate() {
+            self.write_word(address_space, address.wrapping_add(2 * offset as u32), word as u32);
+        }
+    }
+    fn read_longs(&self, address_space: AddressSpace, address: u32, buffer: &mut [u32]) {
+        for (offset, long) in buffer.iter_mut().enumerate() {
+            *long = self.read_long(address_space, address.wrapping_add(4 * offset as u32));
+        }
+    }
+    fn write_longs(&mut self, address_space: AddressSpace, address: u32, longs: &[u32]) {
+        for (offset, &long) in longs.iter().enumerate() {
+            self.write_long(address_space, address.wrapping_add(4 * offset as u32), long);
+        }
+    }
+    // Debug access, for tools like debuggers: reading and writing as
+    // the core would, but without logging, watchpoints, bus errors or
+    // side effects, devices answering from their debug view. By default
+    // the bus is taken to be plain memory, read and written as usual, so
+    // any bus whose reads or writes have side effects (logging, watching,
+    // devices and the like) must override both.
+    fn peek(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        match size {
+            AccessSize::Byte => self.read_byte(address_space, address),
+            AccessSize::Word => self.read_word(address_space, address),
+            AccessSize::Long => self.read_long(address_space, address),
+        }
+    }
+    fn poke(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        match size {
+            AccessSize::Byte => self.write_byte(address_space, address, value),
+            AccessSize::Word => self.write_word(address_space, address, value),
+            AccessSize::Long => self.write_long(address_space, address, value),
+        }
+    }
+    // The system is reset, for buses whose decoding changes after it,
+    // like the boot overlays of memorymap::MemoryMap
+    fn reset(&mut self) {
+    }
 }
 
@@ -0,0 +1,295 @@
+// Loggers for LoggingMem, for runs too long to keep every access the way
+// OpsLogger does. Filtered passes on only the operations matching its
+// watchpoints, to any other logger, RingLogger keeps just the latest, and
+// OpsStream writes them out as they come, as text or compactly in
+// binary, which OpsStreamReader reads back.
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use super::AddressSpace;
+use super::loggingmem::{Operation, OpsLogging};
+use super::watching::{AccessSize, WatchKind, Watchpoint};
+
+// Logs the operations any of the filters match to the inner logger, the
+// filters matching accesses as watchpoints do
+#[derive(Clone)]
+pub struct Filtered<T: OpsLogging> {
+    pub inner: T,
+    pub filters: Vec<Watchpoint>,
+}
+
+impl<T: OpsLogging> Filtered<T> {
+    pub fn new(inner: T, filters: Vec<Watchpoint>) -> Filtered<T> {
+        Filtered { inner, filters }
+    }
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: OpsLogging> OpsLogging for Filtered<T> {
+    fn log(&self, op: Operation) {
+        if let Some((kind, size, address_space, address, _)) = op.parts() {
+            if self.filters.iter().any(|filter| filter.matches(address_space, address, size, kind)) {
+                self.inner.log(op);
+            }
+        }
+    }
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+// Keeps the latest operations, up to the capacity, counting those that
+// made room for them
+#[derive(Clone)]
+pub struct RingLogger {
+    ops: RefCell<VecDeque<Operation>>,
+    capacity: usize,
+    dropped: Cell<u64>,
+}
+
+impl RingLogger {
+    pub fn new(capacity: usize) -> RingLogger {
+        RingLogger { ops: RefCell::new(VecDeque::with_capacity(capacity)), capacity, dropped: Cell::new(0) }
+    }
+    // oldest first
+    pub fn ops(&self) -> Vec<Operation> {
+        self.ops.borrow().iter().cloned().collect()
+    }
+    pub fn dropped(&self) -> u64 {
+        self.dropped.get()
+    }
+    pub fn clear(&self) {
+        self.ops.borrow_mut().clear();
+        self.dropped.set(0);
+    }
+}
+
+impl OpsLogging for RingLogger {
+    fn log(&self, op: Operation) {
+        if self.capacity == 0 {
+            self.dropped.set(self.dropped.get() + 1);
+            return;
+        }
+        let mut ops = self.ops.borrow_mut();
+        if ops.len() == self.capacity {
+            ops.pop_front();
+            self.dropped.set(self.dropped.get() + 1);
+        }
+        ops.push_back(op);
+    }
+    fn len(&self) -> usize {
+        self.ops.borrow().len()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OpsFormat {
+    // a line per operation, as Operation prints
+    Text,
+    // After a magic header, each operation as write_access encodes it
+    Binary,
+}
+
+const MAGIC: &[u8] = b"r68kops1";
+
+// The binary encoding of an access, shared by operation logs and
+// instruction traces: a byte of kind, in the low nibble, 0-2 for byte,
+// word and long reads, and 3-5 for writes, and function code, in the
+// high nibble, then a 24 bit address and the value in as many bytes as
+// the access, all big-endian. Operation::None isn't written, which is
+// what the result tells.
+pub fn write_access(out: &mut Vec<u8>, op: Operation) -> bool {
+    let (kind, size, space, address, value) = match op.parts() {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let code = match size {
+        AccessSize::Byte => 0,
+        AccessSize::Word => 1,
+        AccessSize::Long => 2,
+    } + if kind == WatchKind::Write { 3 } else { 0 };
+    out.push(code | (space.fc() as u8) << 4);
+    out.extend_from_slice(&address.to_be_bytes()[1..]);
+    out.extend_from_slice(&value.to_be_bytes()[4 - size.bytes() as usize..]);
+    true
+}
+
+// Reads an access back, or None if the reader is at its end
+pub fn read_access<R: Read>(reader: &mut R) -> io::Result<Option<Operation>> {
+    let mut code = [0u8; 1];
+    match reader.read_exact(&mut code) {
+        Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let space = AddressSpace::from_fc((code[0] >> 4) as u32).ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "bad function code"))?;
+    let (kind, size) = match code[0] & 0xf {
+        0 => (WatchKind::Read, AccessSize::Byte),
+        1 => (WatchKind::Read, AccessSize::Word),
+        2 => (WatchKind::Read, AccessSize::Long),
+        3 => (WatchKind::Write, AccessSize::Byte),
+        4 => (WatchKind::Write, AccessSize::Word),
+        5 => (WatchKind::Write, AccessSize::Long),
+        _ => return Err(io::Error::new(ErrorKind::InvalidData, "bad access kind")),
+    };
+    let mut address = [0u8; 4];
+    reader.read_exact(&mut address[1..])?;
+    let mut value = [0u8; 4];
+    reader.read_exact(&mut value[4 - size.bytes() as usize..])?;
+    Ok(Some(Operation::from_parts(kind, size, space, u32::from_be_bytes(address), u32::from_be_bytes(value))))
+}
+
+// Writes operations as they are logged. Logging can't fail, so the first
+// error is kept, and writing stops, until finish returns it. Writers are
+// best buffered.
+pub struct OpsStream<W: Write> {
+    writer: RefCell<W>,
+    format: OpsFormat,
+    started: Cell<bool>,
+    written: Cell<usize>,
+    error: RefCell<Option<io::Error>>,
+}
+
+impl<W: Write> OpsStream<W> {
+    pub fn new(writer: W, format: OpsFormat) -> OpsStream<W> {
+        OpsStream { writer: RefCell::new(writer), format, started: Cell::new(false), written: Cell::new(0), error: RefCell::new(None) }
+    }
+    // the writer, once flushed, or the first error writing to it
+    pub fn finish(self) -> io::Result<W> {
+        if let Some(error) = self.error.into_inner() {
+            return Err(error);
+        }
+        let mut writer = self.writer.into_inner();
+        writer.flush()?;
+        Ok(writer)
+    }
+    // whether the operation was written, as binary logs skip None
+    fn write(&self, op: Operation) -> io::Result<bool> {
+        let mut writer = self.writer.borrow_mut();
+        match self.format {
+            OpsFormat::Text => writeln!(writer, "{:?}", op).map(|_| true),
+            OpsFormat::Binary => {
+                let mut out = Vec::with_capacity(16);
+                if !self.started.get() {
+                    out.extend_from_slice(MAGIC);
+                }
+                if !write_access(&mut out, op) {
+                    return Ok(false);
+                }
+                self.started.set(true);
+                writer.write_all(&out).map(|_| true)
+            },
+        }
+    }
+}
+
+impl<W: Write> OpsLogging for OpsStream<W> {
+    fn log(&self, op: Operation) {
+        if self.error.borrow().is_some() {
+            return;
+        }
+        match self.write(op) {
+            Ok(true) => self.written.set(self.written.get() + 1),
+            Ok(false) => (),
+            Err(error) => *self.error.borrow_mut() = Some(error),
+        }
+    }
+    // operations written so far
+    fn len(&self) -> usize {
+        self.written.get()
+    }
+}
+
+// Reads back what an OpsStream wrote in binary, an operation at a time
+pub struct OpsStreamReader<R: Read> {
+    reader: R,
+    started: bool,
+}
+
+impl<R: Read> OpsStreamReader<R> {
+    pub fn new(reader: R) -> OpsStreamReader<R> {
+        OpsStreamReader { reader, started: false }
+    }
+    // None at the end of the log
+    pub fn read_op(&mut self) -> io::Result<Option<Operation>> {
+        if !self.started {
+            let mut magic = [0u8; 8];
+            match self.reader.read_exact(&mut magic) {
+                // an empty log, as nothing was logged
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+                result => result?,
+            }
+            if magic != MAGIC {
+                return Err(io::Error::new(ErrorKind::InvalidData, "not an operations log"));
+            }
+            self.started = true;
+        }
+        read_access(&mut self.reader)
+    }
+}
+
+impl<R: Read> Iterator for OpsStreamReader<R> {
+    type Item = io::Result<Operation>;
+    fn next(&mut self) -> Option<io::Result<Operation>> {
+        self.read_op().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filtered, OpsFormat, OpsStream, OpsStreamReader, RingLogger};
+    use ram::{AddressBus, SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA};
+    use ram::loggingmem::{LoggingMem, Operation, OpsLogger, OpsLogging};
+    use ram::watching::{WatchKind, Watchpoint};
+
+    #[test]
+    fn filters_pick_operations_by_range_space_and_kind() {
+        let writes = Watchpoint::new(WatchKind::Write, 0x1000, 0x1fff);
+        let user_reads = Watchpoint { address_space: Some(USER_DATA), ..Watchpoint::new(WatchKind::Read, 0, 0xffffff) };
+        let mut mem = LoggingMem::new(0, Filtered::new(OpsLogger::new(), vec![writes, user_reads]));
+        mem.write_long(SUPERVISOR_DATA, 0xffe, 0x12345678);
+        mem.write_word(SUPERVISOR_DATA, 0x2000, 0x1234);
+        mem.read_word(SUPERVISOR_DATA, 0x1000);
+        mem.read_byte(USER_DATA, 0x2000);
+        assert_eq!(vec![Operation::WriteLong(SUPERVISOR_DATA, 0xffe, 0x12345678), Operation::ReadByte(USER_DATA, 0x2000, 0x12)], mem.logger.inner.ops());
+        assert_eq!(2, mem.log_len());
+    }
+
+    #[test]
+    fn rings_keep_the_latest_operations() {
+        let mut mem = LoggingMem::new(0, RingLogger::new(2));
+        for address in 0..5 {
+            mem.write_byte(SUPERVISOR_DATA, address, address);
+        }
+        assert_eq!(vec![Operation::WriteByte(SUPERVISOR_DATA, 3, 3), Operation::WriteByte(SUPERVISOR_DATA, 4, 4)], mem.logger.ops());
+        assert_eq!(3, mem.logger.dropped());
+    }
+
+    #[test]
+    fn binary_streams_read_back() {
+        let ops = vec![Operation::ReadByte(USER_DATA, 0x123456, 0x78), Operation::ReadLong(SUPERVISOR_PROGRAM, 0x1000, 0x4e714e75),
+            Operation::WriteWord(SUPERVISOR_DATA, 0xfffffe, 0xabcd)];
+        let stream = OpsStream::new(Vec::new(), OpsFormat::Binary);
+        stream.log(Operation::None);
+        for &op in &ops {
+            stream.log(op);
+        }
+        // None has no binary encoding, and isn't counted
+        stream.log(Operation::None);
+        assert_eq!(3, stream.len());
+        let log = stream.finish().unwrap();
+        assert_eq!(8 + 5 + 8 + 6, log.len());
+        let read: Vec<Operation> = OpsStreamReader::new(&log[..]).map(|op| op.unwrap()).collect();
+        assert_eq!(ops, read);
+        assert_eq!(0, OpsStreamReader::new(&[][..]).count());
+    }
+
+    #[test]
+    fn text_streams_have_a_line_per_operation() {
+        let stream = OpsStream::new(Vec::new(), OpsFormat::Text);
+        stream.log(Operation::WriteByte(SUPERVISOR_DATA, 0x80, 0x12));
+        let text = String::from_utf8(stream.finish().unwrap()).unwrap();
+        assert_eq!("WriteByte[Supervisor/Data] @000080 <= 12\n", text);
+    }
+}
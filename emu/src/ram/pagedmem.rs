@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use super::{AddressSpace, AddressBus, ADDRBUS_MASK};
+use super::{AddressSpace, AddressBus, Fork, ADDRBUS_MASK};
 
 const PAGE_SIZE: u32 = 16; // 16 bytes page size
 const ADDR_MASK: u32 = PAGE_SIZE - 1; // all ones
@@ -13,8 +13,7 @@ pub struct PagedMem {
 }
 
 impl PagedMem {
-    #[allow(dead_code)]
-    fn allocated_pages(&self) -> usize {
+    pub fn allocated_pages(&self) -> usize {
         self.pages.len()
     }
     fn new_page_is_needed(&self, address: u32, value_to_write: u8) -> bool {
@@ -99,13 +98,31 @@ impl<'a> Iterator for DiffIter<'a> {
     }
 }
 
+impl Fork for PagedMem {
+    // copies the pages written, untouched memory taking no copying
+    fn fork(&self) -> PagedMem {
+        PagedMem { pages: self.pages.clone(), initializer: self.initializer }
+    }
+}
+
 impl AddressBus for PagedMem {
     fn copy_from(&mut self, other: &Self) {
+        // pages are the same over the same initializer
+        if self.initializer == other.initializer {
+            for (&pageno, page) in &other.pages {
+                self.pages.insert(pageno, page.clone());
+            }
+            return;
+        }
         for (addr, byte) in other.diffs() {
             self.write_u8(addr, byte as u32);
         }
     }
 
+    fn allocated_pages(&self) -> usize {
+        self.pages.len()
+    }
+
     fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
         self.read_u8(address)
     }
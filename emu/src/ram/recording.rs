@@ -0,0 +1,138 @@
+use std::cell::RefCell;
+use std::mem;
+use super::{AddressSpace, AddressBus, Fork, ADDRBUS_MASK, BusFault, CPU_SPACE, iack_address};
+use super::loggingmem::Operation;
+use super::watching::{AccessSize, WatchHit};
+
+// Wraps any bus, and records every access made through it until taken
+// with take_accesses, as the LoggingMem used for testing does, but for
+// any bus. Values written are masked to their size.
+pub struct Recorder<A: AddressBus> {
+    pub inner: A,
+    accesses: RefCell<Vec<Operation>>,
+}
+
+impl<A: AddressBus> Recorder<A> {
+    pub fn new(inner: A) -> Recorder<A> {
+        Recorder { inner, accesses: RefCell::new(Vec::new()) }
+    }
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+    pub fn take_accesses(&mut self) -> Vec<Operation> {
+        mem::take(self.accesses.get_mut())
+    }
+    fn record(&self, operation: Operation) {
+        self.accesses.borrow_mut().push(operation);
+    }
+}
+
+impl<A: AddressBus + Fork> Fork for Recorder<A> {
+    // with the accesses not yet taken
+    fn fork(&self) -> Recorder<A> {
+        Recorder { inner: self.inner.fork(), accesses: self.accesses.clone() }
+    }
+}
+
+impl<A: AddressBus> AddressBus for Recorder<A> {
+    fn copy_from(&mut self, other: &Self) {
+        self.inner.copy_from(&other.inner);
+    }
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        let value = self.inner.read_byte(address_space, address);
+        self.record(Operation::ReadByte(address_space, address & ADDRBUS_MASK, value as u8));
+        value
+    }
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        let value = self.inner.read_word(address_space, address);
+        self.record(Operation::ReadWord(address_space, address & ADDRBUS_MASK, value as u16));
+        value
+    }
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        let value = self.inner.read_long(address_space, address);
+        self.record(Operation::ReadLong(address_space, address & ADDRBUS_MASK, value));
+        value
+    }
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.record(Operation::WriteByte(address_space, address & ADDRBUS_MASK, value & 0xff));
+        self.inner.write_byte(address_space, address, value)
+    }
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.record(Operation::WriteWord(address_space, address & ADDRBUS_MASK, value & 0xffff));
+        self.inner.write_word(address_space, address, value)
+    }
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.record(Operation::WriteLong(address_space, address & ADDRBUS_MASK, value));
+        self.inner.write_long(address_space, address, value)
+    }
+    fn allocated_pages(&self) -> usize {
+        self.inner.allocated_pages()
+    }
+    fn log_len(&self) -> usize {
+        self.inner.log_len()
+    }
+    fn acknowledge_interrupt(&mut self, level: u8, fallback: u8) -> Option<u8> {
+        let vector = self.inner.acknowledge_interrupt(level, fallback);
+        self.record(Operation::ReadByte(CPU_SPACE, iack_address(level), vector.unwrap_or(fallback)));
+        vector
+    }
+    // not recorded
+    fn peek(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        self.inner.peek(address_space, address, size)
+    }
+    fn poke(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        self.inner.poke(address_space, address, size, value)
+    }
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.inner.take_watch_hit()
+    }
+    fn take_bus_error(&mut self) -> Option<BusFault> {
+        self.inner.take_bus_error()
+    }
+    fn reset(&mut self) {
+        self.inner.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Recorder;
+    use ram::AddressBus;
+    use ram::loggingmem::Operation;
+    use ram::pagedmem::PagedMem;
+    use ram::{CPU_SPACE, SUPERVISOR_DATA, USER_PROGRAM};
+    use cpu::Core;
+
+    #[test]
+    fn accesses_are_recorded_until_taken() {
+        let mut mem = Recorder::new(PagedMem::new(0));
+        mem.write_word(SUPERVISOR_DATA, 0x1001002, 0x12345);
+        assert_eq!(0x2345, mem.read_word(SUPERVISOR_DATA, 0x1002));
+        mem.read_byte(USER_PROGRAM, 0x1003);
+        assert_eq!(vec![
+            Operation::WriteWord(SUPERVISOR_DATA, 0x1002, 0x2345),
+            Operation::ReadWord(SUPERVISOR_DATA, 0x1002, 0x2345),
+            Operation::ReadByte(USER_PROGRAM, 0x1003, 0x45),
+        ], mem.take_accesses());
+        assert!(mem.take_accesses().is_empty());
+    }
+
+    #[test]
+    fn interrupt_acknowledge_cycles_are_recorded() {
+        let mut core = Core::new_with_bus(0x1000, Recorder::new(PagedMem::new(0)));
+        core.dar[15] = 0x8000;
+        core.sr_to_flags(0x2000);
+        core.int_ctrl.request_interrupt(4);
+        core.execute1();
+        let iack = Operation::ReadByte(CPU_SPACE, 0xfffff9, 0x1c);
+        assert_eq!(Some(&iack), core.mem.take_accesses().iter().find(|op| **op == iack));
+    }
+
+    #[test]
+    fn unanswered_acknowledge_cycles_record_the_vector_used() {
+        // as given by an interrupt controller not autovectoring
+        let mut mem = Recorder::new(PagedMem::new(0));
+        assert_eq!(None, mem.acknowledge_interrupt(4, 0x40));
+        assert_eq!(vec![Operation::ReadByte(CPU_SPACE, 0xfffff9, 0x40)], mem.take_accesses());
+    }
+}
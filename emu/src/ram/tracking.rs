@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use super::{AddressSpace, AddressBus, Fork, ADDRBUS_MASK, BusFault};
+use super::watching::{AccessSize, WatchHit};
+
+// Code caches watch memory in pages of this size
+pub const CODE_PAGE_SHIFT: u32 = 12;
+
+pub fn code_page(address: u32) -> u32 {
+    (address & ADDRBUS_MASK) >> CODE_PAGE_SHIFT
+}
+
+// longest 68000 instruction is 10 bytes
+pub const MAX_INSTRUCTION_BYTES: u32 = 10;
+
+// Implemented by buses that can tell a cache of translated or decoded
+// code when the memory it was built from has been written to
+pub trait CodeWrites {
+    fn watch_page(&mut self, page: u32);
+    fn unwatch_page(&mut self, page: u32);
+    // true if any watched page has been written since the last call to
+    // take_written_pages
+    fn code_written(&self) -> bool;
+    fn take_written_pages(&mut self) -> Vec<u32>;
+}
+
+// Wraps any bus, and records writes to watched pages, on behalf of
+// a code cache. Reads and writes are otherwise passed on unchanged.
+pub struct WriteTracker<A: AddressBus> {
+    pub inner: A,
+    // a bit for each page, as every write is looked up
+    watched: Vec<u64>,
+    watching: usize,
+    written: Vec<u32>,
+}
+
+impl<A: AddressBus> WriteTracker<A> {
+    pub fn new(inner: A) -> WriteTracker<A> {
+        let pages = code_page(ADDRBUS_MASK) as usize + 1;
+        WriteTracker { inner, watched: vec![0; pages.div_ceil(64)], watching: 0, written: Vec::new() }
+    }
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+    fn is_watched(&self, page: u32) -> bool {
+        self.watched[page as usize / 64] & 1 << (page % 64) != 0
+    }
+    fn track(&mut self, address: u32, size: u32) {
+        if self.watching == 0 || size == 0 {
+            return;
+        }
+        // blocks may cover any number of pages, wrapping around
+        let all = code_page(ADDRBUS_MASK) + 1;
+        let offset = (address & ((1 << CODE_PAGE_SHIFT) - 1)) as u64;
+        let pages = ((offset + size as u64 - 1) >> CODE_PAGE_SHIFT) + 1;
+        let first = code_page(address);
+        for n in 0..pages.min(all as u64) as u32 {
+            let page = (first + n) % all;
+            if self.is_watched(page) {
+                self.unwatch_page(page);
+                self.written.push(page);
+            }
+        }
+    }
+}
+
+impl<A: AddressBus> CodeWrites for WriteTracker<A> {
+    fn watch_page(&mut self, page: u32) {
+        if !self.is_watched(page) {
+            self.watched[page as usize / 64] |= 1 << (page % 64);
+            self.watching += 1;
+        }
+    }
+    fn unwatch_page(&mut self, page: u32) {
+        if self.is_watched(page) {
+            self.watched[page as usize / 64] &= !(1 << (page % 64));
+            self.watching -= 1;
+        }
+    }
+    fn code_written(&self) -> bool {
+        !self.written.is_empty()
+    }
+    fn take_written_pages(&mut self) -> Vec<u32> {
+        // a page is only reported once, the cache has to watch it again
+        // after rebuilding code from it
+        self.written.drain(..).collect()
+    }
+}
+
+impl<A: AddressBus + Fork> Fork for WriteTracker<A> {
+    // watching the same pages, for a code cache attached to both
+    fn fork(&self) -> WriteTracker<A> {
+        WriteTracker { inner: self.inner.fork(), watched: self.watched.clone(), watching: self.watching, written: self.written.clone() }
+    }
+}
+
+impl<A: AddressBus> AddressBus for WriteTracker<A> {
+    fn copy_from(&mut self, other: &Self) {
+        self.inner.copy_from(&other.inner);
+    }
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.inner.read_byte(address_space, address)
+    }
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.inner.read_word(address_space, address)
+    }
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.inner.read_long(address_space, address)
+    }
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.track(address, 1);
+        self.inner.write_byte(address_space, address, value)
+    }
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.track(address, 2);
+        self.inner.write_word(address_space, address, value)
+    }
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.track(address, 4);
+        self.inner.write_long(address_space, address, value)
+    }
+    fn allocated_pages(&self) -> usize {
+        self.inner.allocated_pages()
+    }
+    fn log_len(&self) -> usize {
+        self.inner.log_len()
+    }
+    fn acknowledge_interrupt(&mut self, level: u8, fallback: u8) -> Option<u8> {
+        self.inner.acknowledge_interrupt(level, fallback)
+    }
+    fn read_bytes(&self, address_space: AddressSpace, address: u32, buffer: &mut [u8]) {
+        self.inner.read_bytes(address_space, address, buffer)
+    }
+    fn read_words(&self, address_space: AddressSpace, address: u32, buffer: &mut [u16]) {
+        self.inner.read_words(address_space, address, buffer)
+    }
+    fn read_longs(&self, address_space: AddressSpace, address: u32, buffer: &mut [u32]) {
+        self.inner.read_longs(address_space, address, buffer)
+    }
+    fn write_bytes(&mut self, address_space: AddressSpace, address: u32, bytes: &[u8]) {
+        self.track(address, bytes.len() as u32);
+        self.inner.write_bytes(address_space, address, bytes)
+    }
+    fn write_words(&mut self, address_space: AddressSpace, address: u32, words: &[u16]) {
+        self.track(address, 2 * words.len() as u32);
+        self.inner.write_words(address_space, address, words)
+    }
+    fn write_longs(&mut self, address_space: AddressSpace, address: u32, longs: &[u32]) {
+        self.track(address, 4 * longs.len() as u32);
+        self.inner.write_longs(address_space, address, longs)
+    }
+    fn peek(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        self.inner.peek(address_space, address, size)
+    }
+    // tracked all the same, as debuggers patch code
+    fn poke(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        self.track(address, size.bytes());
+        self.inner.poke(address_space, address, size, value)
+    }
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.inner.take_watch_hit()
+    }
+    fn take_bus_error(&mut self) -> Option<BusFault> {
+        self.inner.take_bus_error()
+    }
+    fn reset(&mut self) {
+        self.inner.reset()
+    }
+}
+
+// Watches the pages the instruction at PC may span, if not already
+// among those of the block being recorded. Done before running it,
+// so that an instruction writing to code already recorded in the
+// block is noticed.
+pub fn watch_instruction<A: CodeWrites>(mem: &mut A, pc: u32, pages: &mut Vec<u32>) {
+    for page in [code_page(pc), code_page(pc.wrapping_add(MAX_INSTRUCTION_BYTES - 1))].iter() {
+        if !pages.contains(page) {
+            mem.watch_page(*page);
+            pages.push(*page);
+        }
+    }
+}
+
+// Code caches key blocks by their start PC and whether they run in
+// supervisor mode, as the two fetch from different address spaces
+pub type BlockKey = (u32, bool);
+
+// The blocks of a code cache, along with the pages each was built from.
+// Blocks are dropped when the bus reports writes to those pages, so the
+// bus has to implement CodeWrites; wrapping it in a WriteTracker will do.
+pub struct CodeBlocks<B> {
+    blocks: HashMap<BlockKey, (B, Vec<u32>)>,
+    blocks_in_page: HashMap<u32, Vec<BlockKey>>,
+    built: usize,
+    invalidated: usize,
+}
+
+impl<B> CodeBlocks<B> {
+    pub fn new() -> CodeBlocks<B> {
+        CodeBlocks { blocks: HashMap::new(), blocks_in_page: HashMap::new(), built: 0, invalidated: 0 }
+    }
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+    // number of blocks ever inserted, and dropped due to code writes
+    pub fn built(&self) -> usize {
+        self.built
+    }
+    pub fn invalidated(&self) -> usize {
+        self.invalidated
+    }
+    pub fn get(&self, key: &BlockKey) -> Option<&B> {
+        self.blocks.get(key).map(|(block, _)| block)
+    }
+    pub fn insert(&mut self, key: BlockKey, block: B, pages: Vec<u32>) {
+        for page in &pages {
+            self.blocks_in_page.entry(*page).or_default().push(key);
+        }
+        self.blocks.insert(key, (block, pages));
+        self.built += 1;
+    }
+    // Stops watching the pages of a block that was recorded but not
+    // kept, unless other blocks were built from them
+    pub fn discard<A: CodeWrites>(&self, mem: &mut A, pages: Vec<u32>) {
+        for page in pages {
+            if !self.blocks_in_page.contains_key(&page) {
+                mem.unwatch_page(page);
+            }
+        }
+    }
+    // Watches the pages of all blocks on another bus, so that cores
+    // running the same code can share them
+    pub fn attach<A: CodeWrites>(&self, mem: &mut A) {
+        for page in self.blocks_in_page.keys() {
+            mem.watch_page(*page);
+        }
+    }
+    // Drops all blocks, like after loading new code without going
+    // through the bus
+    pub fn flush<A: CodeWrites>(&mut self, mem: &mut A) {
+        for page in self.blocks_in_page.keys() {
+            mem.unwatch_page(*page);
+        }
+        self.blocks.clear();
+        self.blocks_in_page.clear();
+    }
+    // Drops the blocks built from pages written since the last call
+    pub fn invalidate_written<A: CodeWrites>(&mut self, mem: &mut A) {
+        for page in mem.take_written_pages() {
+            let keys = match self.blocks_in_page.remove(&page) {
+                Some(keys) => keys,
+                None => continue
+            };
+            for key in keys {
+                let pages = match self.blocks.remove(&key) {
+                    Some((_, pages)) => pages,
+                    None => continue
+                };
+                self.invalidated += 1;
+                for other in pages.iter().filter(|&&p| p != page) {
+                    let now_empty = match self.blocks_in_page.get_mut(other) {
+                        Some(keys) => {
+                            keys.retain(|&k| k != key);
+                            keys.is_empty()
+                        }
+                        None => false
+                    };
+                    if now_empty {
+                        self.blocks_in_page.remove(other);
+                        mem.unwatch_page(*other);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<B> Default for CodeBlocks<B> {
+    fn default() -> CodeBlocks<B> {
+        CodeBlocks::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriteTracker, CodeWrites, CodeBlocks, code_page, watch_instruction};
+    use ram::AddressBus;
+    use ram::pagedmem::PagedMem;
+    use ram::SUPERVISOR_DATA;
+
+    #[test]
+    fn writes_to_unwatched_pages_are_not_reported() {
+        let mut mem = WriteTracker::new(PagedMem::new(0));
+        mem.write_long(SUPERVISOR_DATA, 0x1000, 0x12345678);
+        assert!(!mem.code_written());
+        assert_eq!(0x12345678, mem.read_long(SUPERVISOR_DATA, 0x1000));
+    }
+
+    #[test]
+    fn writes_to_watched_pages_are_reported_once() {
+        let mut mem = WriteTracker::new(PagedMem::new(0));
+        mem.watch_page(code_page(0x1000));
+        mem.write_byte(SUPERVISOR_DATA, 0x1ffe, 0x12);
+        mem.write_byte(SUPERVISOR_DATA, 0x1fff, 0x34);
+        assert!(mem.code_written());
+        assert_eq!(vec![1], mem.take_written_pages());
+        assert!(!mem.code_written());
+    }
+
+    #[test]
+    fn block_writes_report_every_page_covered() {
+        let mut mem = WriteTracker::new(PagedMem::new(0));
+        for page in &[0, 1, 2, 3, 0xfff] {
+            mem.watch_page(*page);
+        }
+        mem.write_longs(SUPERVISOR_DATA, 0xfffffc, &[0; 0x801]);
+        let mut pages = mem.take_written_pages();
+        pages.sort();
+        assert_eq!(vec![0, 1, 0xfff], pages);
+    }
+
+    #[test]
+    fn writes_straddling_pages_report_both() {
+        let mut mem = WriteTracker::new(PagedMem::new(0));
+        mem.watch_page(1);
+        mem.watch_page(2);
+        mem.write_long(SUPERVISOR_DATA, 0x1ffe, 0x12345678);
+        let mut pages = mem.take_written_pages();
+        pages.sort();
+        assert_eq!(vec![1, 2], pages);
+    }
+
+    #[test]
+    fn writes_drop_the_blocks_built_from_the_page() {
+        let mut mem = WriteTracker::new(PagedMem::new(0));
+        let mut blocks = CodeBlocks::new();
+        // one block straddling pages 1 and 2, another within page 2
+        let mut pages = Vec::new();
+        watch_instruction(&mut mem, 0x1ffc, &mut pages);
+        blocks.insert((0x1ffc, true), "straddling", pages);
+        let mut pages = Vec::new();
+        watch_instruction(&mut mem, 0x2100, &mut pages);
+        blocks.insert((0x2100, true), "within", pages);
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x4e71);
+        blocks.invalidate_written(&mut mem);
+        assert_eq!(None, blocks.get(&(0x1ffc, true)));
+        assert_eq!(Some(&"within"), blocks.get(&(0x2100, true)));
+        assert_eq!((2, 1), (blocks.built(), blocks.invalidated()));
+        // page 2 is still watched for the other block
+        mem.write_word(SUPERVISOR_DATA, 0x2000, 0x4e71);
+        blocks.invalidate_written(&mut mem);
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn discarded_blocks_leave_pages_of_others_watched() {
+        let mut mem = WriteTracker::new(PagedMem::new(0));
+        let mut blocks = CodeBlocks::new();
+        let mut pages = Vec::new();
+        watch_instruction(&mut mem, 0x1000, &mut pages);
+        blocks.insert((0x1000, false), (), pages);
+        let mut pages = Vec::new();
+        watch_instruction(&mut mem, 0x1ffc, &mut pages);
+        blocks.discard(&mut mem, pages);
+        mem.write_word(SUPERVISOR_DATA, 0x2000, 0x4e71);
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x4e71);
+        assert_eq!(vec![1], mem.take_written_pages());
+    }
+}
@@ -0,0 +1,318 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::collections::btree_map::Iter;
+use super::{AddressSpace, AddressBus, Fork, ADDRBUS_MASK, BusFault, CPU_SPACE, iack_address};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchKind {
+    Read, Write, Access
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccessSize {
+    Byte, Word, Long
+}
+
+impl AccessSize {
+    pub fn bytes(self) -> u32 {
+        match self {
+            AccessSize::Byte => 1,
+            AccessSize::Word => 2,
+            AccessSize::Long => 4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Watchpoint {
+    // first and last address watched
+    pub start: u32,
+    pub end: u32,
+    pub kind: WatchKind,
+    // only accesses in this address space, or of this size, if given
+    pub address_space: Option<AddressSpace>,
+    pub size: Option<AccessSize>,
+    pub enabled: bool,
+}
+
+impl Watchpoint {
+    pub fn new(kind: WatchKind, start: u32, end: u32) -> Watchpoint {
+        Watchpoint { start: start & ADDRBUS_MASK, end: end & ADDRBUS_MASK, kind, address_space: None, size: None, enabled: true }
+    }
+    pub fn matches(&self, address_space: AddressSpace, address: u32, size: AccessSize, kind: WatchKind) -> bool {
+        let last = address.wrapping_add(size.bytes() - 1) & ADDRBUS_MASK;
+        self.enabled && address <= self.end && last >= self.start
+            && (self.kind == WatchKind::Access || self.kind == kind)
+            && self.address_space.is_none_or(|space| space == address_space)
+            && self.size.is_none_or(|watched| watched == size)
+    }
+}
+
+// A triggered watchpoint. For reads, old and new are both the value read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchHit {
+    pub id: usize,
+    pub address_space: AddressSpace,
+    pub address: u32,
+    pub size: AccessSize,
+    // Read or Write
+    pub kind: WatchKind,
+    pub old: u32,
+    pub new: u32,
+    // of the accessing instruction, filled in by the core
+    pub pc: u32,
+}
+
+// Wraps any bus, and watches accesses to address ranges. The first
+// access triggering a watchpoint is held until the core takes it with
+// take_watch_hit, and stops. Reads and writes are otherwise passed on
+// unchanged; the old value of triggering writes is peeked.
+pub struct Watcher<A: AddressBus> {
+    pub inner: A,
+    watchpoints: BTreeMap<usize, Watchpoint>,
+    next_id: usize,
+    hit: Cell<Option<WatchHit>>,
+}
+
+impl<A: AddressBus> Watcher<A> {
+    pub fn new(inner: A) -> Watcher<A> {
+        Watcher { inner, watchpoints: BTreeMap::new(), next_id: 0, hit: Cell::new(None) }
+    }
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+    // returns the id to refer to the watchpoint by
+    pub fn add(&mut self, watchpoint: Watchpoint) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.insert(id, watchpoint);
+        id
+    }
+    pub fn remove(&mut self, id: usize) -> Option<Watchpoint> {
+        self.watchpoints.remove(&id)
+    }
+    pub fn get(&self, id: usize) -> Option<&Watchpoint> {
+        self.watchpoints.get(&id)
+    }
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Watchpoint> {
+        self.watchpoints.get_mut(&id)
+    }
+    pub fn iter(&self) -> Iter<'_, usize, Watchpoint> {
+        self.watchpoints.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.watchpoints.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty()
+    }
+    fn matching(&self, address_space: AddressSpace, address: u32, size: AccessSize, kind: WatchKind) -> Option<usize> {
+        if self.watchpoints.is_empty() || self.hit.get().is_some() {
+            return None;
+        }
+        let address = address & ADDRBUS_MASK;
+        self.watchpoints.iter()
+            .find(|&(_, watchpoint)| watchpoint.matches(address_space, address, size, kind))
+            .map(|(&id, _)| id)
+    }
+    fn read(&self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) -> u32 {
+        if let Some(id) = self.matching(address_space, address, size, WatchKind::Read) {
+            self.hit.set(Some(WatchHit { id, address_space, address: address & ADDRBUS_MASK, size, kind: WatchKind::Read, old: value, new: value, pc: 0 }));
+        }
+        value
+    }
+    fn write<F: FnOnce(&mut A)>(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32, write: F) {
+        match self.matching(address_space, address, size, WatchKind::Write) {
+            Some(id) => {
+                // peeked, as the old value is not read on the bus
+                let old = self.inner.peek(address_space, address, size);
+                write(&mut self.inner);
+                let new = match size {
+                    AccessSize::Byte => value & 0xff,
+                    AccessSize::Word => value & 0xffff,
+                    AccessSize::Long => value,
+                };
+                self.hit.set(Some(WatchHit { id, address_space, address: address & ADDRBUS_MASK, size, kind: WatchKind::Write, old, new, pc: 0 }));
+            }
+            None => write(&mut self.inner)
+        }
+    }
+}
+
+impl<A: AddressBus + Fork> Fork for Watcher<A> {
+    // with the same watchpoints, and any hit not yet taken
+    fn fork(&self) -> Watcher<A> {
+        Watcher { inner: self.inner.fork(), watchpoints: self.watchpoints.clone(), next_id: self.next_id, hit: self.hit.clone() }
+    }
+}
+
+impl<A: AddressBus> AddressBus for Watcher<A> {
+    fn copy_from(&mut self, other: &Self) {
+        self.inner.copy_from(&other.inner);
+    }
+    fn read_byte(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, AccessSize::Byte, self.inner.read_byte(address_space, address))
+    }
+    fn read_word(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, AccessSize::Word, self.inner.read_word(address_space, address))
+    }
+    fn read_long(&self, address_space: AddressSpace, address: u32) -> u32 {
+        self.read(address_space, address, AccessSize::Long, self.inner.read_long(address_space, address))
+    }
+    fn write_byte(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.write(address_space, address, AccessSize::Byte, value, |inner| inner.write_byte(address_space, address, value))
+    }
+    fn write_word(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.write(address_space, address, AccessSize::Word, value, |inner| inner.write_word(address_space, address, value))
+    }
+    fn write_long(&mut self, address_space: AddressSpace, address: u32, value: u32) {
+        self.write(address_space, address, AccessSize::Long, value, |inner| inner.write_long(address_space, address, value))
+    }
+    fn allocated_pages(&self) -> usize {
+        self.inner.allocated_pages()
+    }
+    fn log_len(&self) -> usize {
+        self.inner.log_len()
+    }
+    fn acknowledge_interrupt(&mut self, level: u8, fallback: u8) -> Option<u8> {
+        let vector = self.inner.acknowledge_interrupt(level, fallback);
+        self.read(CPU_SPACE, iack_address(level), AccessSize::Byte, vector.unwrap_or(fallback) as u32);
+        vector
+    }
+    // never triggering watchpoints
+    fn peek(&self, address_space: AddressSpace, address: u32, size: AccessSize) -> u32 {
+        self.inner.peek(address_space, address, size)
+    }
+    fn poke(&mut self, address_space: AddressSpace, address: u32, size: AccessSize, value: u32) {
+        self.inner.poke(address_space, address, size, value)
+    }
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+    fn take_bus_error(&mut self) -> Option<BusFault> {
+        self.inner.take_bus_error()
+    }
+    fn reset(&mut self) {
+        self.inner.reset()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Watcher, Watchpoint, WatchKind, WatchHit, AccessSize};
+    use ram::AddressBus;
+    use ram::pagedmem::PagedMem;
+    use ram::{SUPERVISOR_DATA, SUPERVISOR_PROGRAM, USER_DATA};
+
+    #[test]
+    fn writes_report_old_and_new_values() {
+        let mut mem = Watcher::new(PagedMem::new(0));
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x1234);
+        let id = mem.add(Watchpoint::new(WatchKind::Write, 0x1000, 0x1003));
+        assert_eq!(0x1234, mem.read_word(SUPERVISOR_DATA, 0x1000));
+        assert_eq!(None, mem.take_watch_hit());
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x5678);
+        let expected = WatchHit { id, address_space: SUPERVISOR_DATA, address: 0x1000, size: AccessSize::Word, kind: WatchKind::Write, old: 0x1234, new: 0x5678, pc: 0 };
+        assert_eq!(Some(expected), mem.take_watch_hit());
+        assert_eq!(None, mem.take_watch_hit());
+    }
+
+    #[test]
+    fn watched_writes_are_the_only_bus_access() {
+        use ram::loggingmem::{LoggingMem, OpsLogger, Operation};
+        let mut mem = Watcher::new(LoggingMem::new(0x12345678, OpsLogger::new()));
+        mem.add(Watchpoint::new(WatchKind::Write, 0x1000, 0x1003));
+        mem.write_word(SUPERVISOR_DATA, 0x1000, 0x5678);
+        assert_eq!(Some(0x1234), mem.take_watch_hit().map(|hit| hit.old));
+        assert_eq!(vec![Operation::WriteWord(SUPERVISOR_DATA, 0x1000, 0x5678)], mem.inner.logger.ops());
+    }
+
+    #[test]
+    fn forks_watch_on_their_own() {
+        use ram::Fork;
+        use ram::loggingmem::{LoggingMem, OpsLogger};
+        use ram::tracking::{WriteTracker, CodeWrites};
+        let mut mem = Watcher::new(WriteTracker::new(LoggingMem::new(0, OpsLogger::new())));
+        mem.add(Watchpoint::new(WatchKind::Write, 0x1000, 0x1003));
+        mem.inner.watch_page(1);
+        let mut fork = mem.fork();
+        fork.write_long(SUPERVISOR_DATA, 0x1000, 0x12345678);
+        assert!(fork.take_watch_hit().is_some());
+        assert_eq!(vec![1], fork.inner.take_written_pages());
+        assert_eq!(1, fork.inner.inner.logger.len());
+        assert_eq!(None, mem.take_watch_hit());
+        assert!(!mem.inner.code_written());
+        assert_eq!((0, 0), (mem.inner.inner.logger.len(), mem.read_long(SUPERVISOR_DATA, 0x1000)));
+    }
+
+    #[test]
+    fn unanswered_acknowledge_cycles_report_the_vector_used() {
+        use ram::CPU_SPACE;
+        let mut mem = Watcher::new(PagedMem::new(0));
+        mem.add(Watchpoint::new(WatchKind::Read, 0xfffff9, 0xfffff9));
+        assert_eq!(None, mem.acknowledge_interrupt(4, 0x40));
+        let hit = mem.take_watch_hit().unwrap();
+        assert_eq!((CPU_SPACE, 0x40), (hit.address_space, hit.new));
+    }
+
+    #[test]
+    fn peeks_and_pokes_never_trigger() {
+        let mut mem = Watcher::new(PagedMem::new(0));
+        mem.add(Watchpoint::new(WatchKind::Access, 0x1000, 0x1003));
+        mem.poke(SUPERVISOR_DATA, 0x1000, AccessSize::Long, 0x12345678);
+        assert_eq!(0x5678, mem.peek(SUPERVISOR_DATA, 0x1002, AccessSize::Word));
+        assert_eq!(None, mem.take_watch_hit());
+    }
+
+    #[test]
+    fn accesses_overlapping_the_range_trigger() {
+        let mut mem = Watcher::new(PagedMem::new(0));
+        mem.add(Watchpoint::new(WatchKind::Access, 0x1002, 0x1002));
+        mem.read_long(SUPERVISOR_DATA, 0x1000);
+        assert!(mem.take_watch_hit().is_some());
+        mem.write_byte(SUPERVISOR_DATA, 0x1003, 0);
+        assert!(mem.take_watch_hit().is_none());
+        mem.write_byte(SUPERVISOR_DATA, 0x1002, 0);
+        assert!(mem.take_watch_hit().is_some());
+    }
+
+    #[test]
+    fn accesses_can_be_filtered_by_address_space_and_size() {
+        let mut mem = Watcher::new(PagedMem::new(0));
+        let id = mem.add(Watchpoint { address_space: Some(USER_DATA), size: Some(AccessSize::Byte), ..Watchpoint::new(WatchKind::Read, 0, 0xffffff) });
+        mem.read_byte(SUPERVISOR_DATA, 0x1000);
+        mem.read_byte(SUPERVISOR_PROGRAM, 0x1000);
+        mem.read_word(USER_DATA, 0x1000);
+        assert!(mem.take_watch_hit().is_none());
+        mem.read_byte(USER_DATA, 0x1000);
+        assert_eq!(Some(id), mem.take_watch_hit().map(|hit| hit.id));
+        mem.get_mut(id).unwrap().enabled = false;
+        mem.read_byte(USER_DATA, 0x1000);
+        assert!(mem.take_watch_hit().is_none());
+    }
+
+    #[test]
+    fn execution_stops_after_the_accessing_instruction() {
+        use cpu::{Core, StopConditions, StopReason};
+        use testing::load;
+        // MOVEQ #1, D0; MOVE.W D0, (A0); MOVEQ #2, D0
+        let mut mem = Watcher::new(PagedMem::new(0));
+        load(&mut mem, 0x1000, &[0x70, 0x01, 0x30, 0x80, 0x70, 0x02]);
+        mem.add(Watchpoint::new(WatchKind::Write, 0x2000, 0x2fff));
+        let mut core = Core::new_with_bus(0x1000, mem);
+        core.dar[8] = 0x2ffe;
+        let reason = core.run_until(&StopConditions { instructions: Some(3), ..Default::default() });
+        let hit = match reason {
+            StopReason::Watchpoint(hit) => hit,
+            _ => panic!("expected a watchpoint, got {:?}", reason)
+        };
+        assert_eq!((0x1002, 0x2ffe, 0, 1), (hit.pc, hit.address, hit.old, hit.new));
+        assert_eq!(0x1004, core.pc);
+        assert_eq!(Some(hit), core.watch_hit);
+        // execute stops the same way
+        core.jump(0x1000);
+        core.execute(100);
+        assert_eq!(0x1004, core.pc);
+        assert_eq!(Some(1), core.watch_hit.map(|hit| hit.old));
+    }
+}
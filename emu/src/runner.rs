@@ -0,0 +1,515 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use cpu::{Core, Cycles};
+use ram::AddressBus;
+
+// Anything the runner can drive for a quantum of cycles. Cores
+// implement it directly, but a machine wrapping a core together with
+// its devices can implement it too, as long as it can be sent to
+// another thread.
+pub trait Machine: Send {
+    fn execute_quantum(&mut self, cycles: i32) -> Cycles;
+
+    // true if running the machine cannot make any progress until the
+    // host does something to it, like raising an interrupt
+    fn idle(&self) -> bool {
+        false
+    }
+
+    fn usage(&self) -> Usage {
+        Usage::default()
+    }
+}
+
+impl<A: AddressBus + Send> Machine for Core<A> {
+    fn execute_quantum(&mut self, cycles: i32) -> Cycles {
+        self.execute(cycles)
+    }
+
+    fn idle(&self) -> bool {
+        self.is_idle()
+    }
+
+    fn usage(&self) -> Usage {
+        Usage { pages: self.mem.allocated_pages(), log_len: self.mem.log_len() }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub pages: usize,
+    pub log_len: usize,
+}
+
+// Per-machine resource limits; None means unlimited
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    pub cycles_per_second: Option<u64>,
+    pub pages: Option<usize>,
+    pub log_len: Option<usize>,
+}
+
+// Reported to the quota callback with the usage that broke the limit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuotaExceeded {
+    CyclesPerSecond(u64),
+    Pages(usize),
+    LogLen(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MachineState {
+    Running,
+    Parked,     // idle, woken when the host touches it
+    Throttled,  // out of cycles for the current second
+    Suspended,  // over its memory or log quota, until resumed by the host
+}
+
+struct Slot<M: Machine> {
+    machine: M,
+    last_quantum: Cycles,
+}
+
+struct Entry {
+    quota: Quota,
+    state: MachineState,
+    cycles_in_window: u64,
+}
+
+impl Entry {
+    // the quantum, clamped to what is left of the cycle budget, so that
+    // a machine overruns it by at most the instruction that crosses it
+    fn quantum(&self, cycles: i32) -> i32 {
+        match self.quota.cycles_per_second {
+            Some(limit) => cycles.min(limit.saturating_sub(self.cycles_in_window).min(i32::MAX as u64) as i32),
+            None => cycles,
+        }
+    }
+}
+
+struct Shared<M: Machine> {
+    slots: Vec<Mutex<Slot<M>>>,
+    next: AtomicUsize,
+}
+
+enum Job {
+    // the machines to run, each with its quantum
+    Run(Arc<Vec<(usize, i32)>>),
+    Shutdown,
+}
+
+pub type QuotaCallback<M> = Box<dyn FnMut(usize, &mut M, QuotaExceeded)>;
+
+// Executes a set of independent machines on a fixed pool of host
+// threads. Machines never see each other, so the only synchronization
+// needed is at quantum boundaries: run_quantum hands out machines to
+// the workers until all have run, and returns once every worker is
+// done. Between quanta the host has exclusive access to all machines.
+// Dropping the runner disconnects the workers, which then exit.
+//
+// Machines that are idle are parked, and not handed to any worker
+// until the host accesses them again. Quotas are checked on the host
+// thread after each quantum, where the quota callback is also called.
+pub struct ThreadPoolRunner<M: Machine + 'static> {
+    shared: Arc<Shared<M>>,
+    entries: Vec<Entry>,
+    window_start: Instant,
+    on_quota_exceeded: Option<QuotaCallback<M>>,
+    jobs: Vec<Sender<Job>>,
+    done: Receiver<()>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<M: Machine + 'static> ThreadPoolRunner<M> {
+    pub fn new(machines: Vec<M>, threads: usize) -> ThreadPoolRunner<M> {
+        assert!(threads > 0);
+        let entries = machines.iter().map(|_| Entry { quota: Quota::default(), state: MachineState::Running, cycles_in_window: 0 }).collect();
+        let slots = machines.into_iter().map(|machine| Mutex::new(Slot { machine, last_quantum: Cycles(0) })).collect();
+        let shared = Arc::new(Shared { slots, next: AtomicUsize::new(0) });
+        let (done_tx, done_rx) = channel();
+        let mut jobs = Vec::with_capacity(threads);
+        let mut workers = Vec::with_capacity(threads);
+        for _ in 0..threads {
+            let (job_tx, job_rx) = channel();
+            let shared = shared.clone();
+            let done_tx = done_tx.clone();
+            jobs.push(job_tx);
+            workers.push(thread::spawn(move || worker(shared, job_rx, done_tx)));
+        }
+        ThreadPoolRunner { shared, entries, window_start: Instant::now(), on_quota_exceeded: None, jobs, done: done_rx, workers }
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shared.slots.is_empty()
+    }
+
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn set_quota(&mut self, index: usize, quota: Quota) {
+        self.entries[index].quota = quota;
+    }
+
+    pub fn quota(&self, index: usize) -> Quota {
+        self.entries[index].quota
+    }
+
+    pub fn on_quota_exceeded<F>(&mut self, callback: F) where F: FnMut(usize, &mut M, QuotaExceeded) + 'static {
+        self.on_quota_exceeded = Some(Box::new(callback));
+    }
+
+    pub fn state(&self, index: usize) -> MachineState {
+        self.entries[index].state
+    }
+
+    // Lets a suspended machine run again, typically after the host has
+    // freed memory or drained its log
+    pub fn resume(&mut self, index: usize) {
+        if self.entries[index].state == MachineState::Suspended {
+            self.entries[index].state = MachineState::Running;
+        }
+    }
+
+    // Runs every schedulable machine for (at least) the given number of
+    // cycles, or what is left of its cycle budget for the second if less,
+    // and returns the cycles each machine consumed, in the order the
+    // machines were given to the runner. Parked machines are not run, but
+    // time passes for them as it would for a stopped core. Throttled and
+    // suspended machines consume nothing.
+    pub fn run_quantum(&mut self, cycles: i32) -> Vec<Cycles> {
+        if self.window_start.elapsed() >= Duration::from_secs(1) {
+            self.window_start = Instant::now();
+            for entry in &mut self.entries {
+                entry.cycles_in_window = 0;
+                if entry.state == MachineState::Throttled {
+                    entry.state = MachineState::Running;
+                }
+            }
+        }
+        let schedule: Vec<(usize, i32)> = self.entries.iter().enumerate()
+            .filter(|&(_, entry)| entry.state == MachineState::Running)
+            .map(|(index, entry)| (index, entry.quantum(cycles)))
+            .collect();
+        let schedule = Arc::new(schedule);
+        if !schedule.is_empty() {
+            self.shared.next.store(0, Ordering::SeqCst);
+            for job in &self.jobs {
+                job.send(Job::Run(schedule.clone())).expect("runner worker has died");
+            }
+            for _ in 0..self.jobs.len() {
+                self.done.recv().expect("runner worker has died");
+            }
+        }
+        let mut consumed: Vec<Cycles> = self.entries.iter().map(|entry| match entry.state {
+            MachineState::Parked => Cycles(cycles),
+            _ => Cycles(0),
+        }).collect();
+        for &(index, _) in schedule.iter() {
+            consumed[index] = self.after_quantum(index);
+        }
+        consumed
+    }
+
+    fn after_quantum(&mut self, index: usize) -> Cycles {
+        let mut slot = lock(&self.shared.slots[index]);
+        let entry = &mut self.entries[index];
+        let Cycles(used) = slot.last_quantum;
+        entry.cycles_in_window += used.max(0) as u64;
+
+        let usage = slot.machine.usage();
+        let mut exceeded = Vec::new();
+        if let Some(limit) = entry.quota.cycles_per_second {
+            if entry.cycles_in_window >= limit {
+                entry.state = MachineState::Throttled;
+                exceeded.push(QuotaExceeded::CyclesPerSecond(entry.cycles_in_window));
+            }
+        }
+        if let Some(limit) = entry.quota.pages {
+            if usage.pages > limit {
+                entry.state = MachineState::Suspended;
+                exceeded.push(QuotaExceeded::Pages(usage.pages));
+            }
+        }
+        if let Some(limit) = entry.quota.log_len {
+            if usage.log_len > limit {
+                entry.state = MachineState::Suspended;
+                exceeded.push(QuotaExceeded::LogLen(usage.log_len));
+            }
+        }
+        if let Some(ref mut callback) = self.on_quota_exceeded {
+            for quota in exceeded {
+                callback(index, &mut slot.machine, quota);
+            }
+        }
+        if entry.state == MachineState::Running && slot.machine.idle() {
+            entry.state = MachineState::Parked;
+        }
+        slot.last_quantum
+    }
+
+    // Gives the host access to a machine between quanta. A parked
+    // machine is woken up when the access ends, if it is no longer idle
+    pub fn machine(&mut self, index: usize) -> MachineGuard<'_, M> {
+        MachineGuard { slot: lock(&self.shared.slots[index]), entry: &mut self.entries[index] }
+    }
+
+    // Stops all workers and hands the machines back to the host
+    pub fn into_machines(mut self) -> Vec<M> {
+        self.shutdown();
+        let shared = match Arc::try_unwrap(self.shared) {
+            Ok(shared) => shared,
+            Err(_) => panic!("runner workers still hold machines after shutdown"),
+        };
+        shared.slots.into_iter().map(|slot| match slot.into_inner() {
+            Ok(slot) => slot.machine,
+            Err(poisoned) => poisoned.into_inner().machine,
+        }).collect()
+    }
+
+    fn shutdown(&mut self) {
+        for job in self.jobs.drain(..) {
+            let _ = job.send(Job::Shutdown);
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+pub struct MachineGuard<'a, M: Machine + 'a> {
+    slot: MutexGuard<'a, Slot<M>>,
+    entry: &'a mut Entry,
+}
+
+use std::ops::{Deref, DerefMut};
+impl<'a, M: Machine + 'a> Deref for MachineGuard<'a, M> {
+    type Target = M;
+    fn deref(&self) -> &M {
+        &self.slot.machine
+    }
+}
+impl<'a, M: Machine + 'a> DerefMut for MachineGuard<'a, M> {
+    fn deref_mut(&mut self) -> &mut M {
+        &mut self.slot.machine
+    }
+}
+impl<'a, M: Machine + 'a> Drop for MachineGuard<'a, M> {
+    fn drop(&mut self) {
+        if self.entry.state == MachineState::Parked && !self.slot.machine.idle() {
+            self.entry.state = MachineState::Running;
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    // a machine that panicked is still returned to the host, which
+    // can decide what to do with it
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn worker<M: Machine>(shared: Arc<Shared<M>>, jobs: Receiver<Job>, done: Sender<()>) {
+    while let Ok(Job::Run(schedule)) = jobs.recv() {
+        // machines are handed out one at a time, so a thread that
+        // drew cheap machines keeps going until all have been run
+        loop {
+            let next = shared.next.fetch_add(1, Ordering::SeqCst);
+            if next >= schedule.len() {
+                break;
+            }
+            let (index, cycles) = schedule[next];
+            let mut slot = lock(&shared.slots[index]);
+            slot.last_quantum = slot.machine.execute_quantum(cycles);
+        }
+        if done.send(()).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Machine, MachineState, Quota, QuotaExceeded, ThreadPoolRunner};
+    use cpu::{Core, Cycles, ProcessingState};
+    use ram::pagedmem::PagedMem;
+    use ram::loggingmem::{LoggingMem, OpsLogger};
+    use testing::{core_on, core_running};
+    use std::sync::mpsc::channel;
+
+    // ADDQ.L #1, D0 followed by BRA.S back to it
+    const COUNTING_LOOP: [u8; 4] = [0x52, 0x80, 0x60, 0xfc];
+    // STOP #$2000, which leaves interrupts unmasked
+    const STOP: [u8; 4] = [0x4e, 0x72, 0x20, 0x00];
+    // MOVE.B D0, (A0)+ followed by ADDQ.B #1, D0 and BRA.S back to the move
+    const FILLING_LOOP: [u8; 6] = [0x10, 0xc0, 0x52, 0x00, 0x60, 0xfa];
+
+    fn counting_core(start: u32) -> Core<PagedMem> {
+        let mut core = core_running(&COUNTING_LOOP);
+        core.dar[0] = start;
+        core
+    }
+
+    fn is_send<T: Send>() {}
+
+    #[test]
+    fn cores_with_non_logging_bus_are_send() {
+        is_send::<Core<PagedMem>>();
+    }
+
+    #[test]
+    fn runs_all_machines_each_quantum() {
+        let machines = (0..10).map(|i| counting_core(i * 1000)).collect();
+        let mut runner = ThreadPoolRunner::new(machines, 3);
+        assert_eq!(10, runner.len());
+        let consumed = runner.run_quantum(100);
+        assert_eq!(10, consumed.len());
+        for cycles in consumed {
+            assert!(cycles >= Cycles(100));
+        }
+        runner.run_quantum(100);
+        let cores = runner.into_machines();
+        for (i, core) in cores.iter().enumerate() {
+            assert!(core.dar[0] > i as u32 * 1000);
+        }
+    }
+
+    #[test]
+    fn parallel_execution_matches_sequential_execution() {
+        let mut sequential: Vec<Core<PagedMem>> = (0..8).map(counting_core).collect();
+        let parallel = (0..8).map(counting_core).collect();
+        let mut runner = ThreadPoolRunner::new(parallel, 4);
+        for _ in 0..5 {
+            let consumed = runner.run_quantum(250);
+            for (core, cycles) in sequential.iter_mut().zip(consumed) {
+                assert_eq!(core.execute_quantum(250), cycles);
+            }
+        }
+        for (i, core) in sequential.iter().enumerate() {
+            assert_eq!(core.dar[0], runner.machine(i).dar[0]);
+            assert_eq!(core.pc, runner.machine(i).pc);
+        }
+    }
+
+    #[test]
+    fn host_can_modify_machines_between_quanta() {
+        let mut runner = ThreadPoolRunner::new(vec![counting_core(0)], 2);
+        runner.run_quantum(50);
+        runner.machine(0).dar[0] = 0x10000;
+        runner.run_quantum(50);
+        assert!(runner.machine(0).dar[0] > 0x10000);
+    }
+
+    #[test]
+    fn stopped_machines_are_parked_until_interrupted() {
+        let mut core = core_running(&STOP);
+        let vector = 0x2000;
+        core.mem.write_u8((24 + 3) * 4, 0);
+        core.mem.write_u8((24 + 3) * 4 + 1, 0);
+        core.mem.write_u8((24 + 3) * 4 + 2, vector >> 8);
+        core.mem.write_u8((24 + 3) * 4 + 3, vector & 0xff);
+        for (offset, byte) in COUNTING_LOOP.iter().enumerate() {
+            core.mem.write_u8(vector + offset as u32, *byte as u32);
+        }
+        let mut runner = ThreadPoolRunner::new(vec![core], 1);
+        runner.run_quantum(100);
+        assert_eq!(MachineState::Parked, runner.state(0));
+        assert_eq!(ProcessingState::Stopped, runner.machine(0).processing_state);
+        // time still passes for a parked machine
+        assert_eq!(vec![Cycles(100)], runner.run_quantum(100));
+
+        runner.machine(0).int_ctrl.request_interrupt(3);
+        assert_eq!(MachineState::Running, runner.state(0));
+        runner.run_quantum(100);
+        assert_eq!(MachineState::Running, runner.state(0));
+        assert!(runner.machine(0).dar[0] > 0);
+    }
+
+    #[test]
+    fn reading_a_parked_machine_does_not_wake_it() {
+        let mut runner = ThreadPoolRunner::new(vec![core_running(&STOP)], 1);
+        runner.run_quantum(100);
+        let pc = runner.machine(0).pc;
+        assert_eq!(0x1004, pc);
+        assert_eq!(MachineState::Parked, runner.state(0));
+    }
+
+    #[test]
+    fn cycle_budget_throttles_machine() {
+        let mut runner = ThreadPoolRunner::new(vec![counting_core(0), counting_core(0)], 2);
+        runner.set_quota(0, Quota { cycles_per_second: Some(1000), ..Quota::default() });
+        let (tx, rx) = channel();
+        runner.on_quota_exceeded(move |index, _, exceeded| tx.send((index, exceeded)).unwrap());
+        for _ in 0..4 {
+            runner.run_quantum(400);
+        }
+        assert_eq!(MachineState::Throttled, runner.state(0));
+        assert_eq!(MachineState::Running, runner.state(1));
+        match rx.try_recv() {
+            Ok((0, QuotaExceeded::CyclesPerSecond(used))) => assert!(used >= 1000),
+            other => panic!("unexpected quota report {:?}", other),
+        }
+        // a throttled machine does not run
+        let before = runner.machine(0).dar[0];
+        assert_eq!(Cycles(0), runner.run_quantum(400)[0]);
+        assert_eq!(before, runner.machine(0).dar[0]);
+    }
+
+    #[test]
+    fn quanta_are_clamped_to_the_cycle_budget() {
+        let mut runner = ThreadPoolRunner::new(vec![counting_core(0)], 1);
+        runner.set_quota(0, Quota { cycles_per_second: Some(1000), ..Quota::default() });
+        runner.run_quantum(400);
+        runner.run_quantum(400);
+        let Cycles(last) = runner.run_quantum(400)[0];
+        // no further than the BRA crossing the budget
+        assert!(last < 400);
+        assert!(runner.entries[0].cycles_in_window < 1000 + 10);
+        assert_eq!(MachineState::Throttled, runner.state(0));
+    }
+
+    #[test]
+    fn page_quota_suspends_machine_until_resumed() {
+        let mut core = core_running(&FILLING_LOOP);
+        core.dar[8] = 0x4000;
+        let mut runner = ThreadPoolRunner::new(vec![core], 1);
+        runner.set_quota(0, Quota { pages: Some(4), ..Quota::default() });
+        let (tx, rx) = channel();
+        runner.on_quota_exceeded(move |_, machine: &mut Core<PagedMem>, exceeded| {
+            tx.send((exceeded, machine.dar[8])).unwrap();
+        });
+        for _ in 0..10 {
+            runner.run_quantum(1000);
+        }
+        assert_eq!(MachineState::Suspended, runner.state(0));
+        match rx.try_recv() {
+            Ok((QuotaExceeded::Pages(pages), a0)) => {
+                assert!(pages > 4);
+                assert!(a0 > 0x4000 + 4 * 16);
+            },
+            other => panic!("unexpected quota report {:?}", other),
+        }
+        assert!(rx.try_recv().is_err());
+        runner.resume(0);
+        assert_eq!(MachineState::Running, runner.state(0));
+    }
+
+    #[test]
+    fn log_quota_is_checked_against_logging_bus() {
+        let mut core = core_on(LoggingMem::new(0xaaaaaaaa, OpsLogger::new()), &FILLING_LOOP);
+        core.dar[8] = 0x4000;
+        let mut runner = ThreadPoolRunner::new(vec![core], 1);
+        runner.set_quota(0, Quota { log_len: Some(10), ..Quota::default() });
+        runner.run_quantum(200);
+        assert_eq!(MachineState::Suspended, runner.state(0));
+    }
+}
@@ -0,0 +1,27 @@
+// Fixtures shared by the tests of the crate
+use cpu::Core;
+use ram::{AddressBus, SUPERVISOR_PROGRAM};
+use ram::pagedmem::PagedMem;
+use ram::watching::AccessSize;
+
+// where test programs are loaded, and cores start running them
+pub const PROGRAM_START: u32 = 0x1000;
+
+// Writes bytes the way a debugger would, so loggers, watchers and the
+// like don't see them
+pub fn load<A: AddressBus>(mem: &mut A, address: u32, bytes: &[u8]) {
+    for (offset, byte) in bytes.iter().enumerate() {
+        mem.poke(SUPERVISOR_PROGRAM, address.wrapping_add(offset as u32), AccessSize::Byte, *byte as u32);
+    }
+}
+
+// A core about to run a program loaded into the memory given
+pub fn core_on<A: AddressBus>(mut mem: A, program: &[u8]) -> Core<A> {
+    load(&mut mem, PROGRAM_START, program);
+    Core::new_with_bus(PROGRAM_START, mem)
+}
+
+// A core about to run a program in otherwise zeroed memory
+pub fn core_running(program: &[u8]) -> Core<PagedMem> {
+    core_on(PagedMem::new(0), program)
+}
@@ -123,16 +123,25 @@ use self::parser::{Rdp, Rule, Directive};
 use pest::{StringInput, Parser};
 pub struct Assembler;
 
+// instruction addresses, and the lines (counting from 1) they came from
+pub type LineNumbers = Vec<(u32, usize)>;
+
 impl Assembler {
     pub fn new() -> Assembler {
         Assembler
     }
 
     pub fn assemble(&self, reader: &mut BufRead) ->  io::Result<(u32, MemoryVec)> {
+        self.assemble_with_lines(reader).map(|(pc, mem, _)| (pc, mem))
+    }
+
+    // As assemble, but also telling which line each instruction is from
+    pub fn assemble_with_lines(&self, reader: &mut dyn BufRead) ->  io::Result<(u32, MemoryVec, LineNumbers)> {
         let mut mem = MemoryVec::new();
         let mut pc = 0;
+        let mut lines = Vec::new();
 
-        for line in reader.lines() {
+        for (number, line) in reader.lines().enumerate() {
             let asm = line.unwrap();
             let mut parser = Rdp::new(StringInput::new(&asm));
             assert!(parser.statement());
@@ -149,13 +158,14 @@ impl Assembler {
                 },
                 Rule::an_instruction => {
                     let op = parser.process_instruction();
+                    lines.push((pc, number + 1));
                     pc = encode_instruction(&queue[0].1, &op, pc, &mut mem);
                 },
                 Rule::asm_comment => continue,
                 other_rule => panic!("Does not yet handle {:?}", other_rule),
             }
         }
-        Ok((pc, mem))
+        Ok((pc, mem, lines))
     }
 
     pub fn parse_assembler<'a>(&'a self, instruction: &'a str) -> OpcodeInstance {
@@ -238,4 +248,18 @@ mod tests {
         assert_eq!(0x1000 + 6, end);
         assert_eq!(0x1000, mem.offset());
     }
+
+    #[test]
+    fn maps_instructions_to_lines() {
+        let r68k = Assembler::new();
+
+        let asm = r#"    ORG $1000
+    ; two instructions
+    ADD.B   #$3,D0
+    ADD.B   D0,D1"#;
+
+        let mut reader = BufReader::new(asm.as_bytes());
+        let (_, _, lines) = r68k.assemble_with_lines(&mut reader).unwrap();
+        assert_eq!(vec![(0x1000, 3), (0x1004, 4)], lines);
+    }
 }
\ No newline at end of file
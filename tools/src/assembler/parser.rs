@@ -51,6 +51,35 @@ impl_rdp! {
         bitwise_and_op = { ["&"] }
         complement_op  = { ["~"] }
 
+        // conditions, as used by emulator breakpoints; expressions
+        // extended with registers, memory dereference like (A0).W,
+        // comparisons and logical operators
+        condition = _{
+            // precedence climbing, lowest to highest
+            { (negate | complement | logical_not)? ~ (deref | ["("] ~ condition ~ [")"] | register | symbol | number) }
+            logical_or = { logical_or_op }
+            logical_and = { logical_and_op }
+            equality = { equal_op | not_equal_op }
+            relation = { less_equal_op | greater_equal_op | less_op | greater_op }
+            cond_add = { add_op | sub_op }
+            cond_mul = { mul_op | div_op | mod_op }
+            cond_ior = { bitwise_ior_op }
+            cond_xor = { bitwise_xor_op }
+            cond_and = { bitwise_and_op }
+            cond_shift = { shift_left_op | shift_right_op }
+        }
+        deref = { ["("] ~ condition ~ [")"] ~ (longsize | wordsize | bytesize) }
+        register = @{ ([i"D"] | [i"A"]) ~ ['0'..'7'] ~ !(letter | digit) | ([i"SSP"] | [i"USP"] | [i"SP"] | [i"SR"] | [i"CCR"] | [i"PC"]) ~ !(letter | digit) }
+        logical_not = { ["!"] }
+        logical_or_op = { ["||"] }
+        logical_and_op = { ["&&"] }
+        equal_op = { ["=="] }
+        not_equal_op = { ["!="] }
+        less_equal_op = { ["<="] }
+        greater_equal_op = { [">="] }
+        less_op = @{ ["<"] ~ !["<"] }
+        greater_op = @{ [">"] ~ ![">"] }
+
         quoted_string = @{ ["\""] ~ (letter|digit| !["\""] ~ any )* ~ ["\""] | ["'"] ~ (letter|digit|!["'"] ~ any)* ~ ["'"] }
         // chr = {["!"]|["#"]|["$"]|["%"]|["&"]|["/"]|["("]|[")"]|["="]|["?"]|["*"]|[","]|["."]|[":"]|[";"]|["+"]|["-"]|["_"]|["<"]|[">"]|["["]|["]"]|["{"]|["}"]}
         an_instruction = { label? ~ instruction }
@@ -310,6 +339,96 @@ impl_rdp! {
                 Expr::Num(0)
             }
         }
+
+        process_condition(&self) -> Expr {
+            (_: number, num: process_number()) => {
+                Expr::Num(num)
+            },
+            (&name: register) => {
+                Expr::Reg(Register::named(name))
+            },
+            (&name: name) => {
+                Expr::Sym(name.to_owned())
+            },
+            (_: deref, address: process_condition(), size: process_size()) => {
+                Expr::Deref(Box::new(address), size)
+            },
+            (_: complement, right: process_condition()) => {
+                Expr::Cpl(Box::new(right))
+            },
+            (_: negate, right: process_condition()) => {
+                Expr::Neg(Box::new(right))
+            },
+            (_: logical_not, right: process_condition()) => {
+                Expr::Not(Box::new(right))
+            },
+            (_: logical_or, left: process_condition(), _, right: process_condition()) => {
+                Expr::LogOr(Box::new(left), Box::new(right))
+            },
+            (_: logical_and, left: process_condition(), _, right: process_condition()) => {
+                Expr::LogAnd(Box::new(left), Box::new(right))
+            },
+            (_: equality, left: process_condition(), op, right: process_condition()) => {
+                match op.rule {
+                    Rule::equal_op => Expr::Eq(Box::new(left), Box::new(right)),
+                    Rule::not_equal_op => Expr::Ne(Box::new(left), Box::new(right)),
+                    _ => unreachable!()
+                }
+            },
+            (_: relation, left: process_condition(), op, right: process_condition()) => {
+                match op.rule {
+                    Rule::less_equal_op => Expr::Le(Box::new(left), Box::new(right)),
+                    Rule::greater_equal_op => Expr::Ge(Box::new(left), Box::new(right)),
+                    Rule::less_op => Expr::Lt(Box::new(left), Box::new(right)),
+                    Rule::greater_op => Expr::Gt(Box::new(left), Box::new(right)),
+                    _ => unreachable!()
+                }
+            },
+            (_: cond_add, left: process_condition(), op, right: process_condition()) => {
+                match op.rule {
+                    Rule::add_op => Expr::Add(Box::new(left), Box::new(right)),
+                    Rule::sub_op => Expr::Sub(Box::new(left), Box::new(right)),
+                    _ => unreachable!()
+                }
+            },
+            (_: cond_mul, left: process_condition(), op, right: process_condition()) => {
+                match op.rule {
+                    Rule::mul_op => Expr::Mul(Box::new(left), Box::new(right)),
+                    Rule::div_op => Expr::Div(Box::new(left), Box::new(right)),
+                    Rule::mod_op => Expr::Mod(Box::new(left), Box::new(right)),
+                    _ => unreachable!()
+                }
+            },
+            (_: cond_ior, left: process_condition(), _, right: process_condition()) => {
+                Expr::Ior(Box::new(left), Box::new(right))
+            },
+            (_: cond_xor, left: process_condition(), _, right: process_condition()) => {
+                Expr::Xor(Box::new(left), Box::new(right))
+            },
+            (_: cond_and, left: process_condition(), _, right: process_condition()) => {
+                Expr::And(Box::new(left), Box::new(right))
+            },
+            (_: cond_shift, left: process_condition(), op, right: process_condition()) => {
+                match op.rule {
+                    Rule::shift_left_op => Expr::Shl(Box::new(left), Box::new(right)),
+                    Rule::shift_right_op => Expr::Shr(Box::new(left), Box::new(right)),
+                    _ => unreachable!()
+                }
+            },
+            () => {
+                Expr::Num(0)
+            }
+        }
+    }
+}
+
+// Parses a condition like D0 == $10 && (A0).W > 3
+pub fn parse_condition(condition: &str) -> Option<Expr> {
+    let mut parser = Rdp::new(StringInput::new(condition.trim()));
+    if parser.condition() && parser.end() {
+        Some(parser.process_condition())
+    } else {
+        None
     }
 }
 
@@ -341,25 +460,103 @@ pub enum Expr {
     And(Box<Expr>, Box<Expr>),
     Shl(Box<Expr>, Box<Expr>),
     Shr(Box<Expr>, Box<Expr>),
+    // only found in conditions
+    Reg(Register),
+    Deref(Box<Expr>, Size),
+    Not(Box<Expr>),
+    Eq(Box<Expr>, Box<Expr>),
+    Ne(Box<Expr>, Box<Expr>),
+    Lt(Box<Expr>, Box<Expr>),
+    Le(Box<Expr>, Box<Expr>),
+    Gt(Box<Expr>, Box<Expr>),
+    Ge(Box<Expr>, Box<Expr>),
+    LogAnd(Box<Expr>, Box<Expr>),
+    LogOr(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Register {
+    Data(u8), Address(u8), Pc, Sr, Ccr, Usp, Ssp
+}
+impl Register {
+    // from a name matched by the register rule
+    fn named(name: &str) -> Register {
+        match name.to_uppercase().as_str() {
+            "PC" => Register::Pc,
+            "SR" => Register::Sr,
+            "CCR" => Register::Ccr,
+            "USP" => Register::Usp,
+            "SSP" => Register::Ssp,
+            "SP" => Register::Address(7),
+            reg if reg.starts_with('D') => Register::Data(reg[1..].parse().unwrap()),
+            reg => Register::Address(reg[1..].parse().unwrap()),
+        }
+    }
+}
+
+// Supplies register and memory contents when evaluating conditions
+pub trait Environment {
+    fn register(&self, reg: Register) -> Option<i32>;
+    fn memory(&self, address: u32, size: Size) -> Option<i32>;
+}
+
+// No registers nor memory, as when assembling
+struct Constants;
+impl Environment for Constants {
+    fn register(&self, _: Register) -> Option<i32> {
+        None
+    }
+    fn memory(&self, _: u32, _: Size) -> Option<i32> {
+        None
+    }
+}
+
+fn truth(value: bool) -> i32 {
+    if value { 1 } else { 0 }
 }
+
 impl Expr {
     pub fn eval(&self) -> Option<i32> {
+        self.eval_in(&Constants)
+    }
+    // Arithmetic wraps around like on the target, while division by zero
+    // and out of range shifts evaluate to None rather than panicking
+    pub fn eval_in<E: Environment>(&self, env: &E) -> Option<i32> {
+        let binary = |left: &Expr, right: &Expr, op: &dyn Fn(i32, i32) -> Option<i32>|
+            left.eval_in(env).and_then(|lv| right.eval_in(env).and_then(|rv| op(lv, rv)));
         match *self {
             Expr::Num(n) => Some(n),
             Expr::Sym(_) => None,
             Expr::Str(_) => None,
-            Expr::Neg(ref right) => right.eval().map(|lv| -lv),
-            Expr::Cpl(ref right) => right.eval().map(|lv| !lv),
-            Expr::Add(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv + rv))),
-            Expr::Sub(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv - rv))),
-            Expr::Mul(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv * rv))),
-            Expr::Div(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv / rv))),
-            Expr::Mod(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv % rv))),
-            Expr::Ior(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv | rv))),
-            Expr::Xor(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv ^ rv))),
-            Expr::And(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv & rv))),
-            Expr::Shl(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv << rv))),
-            Expr::Shr(ref left, ref right) => left.eval().and_then(|lv| right.eval().and_then(|rv| Some(lv >> rv))),
+            Expr::Neg(ref right) => right.eval_in(env).map(|lv| lv.wrapping_neg()),
+            Expr::Cpl(ref right) => right.eval_in(env).map(|lv| !lv),
+            Expr::Add(ref left, ref right) => binary(left, right, &|lv, rv| Some(lv.wrapping_add(rv))),
+            Expr::Sub(ref left, ref right) => binary(left, right, &|lv, rv| Some(lv.wrapping_sub(rv))),
+            Expr::Mul(ref left, ref right) => binary(left, right, &|lv, rv| Some(lv.wrapping_mul(rv))),
+            Expr::Div(ref left, ref right) => binary(left, right, &|lv, rv| lv.checked_div(rv)),
+            Expr::Mod(ref left, ref right) => binary(left, right, &|lv, rv| lv.checked_rem(rv)),
+            Expr::Ior(ref left, ref right) => binary(left, right, &|lv, rv| Some(lv | rv)),
+            Expr::Xor(ref left, ref right) => binary(left, right, &|lv, rv| Some(lv ^ rv)),
+            Expr::And(ref left, ref right) => binary(left, right, &|lv, rv| Some(lv & rv)),
+            Expr::Shl(ref left, ref right) => binary(left, right, &|lv, rv| lv.checked_shl(rv as u32)),
+            Expr::Shr(ref left, ref right) => binary(left, right, &|lv, rv| lv.checked_shr(rv as u32)),
+            Expr::Reg(reg) => env.register(reg),
+            Expr::Deref(ref address, size) => address.eval_in(env).and_then(|address| env.memory(address as u32, size)),
+            Expr::Not(ref right) => right.eval_in(env).map(|lv| truth(lv == 0)),
+            Expr::Eq(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv == rv))),
+            Expr::Ne(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv != rv))),
+            Expr::Lt(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv < rv))),
+            Expr::Le(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv <= rv))),
+            Expr::Gt(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv > rv))),
+            Expr::Ge(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv >= rv))),
+            Expr::LogAnd(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv != 0 && rv != 0))),
+            Expr::LogOr(ref left, ref right) => binary(left, right, &|lv, rv| Some(truth(lv != 0 || rv != 0))),
+        }
+    }
+    fn folded(self) -> Expr {
+        match self.eval() {
+            Some(num) => Expr::Num(num),
+            None => self
         }
     }
     pub fn resolve(&self, name: &str, value: i32) -> Expr {
@@ -460,6 +657,17 @@ impl Expr {
                     res
                 }
             },
+            Expr::Deref(ref address, size) => Expr::Deref(Box::new(address.resolve(name, value)), size),
+            Expr::Not(ref right) => Expr::Not(Box::new(right.resolve(name, value))).folded(),
+            Expr::Eq(ref left, ref right) => Expr::Eq(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::Ne(ref left, ref right) => Expr::Ne(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::Lt(ref left, ref right) => Expr::Lt(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::Le(ref left, ref right) => Expr::Le(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::Gt(ref left, ref right) => Expr::Gt(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::Ge(ref left, ref right) => Expr::Ge(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::LogAnd(ref left, ref right) => Expr::LogAnd(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::LogOr(ref left, ref right) => Expr::LogOr(Box::new(left.resolve(name, value)), Box::new(right.resolve(name, value))).folded(),
+            Expr::Reg(reg) => Expr::Reg(reg),
             Expr::Sym(ref symbol) if symbol == name => Expr::Num(value),
             Expr::Sym(ref symbol) => Expr::Sym(symbol.clone()),
             Expr::Str(ref string) => Expr::Str(string.clone()),
@@ -1022,6 +1230,59 @@ mod tests {
         }
     }
 
+    use super::{parse_condition, Register, Environment};
+    #[test]
+    fn conditions_parse_registers_and_dereferences() {
+        let expected = Expr::LogAnd(
+            Box::new(Expr::Eq(
+                Box::new(Expr::Reg(Register::Data(0))),
+                Box::new(Expr::Num(0x10)))),
+            Box::new(Expr::Gt(
+                Box::new(Expr::Deref(
+                    Box::new(Expr::Reg(Register::Address(0))),
+                    Size::Word)),
+                Box::new(Expr::Num(3)))));
+        assert_eq!(Some(expected), parse_condition("D0 == $10 && (A0).W > 3"));
+        assert_eq!(Some(Expr::Reg(Register::Address(7))), parse_condition("sp"));
+        assert_eq!(Some(Expr::Sym("d0x".to_owned())), parse_condition("d0x"));
+        assert_eq!(None, parse_condition("D0 =="));
+    }
+
+    struct Machine;
+    impl Environment for Machine {
+        fn register(&self, reg: Register) -> Option<i32> {
+            match reg {
+                Register::Data(n) => Some(n as i32),
+                Register::Address(n) => Some(0x1000 + n as i32),
+                _ => None
+            }
+        }
+        fn memory(&self, address: u32, size: Size) -> Option<i32> {
+            match size {
+                Size::Byte => Some((address & 0xff) as i32),
+                _ => Some(address as i32 * 2),
+            }
+        }
+    }
+
+    fn condition_holds(condition: &str) -> bool {
+        parse_condition(condition).and_then(|expr| expr.eval_in(&Machine)) == Some(1)
+    }
+
+    #[test]
+    fn conditions_evaluate_in_an_environment() {
+        assert!(condition_holds("D3 == 3"));
+        assert!(condition_holds("D3 != 4 && !(D3 == 4)"));
+        assert!(condition_holds("D1 < D2 || D7 < 2"));
+        assert!(condition_holds("D1 << 2 <= 4 && D2 >> 1 >= 1"));
+        assert!(condition_holds("(A1).W == $2002"));
+        assert!(condition_holds("(A1 + 3).B == 4"));
+        assert!(condition_holds("(D1 + 1) * 2 == 4"));
+        assert!(condition_holds("D7 & 3 == 3"));
+        assert!(!condition_holds("D1 / D0 == 0"));
+        assert!(!condition_holds("PC == 0"));
+    }
+
     use super::Directive;
     #[test]
     fn directive_parsing() {
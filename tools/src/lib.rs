@@ -1,4 +1,4 @@
-#![recursion_limit = "160"] // 150 was too low in rust 1.15
+#![recursion_limit = "320"] // 150 was too low in rust 1.15, 160 too low for conditions
 use std::result;
 mod operand;
 use operand::Operand;